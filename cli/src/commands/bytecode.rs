@@ -0,0 +1,231 @@
+//! TritVM Bytecode Decoder
+//!
+//! A `.tritvm` file is a 14-byte header followed by instructions:
+//!
+//! | Bytes | Field |
+//! |-------|-------|
+//! | 0..6  | magic `TRITVM` |
+//! | 6..10 | format version, u32 little-endian (must be 1) |
+//! | 10..14 | instruction count, u32 little-endian |
+//!
+//! Each instruction is an opcode byte followed by `operand_count` 16-bit
+//! little-endian operands (see [`Opcode::operand_count`]), the same layout
+//! `utils/instructionParser.ts` reads. Decoding stops at the first error,
+//! which carries the file offset it occurred at.
+
+use super::gas_schedule::Opcode;
+use std::fmt;
+
+pub const MAGIC: &[u8; 6] = b"TRITVM";
+pub const SUPPORTED_VERSION: u32 = 1;
+pub const HEADER_LEN: usize = 14;
+
+/// Parsed `.tritvm` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BytecodeHeader {
+    pub version: u32,
+    pub instruction_count: u32,
+}
+
+/// One decoded instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instruction {
+    /// File offset of the opcode byte
+    pub offset: usize,
+    pub opcode: Opcode,
+    pub operands: Vec<u16>,
+}
+
+impl Instruction {
+    /// Offset relative to the first instruction, as jump targets address it
+    pub fn address(&self) -> usize {
+        self.offset - HEADER_LEN
+    }
+
+    /// Encoded length in bytes
+    pub fn size(&self) -> usize {
+        1 + 2 * self.operands.len()
+    }
+}
+
+/// A decoded `.tritvm` program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bytecode {
+    pub header: BytecodeHeader,
+    pub instructions: Vec<Instruction>,
+}
+
+/// Why bytecode could not be decoded; every variant carries a file offset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    TruncatedHeader { len: usize },
+    BadMagic { found: Vec<u8> },
+    UnsupportedVersion { version: u32 },
+    UnknownOpcode { offset: usize, byte: u8 },
+    TruncatedInstruction { offset: usize, opcode: Opcode, needed: usize, available: usize },
+    MissingInstructions { offset: usize, declared: u32, decoded: u32 },
+    TrailingBytes { offset: usize, count: usize },
+}
+
+impl DecodeError {
+    pub fn offset(&self) -> usize {
+        match self {
+            DecodeError::TruncatedHeader { .. } | DecodeError::BadMagic { .. } => 0,
+            DecodeError::UnsupportedVersion { .. } => MAGIC.len(),
+            DecodeError::UnknownOpcode { offset, .. }
+            | DecodeError::TruncatedInstruction { offset, .. }
+            | DecodeError::MissingInstructions { offset, .. }
+            | DecodeError::TrailingBytes { offset, .. } => *offset,
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "offset {:#06x}: ", self.offset())?;
+        match self {
+            DecodeError::TruncatedHeader { len } => {
+                write!(f, "file is {} bytes, shorter than the {}-byte header", len, HEADER_LEN)
+            }
+            DecodeError::BadMagic { found } => {
+                write!(f, "bad magic {:?}, expected \"TRITVM\"", String::from_utf8_lossy(found))
+            }
+            DecodeError::UnsupportedVersion { version } => {
+                write!(f, "unsupported bytecode version {} (expected {})", version, SUPPORTED_VERSION)
+            }
+            DecodeError::UnknownOpcode { byte, .. } => write!(f, "unknown opcode 0x{:02x}", byte),
+            DecodeError::TruncatedInstruction { opcode, needed, available, .. } => write!(
+                f,
+                "{} needs {} operand bytes, only {} left",
+                opcode.mnemonic(),
+                needed,
+                available
+            ),
+            DecodeError::MissingInstructions { declared, decoded, .. } => write!(
+                f,
+                "header declares {} instructions, bytecode ends after {}",
+                declared, decoded
+            ),
+            DecodeError::TrailingBytes { count, .. } => {
+                write!(f, "{} bytes follow the last declared instruction", count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+fn read_u32(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().expect("four header bytes"))
+}
+
+pub fn decode_header(bytes: &[u8]) -> Result<BytecodeHeader, DecodeError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(DecodeError::TruncatedHeader { len: bytes.len() });
+    }
+    if &bytes[..MAGIC.len()] != MAGIC {
+        return Err(DecodeError::BadMagic { found: bytes[..MAGIC.len()].to_vec() });
+    }
+    let version = read_u32(bytes, 6);
+    if version != SUPPORTED_VERSION {
+        return Err(DecodeError::UnsupportedVersion { version });
+    }
+    Ok(BytecodeHeader { version, instruction_count: read_u32(bytes, 10) })
+}
+
+/// Decode a complete `.tritvm` file
+pub fn decode(bytes: &[u8]) -> Result<Bytecode, DecodeError> {
+    let header = decode_header(bytes)?;
+    let mut instructions = Vec::new();
+    let mut offset = HEADER_LEN;
+
+    while instructions.len() < header.instruction_count as usize {
+        if offset >= bytes.len() {
+            return Err(DecodeError::MissingInstructions {
+                offset,
+                declared: header.instruction_count,
+                decoded: instructions.len() as u32,
+            });
+        }
+        let opcode = Opcode::from_byte(bytes[offset]).map_err(|byte| DecodeError::UnknownOpcode { offset, byte })?;
+        let needed = 2 * opcode.operand_count();
+        let available = bytes.len() - offset - 1;
+        if needed > available {
+            return Err(DecodeError::TruncatedInstruction { offset, opcode, needed, available });
+        }
+        let operands = bytes[offset + 1..offset + 1 + needed]
+            .chunks_exact(2)
+            .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        instructions.push(Instruction { offset, opcode, operands });
+        offset += 1 + needed;
+    }
+
+    if offset < bytes.len() {
+        return Err(DecodeError::TrailingBytes { offset, count: bytes.len() - offset });
+    }
+    Ok(Bytecode { header, instructions })
+}
+
+/// Assemble a `.tritvm` file from opcodes and operands (for tests and tooling)
+pub fn encode(instructions: &[(Opcode, &[u16])]) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(SUPPORTED_VERSION.to_le_bytes());
+    bytes.extend((instructions.len() as u32).to_le_bytes());
+    for (opcode, operands) in instructions {
+        bytes.push(opcode.to_byte());
+        for operand in *operands {
+            bytes.extend(operand.to_le_bytes());
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_respects_operand_widths() {
+        // hello-world.tritvm: PUSH 42, POP R1, ADD R2, R1, R1, HALT
+        let bytes = encode(&[
+            (Opcode::Push, &[42]),
+            (Opcode::Pop, &[1]),
+            (Opcode::Add, &[2, 1, 1]),
+            (Opcode::Halt, &[]),
+        ]);
+        let program = decode(&bytes).unwrap();
+        let summary: Vec<(usize, Opcode)> = program.instructions.iter().map(|i| (i.address(), i.opcode)).collect();
+        assert_eq!(summary, vec![(0, Opcode::Push), (3, Opcode::Pop), (6, Opcode::Add), (13, Opcode::Halt)]);
+        assert_eq!(program.instructions[2].operands, vec![2, 1, 1]);
+    }
+
+    #[test]
+    fn test_decode_errors_carry_offsets() {
+        assert!(matches!(decode(b"INVALID_BYTECODE"), Err(DecodeError::BadMagic { .. })));
+        assert_eq!(decode(b"TRITVM"), Err(DecodeError::TruncatedHeader { len: 6 }));
+
+        let mut bad_version = encode(&[]);
+        bad_version[6] = 2;
+        assert_eq!(decode(&bad_version), Err(DecodeError::UnsupportedVersion { version: 2 }));
+
+        let mut unknown = encode(&[(Opcode::Nop, &[]), (Opcode::Nop, &[])]);
+        unknown[15] = 0xFF;
+        let err = decode(&unknown).unwrap_err();
+        assert_eq!(err, DecodeError::UnknownOpcode { offset: 15, byte: 0xFF });
+        assert_eq!(err.to_string(), "offset 0x000f: unknown opcode 0xff");
+
+        let mut truncated = encode(&[(Opcode::Load, &[1, 2])]);
+        truncated.pop();
+        assert_eq!(
+            decode(&truncated),
+            Err(DecodeError::TruncatedInstruction { offset: 14, opcode: Opcode::Load, needed: 4, available: 3 })
+        );
+
+        let mut short = encode(&[(Opcode::Halt, &[])]);
+        short[10] = 2;
+        assert!(matches!(decode(&short), Err(DecodeError::MissingInstructions { offset: 15, declared: 2, decoded: 1 })));
+        short[10] = 0;
+        assert_eq!(decode(&short), Err(DecodeError::TrailingBytes { offset: 14, count: 1 }));
+    }
+}
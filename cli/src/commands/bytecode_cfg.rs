@@ -0,0 +1,516 @@
+//! Worst-Case Gas Analysis
+//!
+//! Builds a control-flow graph over decoded `.tritvm` instructions and prices
+//! every function entry point (instruction 0 and each CALL target):
+//!
+//! * **min** - cheapest path from the entry to a RET, HALT, REVERT, RETURN or
+//!   the end of the code
+//! * **expected** - each conditional jump taken half the time
+//! * **max** - most expensive such path; unbounded if the function (or
+//!   anything it calls) contains a loop or recursion
+//!
+//! Jump and call targets are byte addresses relative to the first
+//! instruction ([`Instruction::address`]). A target that is not an
+//! instruction boundary ends the path with a fault and is reported.
+
+use super::bytecode::Instruction;
+use super::gas_schedule::{GasMeter, Opcode};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::cmp::Reverse;
+
+/// Where control goes after an instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edge {
+    /// Instruction index within the program
+    To(usize),
+    /// Function or program ends here
+    Exit,
+    /// Jump to an address that is not an instruction
+    Fault,
+}
+
+/// A jump or call whose target is not an instruction boundary
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InvalidTarget {
+    pub address: usize,
+    pub opcode: &'static str,
+    pub target: usize,
+}
+
+/// Gas bounds of one function entry point
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionGas {
+    pub entry: usize,
+    pub name: String,
+    /// `None` if no path returns
+    pub min_gas: Option<u64>,
+    /// Conditional jumps taken half the time; `None` if it never returns
+    pub expected_gas: Option<f64>,
+    /// `None` if unbounded
+    pub max_gas: Option<u64>,
+    /// Addresses of loop headers with no inferred bound
+    pub unbounded_loops: Vec<usize>,
+    /// Part of, or calls into, a recursive call chain
+    pub recursive: bool,
+}
+
+/// Path-sensitive gas bounds of a whole program
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WorstCase {
+    pub functions: Vec<FunctionGas>,
+    pub invalid_targets: Vec<InvalidTarget>,
+}
+
+impl WorstCase {
+    /// Bounds of the program entry (instruction 0)
+    pub fn main(&self) -> Option<&FunctionGas> {
+        self.functions.iter().find(|f| f.entry == 0)
+    }
+
+    /// Worst-case execution gas of the program, `None` if unbounded
+    pub fn max_gas(&self) -> Option<u64> {
+        self.main().map_or(Some(0), |main| main.max_gas)
+    }
+}
+
+/// Execution gas of one instruction, homomorphic ops at the default ciphertext size
+pub fn instruction_gas(opcode: Opcode) -> u64 {
+    if GasMeter::is_homomorphic_op(&opcode) {
+        GasMeter::calculate_homomorphic_gas(&opcode, super::gas_schedule::DEFAULT_CIPHERTEXT_TRITS)
+    } else {
+        GasMeter::get_opcode_cost(&opcode)
+    }
+}
+
+/// Per-function costs while the call graph is being walked
+#[derive(Debug, Clone, Copy)]
+struct Cost {
+    min: Option<u64>,
+    expected: Option<f64>,
+    max: Option<u64>,
+}
+
+struct Graph<'a> {
+    instructions: &'a [Instruction],
+    by_address: BTreeMap<usize, usize>,
+    end_address: usize,
+    invalid: Vec<InvalidTarget>,
+}
+
+impl<'a> Graph<'a> {
+    fn new(instructions: &'a [Instruction]) -> Self {
+        let by_address = instructions.iter().enumerate().map(|(i, instr)| (instr.address(), i)).collect();
+        let end_address = instructions.last().map_or(0, |i| i.address() + i.size());
+        Graph { instructions, by_address, end_address, invalid: Vec::new() }
+    }
+
+    fn target(&mut self, index: usize, operand: u16) -> Edge {
+        let target = operand as usize;
+        match self.by_address.get(&target) {
+            Some(&i) => Edge::To(i),
+            None if target == self.end_address => Edge::Exit,
+            None => {
+                let instr = &self.instructions[index];
+                let invalid = InvalidTarget { address: instr.address(), opcode: instr.opcode.mnemonic(), target };
+                if !self.invalid.contains(&invalid) {
+                    self.invalid.push(invalid);
+                }
+                Edge::Fault
+            }
+        }
+    }
+
+    fn next(&self, index: usize) -> Edge {
+        if index + 1 < self.instructions.len() { Edge::To(index + 1) } else { Edge::Exit }
+    }
+
+    /// Intra-function successors; a CALL continues at the next instruction
+    fn successors(&mut self, index: usize) -> Vec<Edge> {
+        let instr = &self.instructions[index];
+        let operands = instr.operands.clone();
+        match instr.opcode {
+            Opcode::Jmp => vec![self.target(index, operands[0])],
+            Opcode::Jeq | Opcode::Jne | Opcode::Jlt | Opcode::Jgt | Opcode::Jle | Opcode::Jge => {
+                vec![self.target(index, operands[2]), self.next(index)]
+            }
+            Opcode::Call => match self.target(index, operands[0]) {
+                Edge::To(_) => vec![self.next(index)],
+                // Calling a bad target faults
+                other => vec![other],
+            },
+            Opcode::Ret | Opcode::Halt | Opcode::Revert | Opcode::Return => vec![Edge::Exit],
+            _ => vec![self.next(index)],
+        }
+    }
+
+    /// Callee entry of a CALL instruction
+    fn callee(&mut self, index: usize) -> Option<Edge> {
+        let instr = &self.instructions[index];
+        (instr.opcode == Opcode::Call).then(|| instr.operands[0]).map(|operand| self.target(index, operand))
+    }
+}
+
+struct Analysis<'a> {
+    graph: Graph<'a>,
+    done: BTreeMap<usize, (Cost, FunctionGas)>,
+    active: BTreeSet<usize>,
+}
+
+impl Analysis<'_> {
+    fn function(&mut self, entry: usize) -> Cost {
+        if let Some((cost, _)) = self.done.get(&entry) {
+            return *cost;
+        }
+        if !self.active.insert(entry) {
+            // Recursion: the callee's cost depends on itself
+            return Cost { min: None, expected: None, max: None };
+        }
+
+        // Instructions reachable inside the function
+        let mut succs: BTreeMap<usize, Vec<Edge>> = BTreeMap::new();
+        let mut pending = vec![entry];
+        while let Some(index) = pending.pop() {
+            if succs.contains_key(&index) {
+                continue;
+            }
+            let edges = self.graph.successors(index);
+            pending.extend(edges.iter().filter_map(|e| match e { Edge::To(i) => Some(*i), _ => None }));
+            succs.insert(index, edges);
+        }
+
+        // Node costs include the full cost of any callee
+        let mut recursive = false;
+        let mut costs: BTreeMap<usize, Cost> = BTreeMap::new();
+        for &index in succs.keys() {
+            let own = instruction_gas(self.graph.instructions[index].opcode);
+            let cost = match self.graph.callee(index) {
+                Some(Edge::To(callee)) => {
+                    let cycle = self.active.contains(&callee);
+                    let c = self.function(callee);
+                    recursive |= cycle || self.done.get(&callee).is_some_and(|(_, f)| f.recursive);
+                    Cost { min: c.min.map(|g| g + own), expected: c.expected.map(|g| g + own as f64), max: c.max.map(|g| g + own) }
+                }
+                _ => Cost { min: Some(own), expected: Some(own as f64), max: Some(own) },
+            };
+            costs.insert(index, cost);
+        }
+
+        let loops = back_edge_targets(entry, &succs);
+        let components = components(entry, &succs);
+        let cost = Cost {
+            min: min_gas(entry, &succs, &costs),
+            expected: expected_gas(entry, &succs, &costs, &components),
+            max: if loops.is_empty() { max_gas(entry, &succs, &costs, &components) } else { None },
+        };
+
+        self.active.remove(&entry);
+        let function = FunctionGas {
+            entry: self.graph.instructions[entry].address(),
+            name: if entry == 0 { "main".to_string() } else { format!("fn@{:#06x}", self.graph.instructions[entry].address()) },
+            min_gas: cost.min,
+            expected_gas: cost.expected,
+            max_gas: cost.max,
+            unbounded_loops: loops.into_iter().map(|i| self.graph.instructions[i].address()).collect(),
+            recursive,
+        };
+        self.done.insert(entry, (cost, function));
+        cost
+    }
+}
+
+/// Loop headers: targets of DFS back edges
+fn back_edge_targets(entry: usize, succs: &BTreeMap<usize, Vec<Edge>>) -> Vec<usize> {
+    let mut headers = BTreeSet::new();
+    let mut state: BTreeMap<usize, bool> = BTreeMap::new(); // false = on stack, true = finished
+    let mut stack = vec![(entry, 0usize)];
+    state.insert(entry, false);
+    while let Some((node, child)) = stack.pop() {
+        match succs[&node].get(child) {
+            Some(Edge::To(next)) => {
+                stack.push((node, child + 1));
+                match state.get(next) {
+                    Some(false) => {
+                        headers.insert(*next);
+                    }
+                    Some(true) => {}
+                    None => {
+                        state.insert(*next, false);
+                        stack.push((*next, 0));
+                    }
+                }
+            }
+            Some(_) => stack.push((node, child + 1)),
+            None => {
+                state.insert(node, true);
+            }
+        }
+    }
+    headers.into_iter().collect()
+}
+
+/// Cheapest path to an exit (Dijkstra; nodes of unknown cost are impassable)
+fn min_gas(entry: usize, succs: &BTreeMap<usize, Vec<Edge>>, costs: &BTreeMap<usize, Cost>) -> Option<u64> {
+    let mut settled = BTreeSet::new();
+    let mut queue = BinaryHeap::from([Reverse((costs[&entry].min?, entry))]);
+    while let Some(Reverse((gas, node))) = queue.pop() {
+        if !settled.insert(node) {
+            continue;
+        }
+        for edge in &succs[&node] {
+            match edge {
+                Edge::Exit | Edge::Fault => return Some(gas),
+                Edge::To(next) if !settled.contains(next) => {
+                    if let Some(cost) = costs[next].min {
+                        queue.push(Reverse((gas + cost, *next)));
+                    }
+                }
+                Edge::To(_) => {}
+            }
+        }
+    }
+    None
+}
+
+/// Strongly connected components of the function body, each one after every
+/// component it can reach (Tarjan's algorithm, iterative)
+fn components(entry: usize, succs: &BTreeMap<usize, Vec<Edge>>) -> Vec<Vec<usize>> {
+    let targets = |node: usize| succs[&node].iter().filter_map(|e| if let Edge::To(i) = e { Some(*i) } else { None });
+    let mut order: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut low: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut stack = Vec::new();
+    let mut on_stack = BTreeSet::new();
+    let mut components = Vec::new();
+    let mut work = vec![(entry, 0usize)];
+    while let Some((node, child)) = work.pop() {
+        if child == 0 {
+            order.insert(node, order.len());
+            low.insert(node, order[&node]);
+            stack.push(node);
+            on_stack.insert(node);
+        }
+        if let Some(next) = targets(node).nth(child) {
+            work.push((node, child + 1));
+            if !order.contains_key(&next) {
+                work.push((next, 0));
+            } else if on_stack.contains(&next) {
+                low.insert(node, low[&node].min(order[&next]));
+            }
+            continue;
+        }
+        if low[&node] == order[&node] {
+            let mut component = Vec::new();
+            while let Some(member) = stack.pop() {
+                on_stack.remove(&member);
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            components.push(component);
+        }
+        if let Some(&(parent, _)) = work.last() {
+            low.insert(parent, low[&parent].min(low[&node]));
+        }
+    }
+    components
+}
+
+/// Longest path to an exit over an acyclic function body
+fn max_gas(
+    entry: usize,
+    succs: &BTreeMap<usize, Vec<Edge>>,
+    costs: &BTreeMap<usize, Cost>,
+    components: &[Vec<usize>],
+) -> Option<u64> {
+    let mut longest: BTreeMap<usize, Option<u64>> = BTreeMap::new();
+    // Acyclic, so every component is one node and its successors come first
+    for &node in components.iter().flatten() {
+        let mut rest = Some(0);
+        for edge in &succs[&node] {
+            let tail = match edge {
+                Edge::Exit | Edge::Fault => Some(0),
+                Edge::To(next) => longest[next],
+            };
+            rest = match (rest, tail) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                _ => None,
+            };
+        }
+        longest.insert(node, rest.and_then(|rest| costs[&node].max.map(|own| own + rest)));
+    }
+    longest[&entry]
+}
+
+/// Own cost plus the average of the successors' values; `None` if any is unknown
+fn expected_at(
+    node: usize,
+    succs: &BTreeMap<usize, Vec<Edge>>,
+    costs: &BTreeMap<usize, Cost>,
+    value: &BTreeMap<usize, Option<f64>>,
+) -> Option<f64> {
+    let edges = &succs[&node];
+    let mut rest = 0.0;
+    for edge in edges {
+        if let Edge::To(next) = edge {
+            rest += value.get(next).copied().flatten()?;
+        }
+    }
+    Some(costs[&node].expected? + rest / edges.len() as f64)
+}
+
+/// Expected gas with every conditional jump taken with probability 1/2;
+/// `None` if it diverges (no exit, or unknown costs)
+///
+/// Components are solved successors first: acyclic code in one pass, and
+/// each loop by value iteration over its own nodes only.
+fn expected_gas(
+    entry: usize,
+    succs: &BTreeMap<usize, Vec<Edge>>,
+    costs: &BTreeMap<usize, Cost>,
+    components: &[Vec<usize>],
+) -> Option<f64> {
+    /// Node updates allowed per loop before giving up on convergence
+    const MAX_UPDATES: usize = 10_000_000;
+    const DIVERGED: f64 = 1e18;
+    let mut value: BTreeMap<usize, Option<f64>> = BTreeMap::new();
+    for component in components {
+        let node = component[0];
+        if component.len() == 1 && !succs[&node].contains(&Edge::To(node)) {
+            value.insert(node, expected_at(node, succs, costs, &value));
+            continue;
+        }
+
+        // Later instructions first, so values flow back through the loop body in one sweep
+        let mut members = component.clone();
+        members.sort_unstable_by(|a, b| b.cmp(a));
+        let leaves = members.iter().any(|n| succs[n].iter().any(|e| !matches!(e, Edge::To(next) if component.contains(next))));
+        let solved = if leaves { solve_loop(&members, succs, costs, &mut value, MAX_UPDATES, DIVERGED) } else { false };
+        if !solved {
+            for &member in &members {
+                value.insert(member, None);
+            }
+        }
+    }
+    value[&entry]
+}
+
+/// Gauss-Seidel iteration over one loop; false if it diverges or does not settle
+fn solve_loop(
+    members: &[usize],
+    succs: &BTreeMap<usize, Vec<Edge>>,
+    costs: &BTreeMap<usize, Cost>,
+    value: &mut BTreeMap<usize, Option<f64>>,
+    max_updates: usize,
+    diverged: f64,
+) -> bool {
+    for &member in members {
+        value.insert(member, Some(0.0));
+    }
+    for _ in 0..(max_updates / members.len()).max(1) {
+        let (mut delta, mut scale) = (0.0f64, 1.0f64);
+        for &node in members {
+            let Some(updated) = expected_at(node, succs, costs, value) else { return false };
+            let previous = value.insert(node, Some(updated)).flatten().unwrap_or(0.0);
+            delta = delta.max((updated - previous).abs());
+            scale = scale.max(updated);
+        }
+        if scale > diverged {
+            return false;
+        }
+        if delta < 1e-9 * scale {
+            return true;
+        }
+    }
+    false
+}
+
+/// Analyze every function entry point of a decoded program
+pub fn analyze(instructions: &[Instruction]) -> WorstCase {
+    if instructions.is_empty() {
+        return WorstCase { functions: Vec::new(), invalid_targets: Vec::new() };
+    }
+    let mut analysis = Analysis { graph: Graph::new(instructions), done: BTreeMap::new(), active: BTreeSet::new() };
+    analysis.function(0);
+    // Called functions are analyzed from main; also pick up CALL targets in unreachable code
+    for index in 0..instructions.len() {
+        if let Some(Edge::To(callee)) = analysis.graph.callee(index) {
+            analysis.function(callee);
+        }
+    }
+    let mut invalid_targets = analysis.graph.invalid;
+    invalid_targets.sort_by_key(|t| t.address);
+    WorstCase {
+        functions: analysis.done.into_values().map(|(_, f)| f).collect(),
+        invalid_targets,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::bytecode::{decode, encode};
+
+    fn worst_case(program: &[(Opcode, &[u16])]) -> WorstCase {
+        analyze(&decode(&encode(program)).unwrap().instructions)
+    }
+
+    #[test]
+    fn test_branches_give_min_expected_max() {
+        // 0: JEQ R1, R2, 14 | 7: DIV R1, R1, R2 | 14: HALT
+        let wc = worst_case(&[(Opcode::Jeq, &[1, 2, 14]), (Opcode::Div, &[1, 1, 2]), (Opcode::Halt, &[])]);
+        let main = wc.main().unwrap();
+        assert_eq!((main.min_gas, main.max_gas), (Some(2), Some(5)));
+        assert!((main.expected_gas.unwrap() - 3.5).abs() < 1e-6);
+        assert!(main.unbounded_loops.is_empty() && wc.invalid_targets.is_empty());
+    }
+
+    #[test]
+    fn test_calls_add_callee_cost() {
+        // 0: CALL 4 | 3: HALT | 4: MUL R1, R1, R1 | 11: RET
+        let wc = worst_case(&[(Opcode::Call, &[4]), (Opcode::Halt, &[]), (Opcode::Mul, &[1, 1, 1]), (Opcode::Ret, &[])]);
+        let names: Vec<&str> = wc.functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["main", "fn@0x0004"]);
+        assert_eq!(wc.functions[1].max_gas, Some(5));
+        assert_eq!(wc.max_gas(), Some(5 + 5));
+    }
+
+    #[test]
+    fn test_loops_recursion_and_bad_targets() {
+        // 0: DEC R1 | 3: JNE R1, R0, 0 | 10: HALT
+        let looped = worst_case(&[(Opcode::Dec, &[1]), (Opcode::Jne, &[1, 0, 0]), (Opcode::Halt, &[])]);
+        let main = looped.main().unwrap();
+        assert_eq!((main.min_gas, main.max_gas), (Some(3), None));
+        assert_eq!(main.unbounded_loops, vec![0]);
+        // Loop body (3 gas) runs twice on average
+        assert!((main.expected_gas.unwrap() - 6.0).abs() < 1e-6);
+
+        let recursive = worst_case(&[(Opcode::Call, &[0]), (Opcode::Ret, &[])]);
+        assert!(recursive.main().unwrap().recursive);
+        assert_eq!(recursive.max_gas(), None);
+
+        let bad = worst_case(&[(Opcode::Jmp, &[1]), (Opcode::Halt, &[])]);
+        assert_eq!(bad.invalid_targets, vec![InvalidTarget { address: 0, opcode: "JMP", target: 1 }]);
+    }
+
+    #[test]
+    fn test_long_bodies_are_linear() {
+        // 100k NOPs, then the same behind a DEC/JNE loop at the end
+        let mut program: Vec<(Opcode, &[u16])> = vec![(Opcode::Nop, &[]); 100_000];
+        let straight = worst_case(&program);
+        let main = straight.main().unwrap();
+        assert_eq!((main.min_gas, main.max_gas), (Some(100_000), Some(100_000)));
+        assert!((main.expected_gas.unwrap() - 100_000.0).abs() < 1e-3);
+
+        program.truncate(20_000);
+        program.extend([(Opcode::Dec, &[1][..]), (Opcode::Jne, &[1, 0, 20_000]), (Opcode::Halt, &[])]);
+        let looped = worst_case(&program);
+        let main = looped.main().unwrap();
+        assert_eq!(main.max_gas, None);
+        assert!((main.expected_gas.unwrap() - 20_006.0).abs() < 1e-3);
+
+        // A loop with no way out never returns
+        let stuck = worst_case(&[(Opcode::Nop, &[]), (Opcode::Jmp, &[0])]);
+        assert_eq!(stuck.main().unwrap().expected_gas, None);
+    }
+}
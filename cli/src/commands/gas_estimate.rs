@@ -3,13 +3,13 @@
 //! Provides gas estimation tools for TritLang contracts, transactions, and homomorphic operations.
 //! Includes tier selection advisor, compression savings calculator, and detailed cost breakdowns.
 
-use trinaryvm_runtime::{
-    gas_meter::GasMeter,
-    homomorphic_gas::{HomomorphicGasMeter, HomomorphicOperation},
-    gas_tiers::GasTier,
-    instruction::Opcode,
-    testnet_config::TestnetConfig,
+use super::bytecode::{decode, DecodeError};
+use super::bytecode_cfg::{self, WorstCase};
+use super::gas_schedule::{
+    GasMeter, GasTier, HomomorphicGasMeter, HomomorphicOperation, Opcode, TestnetConfig,
+    DEFAULT_CIPHERTEXT_TRITS,
 };
+use std::collections::BTreeMap;
 
 /// Gas estimation result
 #[derive(Debug, Clone, serde::Serialize)]
@@ -28,6 +28,9 @@ pub struct GasEstimate {
     pub opcode_breakdown: Vec<OpcodeEstimate>,
     /// Compression savings (if tetragram-encoded)
     pub compression_savings: Option<CompressionSavings>,
+    /// Path-sensitive bounds (with `--worst-case`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worst_case: Option<WorstCase>,
 }
 
 /// Gas estimate for a specific opcode
@@ -61,39 +64,26 @@ impl GasEstimator {
     }
     
     /// Estimate gas for TritLang contract bytecode
-    pub fn estimate_contract(&self, bytecode: &[u8], is_tetragram_compressed: bool) -> GasEstimate {
+    pub fn estimate_contract(&self, bytecode: &[u8], is_tetragram_compressed: bool) -> Result<GasEstimate, DecodeError> {
         // Calculate intrinsic gas
         let intrinsic_gas = GasMeter::calculate_intrinsic_gas(bytecode, is_tetragram_compressed);
         
-        // Parse bytecode and estimate execution gas
+        // Decode header and instructions, then price each instruction once
+        let program = decode(bytecode)?;
         let mut execution_gas = 0u64;
         let mut homomorphic_gas = 0u64;
-        let mut opcode_counts: std::collections::HashMap<Opcode, u64> = std::collections::HashMap::new();
+        let mut opcode_counts: BTreeMap<Opcode, u64> = BTreeMap::new();
         
-        // Simple bytecode parsing (simplified - real implementation would be more sophisticated)
-        let mut offset = 0;
-        while offset < bytecode.len() {
-            if let Ok(opcode) = Opcode::from_byte(bytecode[offset]) {
-                let gas_cost = GasMeter::get_opcode_cost(&opcode);
-                
-                // Check if homomorphic operation
-                if GasMeter::is_homomorphic_op(&opcode) {
-                    // Estimate data size (default to 2187 trits)
-                    let data_size = 2187;
-                    let he_gas = GasMeter::calculate_homomorphic_gas(&opcode, data_size);
-                    homomorphic_gas += he_gas;
-                    execution_gas += he_gas;
-                } else {
-                    execution_gas += gas_cost;
-                }
-                
-                *opcode_counts.entry(opcode).or_insert(0) += 1;
-                
-                // Move to next instruction (simplified - real parsing would handle args)
-                offset += 1;
+        for instruction in &program.instructions {
+            let opcode = instruction.opcode;
+            if GasMeter::is_homomorphic_op(&opcode) {
+                let he_gas = GasMeter::calculate_homomorphic_gas(&opcode, DEFAULT_CIPHERTEXT_TRITS);
+                homomorphic_gas += he_gas;
+                execution_gas += he_gas;
             } else {
-                offset += 1; // Skip invalid bytes
+                execution_gas += GasMeter::get_opcode_cost(&opcode);
             }
+            *opcode_counts.entry(opcode).or_insert(0) += 1;
         }
         
         // Build opcode breakdown
@@ -101,12 +91,12 @@ impl GasEstimator {
             .iter()
             .map(|(opcode, count)| {
                 let gas = if GasMeter::is_homomorphic_op(opcode) {
-                    GasMeter::calculate_homomorphic_gas(opcode, 2187) // Default size
+                    GasMeter::calculate_homomorphic_gas(opcode, DEFAULT_CIPHERTEXT_TRITS)
                 } else {
                     GasMeter::get_opcode_cost(opcode)
                 };
                 OpcodeEstimate {
-                    opcode: format!("{:?}", opcode),
+                    opcode: opcode.mnemonic().to_string(),
                     count: *count,
                     total_gas: gas * count,
                 }
@@ -135,7 +125,7 @@ impl GasEstimator {
         let total_gas = intrinsic_gas + execution_gas;
         let recommended_tier = GasTier::from_gas_limit(total_gas);
         
-        GasEstimate {
+        Ok(GasEstimate {
             total_gas,
             recommended_tier: format!("{:?}", recommended_tier),
             intrinsic_gas,
@@ -143,7 +133,20 @@ impl GasEstimator {
             homomorphic_gas,
             opcode_breakdown,
             compression_savings,
-        }
+            worst_case: None,
+        })
+    }
+    
+    /// Like `estimate_contract`, plus per-function min/expected/max gas over the
+    /// control-flow graph; the tier is chosen for the worst path
+    pub fn estimate_worst_case(&self, bytecode: &[u8], is_tetragram_compressed: bool) -> Result<GasEstimate, DecodeError> {
+        let mut estimate = self.estimate_contract(bytecode, is_tetragram_compressed)?;
+        let worst_case = bytecode_cfg::analyze(&decode(bytecode)?.instructions);
+        // An unbounded program needs the largest tier
+        let worst_gas = worst_case.max_gas().map_or(u64::MAX, |gas| estimate.intrinsic_gas + gas);
+        estimate.recommended_tier = format!("{:?}", self.recommend_tier(worst_gas));
+        estimate.worst_case = Some(worst_case);
+        Ok(estimate)
     }
     
     /// Estimate gas for a specific homomorphic operation
@@ -155,6 +158,28 @@ impl GasEstimator {
         HomomorphicGasMeter::calculate_gas_cost(operation, data_size_trits)
     }
     
+    /// Estimate a single homomorphic operation as a transaction with no payload
+    pub fn estimate_operation(&self, operation: HomomorphicOperation, data_size_trits: usize) -> GasEstimate {
+        let homomorphic_gas = self.estimate_homomorphic_op(operation, data_size_trits);
+        let intrinsic_gas = GasMeter::calculate_intrinsic_gas(&[], false);
+        let total_gas = intrinsic_gas + homomorphic_gas;
+        
+        GasEstimate {
+            total_gas,
+            recommended_tier: format!("{:?}", self.recommend_tier(total_gas)),
+            intrinsic_gas,
+            execution_gas: homomorphic_gas,
+            homomorphic_gas,
+            opcode_breakdown: vec![OpcodeEstimate {
+                opcode: format!("{:?}", operation),
+                count: 1,
+                total_gas: homomorphic_gas,
+            }],
+            compression_savings: None,
+            worst_case: None,
+        }
+    }
+    
     /// Calculate tetragram compression savings
     pub fn calculate_compression_savings(&self, data: &[u8]) -> CompressionSavings {
         let original_gas = GasMeter::calculate_intrinsic_gas(data, false);
@@ -224,35 +249,62 @@ pub fn format_gas_estimate(estimate: &GasEstimate) -> String {
     if !estimate.opcode_breakdown.is_empty() {
         output.push_str("\n📊 Opcode Breakdown:\n");
         for op_est in &estimate.opcode_breakdown {
-            let gas_per_op = if op_est.count > 0 {
-                op_est.total_gas / op_est.count
-            } else {
-                0
-            };
+            let gas_per_op = op_est.total_gas.checked_div(op_est.count).unwrap_or(0);
             output.push_str(&format!("  {}: {} ops × {} gas = {} gas\n",
                 op_est.opcode, op_est.count, gas_per_op, op_est.total_gas));
         }
     }
     
+    if let Some(ref worst_case) = estimate.worst_case {
+        let show = |gas: Option<u64>| gas.map_or("unbounded".to_string(), |g| g.to_string());
+        output.push_str("\n🧭 Worst-Case Paths (tier covers the worst path):\n");
+        for function in &worst_case.functions {
+            output.push_str(&format!("  {} @ {:#06x}: min {}, expected {}, max {}\n",
+                function.name,
+                function.entry,
+                function.min_gas.map_or("never returns".to_string(), |g| g.to_string()),
+                function.expected_gas.map_or("n/a".to_string(), |g| format!("{:.1}", g)),
+                show(function.max_gas)));
+            for header in &function.unbounded_loops {
+                output.push_str(&format!("    ⚠️  Unbounded loop at {:#06x}\n", header));
+            }
+            if function.recursive {
+                output.push_str("    ⚠️  Recursive call chain\n");
+            }
+        }
+        for invalid in &worst_case.invalid_targets {
+            output.push_str(&format!("  ❌ {} at {:#06x} targets {:#06x}, not an instruction boundary\n",
+                invalid.opcode, invalid.address, invalid.target));
+        }
+    }
+    
     output
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::commands::bytecode::encode;
     
     #[test]
     fn test_gas_estimation() {
         let estimator = GasEstimator::new();
-        let bytecode = vec![
-            Opcode::Nop.to_byte(),
-            Opcode::Add.to_byte(),
-            Opcode::Mul.to_byte(),
-        ];
+        let bytecode = encode(&[
+            (Opcode::Nop, &[]),
+            (Opcode::Add, &[1, 2, 3]),
+            (Opcode::Mul, &[1, 2, 3]),
+        ]);
         
-        let estimate = estimator.estimate_contract(&bytecode, false);
+        let estimate = estimator.estimate_contract(&bytecode, false).unwrap();
         assert!(estimate.total_gas > 0);
         assert!(estimate.intrinsic_gas > 0);
+        // Operand bytes are not counted as opcodes: NOP 1 + ADD 1 + MUL 2
+        assert_eq!(estimate.execution_gas, 4);
+        let counts: Vec<(&str, u64)> = estimate.opcode_breakdown.iter().map(|o| (o.opcode.as_str(), o.count)).collect();
+        assert_eq!(counts, vec![("NOP", 1), ("ADD", 1), ("MUL", 1)]);
+        
+        let err = estimator.estimate_contract(&bytecode[..bytecode.len() - 1], false).unwrap_err();
+        assert_eq!(err.offset(), 22);
     }
     
     #[test]
@@ -279,6 +331,31 @@ mod tests {
         assert!(savings.savings_percent > 0.0);
     }
     
+    #[test]
+    fn test_operation_estimate() {
+        let estimator = GasEstimator::new();
+        let estimate = estimator.estimate_operation(HomomorphicOperation::HEMultiply, 5000);
+        assert_eq!(estimate.homomorphic_gas, 273);
+        assert_eq!(estimate.total_gas, estimate.intrinsic_gas + 273);
+        assert_eq!(estimate.recommended_tier, "Tier3");
+        
+        let json = serde_json::to_value(&estimate).unwrap();
+        assert_eq!(json["opcode_breakdown"][0]["opcode"], "HEMultiply");
+    }
+    
+    #[test]
+    fn test_worst_case_drives_tier() {
+        let estimator = GasEstimator::new();
+        // 0: HMUL R1, R1, R1 | 7: JMP 0 -- linear sum is small, the loop is unbounded
+        let looped = encode(&[(Opcode::HMul, &[1, 1, 1]), (Opcode::Jmp, &[0])]);
+        let linear = estimator.estimate_contract(&looped, false).unwrap();
+        assert_eq!(linear.recommended_tier, "Tier3");
+        let worst = estimator.estimate_worst_case(&looped, false).unwrap();
+        assert_eq!(worst.recommended_tier, "Tier9");
+        assert_eq!(worst.recommended_tier.parse::<GasTier>(), Ok(GasTier::Tier9));
+        assert!(format_gas_estimate(&worst).contains("Unbounded loop at 0x0000"));
+    }
+    
     #[test]
     fn test_tier_recommendation() {
         let estimator = GasEstimator::new();
@@ -0,0 +1,304 @@
+//! Gas Schedule
+//!
+//! The TritVM instruction set and gas rules `gas-estimate` prices bytecode
+//! with, so estimates work without the private runtime installed. Opcode
+//! bytes, operand counts and per-instruction costs follow the CLI's
+//! instruction table (`utils/instructionParser.ts`); homomorphic operations
+//! are priced per kilotrit of ciphertext on top of a Tesla 3-6-9 base.
+
+use serde::Serialize;
+
+/// TritVM bytecode opcode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[repr(u8)]
+pub enum Opcode {
+    Nop = 0x00,
+    // Arithmetic
+    Add = 0x01,
+    Sub = 0x02,
+    Mul = 0x03,
+    Div = 0x04,
+    Mod = 0x05,
+    Neg = 0x06,
+    Inc = 0x07,
+    Dec = 0x08,
+    // Control flow
+    Jmp = 0x10,
+    Jeq = 0x11,
+    Jne = 0x12,
+    Jlt = 0x13,
+    Jgt = 0x14,
+    Jle = 0x15,
+    Jge = 0x16,
+    Call = 0x17,
+    Ret = 0x18,
+    // Data movement
+    Push = 0x20,
+    Pop = 0x21,
+    Load = 0x22,
+    Store = 0x23,
+    Mov = 0x24,
+    Swap = 0x25,
+    // System
+    Halt = 0x30,
+    Gas = 0x31,
+    Revert = 0x32,
+    Return = 0x33,
+    // Ternary logic
+    TAnd = 0x40,
+    TOr = 0x41,
+    TXor = 0x42,
+    TNot = 0x43,
+    TShift = 0x44,
+    TRotate = 0x45,
+    // Cryptographic
+    Hash = 0x50,
+    Verify = 0x51,
+    Encrypt = 0x52,
+    Decrypt = 0x53,
+    // Homomorphic
+    HAdd = 0x60,
+    HMul = 0x61,
+    HEnc = 0x62,
+    HDec = 0x63,
+}
+
+impl Opcode {
+    /// Every opcode, in byte order
+    pub const ALL: &'static [Opcode] = &[
+        Opcode::Nop, Opcode::Add, Opcode::Sub, Opcode::Mul, Opcode::Div, Opcode::Mod, Opcode::Neg, Opcode::Inc,
+        Opcode::Dec, Opcode::Jmp, Opcode::Jeq, Opcode::Jne, Opcode::Jlt, Opcode::Jgt, Opcode::Jle, Opcode::Jge,
+        Opcode::Call, Opcode::Ret, Opcode::Push, Opcode::Pop, Opcode::Load, Opcode::Store, Opcode::Mov,
+        Opcode::Swap, Opcode::Halt, Opcode::Gas, Opcode::Revert, Opcode::Return, Opcode::TAnd, Opcode::TOr,
+        Opcode::TXor, Opcode::TNot, Opcode::TShift, Opcode::TRotate, Opcode::Hash, Opcode::Verify,
+        Opcode::Encrypt, Opcode::Decrypt, Opcode::HAdd, Opcode::HMul, Opcode::HEnc, Opcode::HDec,
+    ];
+
+    /// Decode an opcode byte; unknown bytes are returned as the error
+    pub fn from_byte(byte: u8) -> Result<Self, u8> {
+        Self::ALL.iter().copied().find(|op| *op as u8 == byte).ok_or(byte)
+    }
+
+    pub fn to_byte(self) -> u8 {
+        self as u8
+    }
+
+    /// Assembly mnemonic, operand count and base gas cost
+    fn info(self) -> (&'static str, usize, u64) {
+        match self {
+            Opcode::Nop => ("NOP", 0, 1),
+            Opcode::Add => ("ADD", 3, 1),
+            Opcode::Sub => ("SUB", 3, 1),
+            Opcode::Mul => ("MUL", 3, 2),
+            Opcode::Div => ("DIV", 3, 3),
+            Opcode::Mod => ("MOD", 3, 3),
+            Opcode::Neg => ("NEG", 2, 1),
+            Opcode::Inc => ("INC", 1, 1),
+            Opcode::Dec => ("DEC", 1, 1),
+            Opcode::Jmp => ("JMP", 1, 2),
+            Opcode::Jeq => ("JEQ", 3, 2),
+            Opcode::Jne => ("JNE", 3, 2),
+            Opcode::Jlt => ("JLT", 3, 2),
+            Opcode::Jgt => ("JGT", 3, 2),
+            Opcode::Jle => ("JLE", 3, 2),
+            Opcode::Jge => ("JGE", 3, 2),
+            Opcode::Call => ("CALL", 1, 5),
+            Opcode::Ret => ("RET", 0, 3),
+            Opcode::Push => ("PUSH", 1, 1),
+            Opcode::Pop => ("POP", 1, 1),
+            Opcode::Load => ("LOAD", 2, 3),
+            Opcode::Store => ("STORE", 2, 5),
+            Opcode::Mov => ("MOV", 2, 1),
+            Opcode::Swap => ("SWAP", 2, 1),
+            Opcode::Halt => ("HALT", 0, 0),
+            Opcode::Gas => ("GAS", 1, 1),
+            Opcode::Revert => ("REVERT", 0, 0),
+            Opcode::Return => ("RETURN", 1, 0),
+            Opcode::TAnd => ("TAND", 3, 1),
+            Opcode::TOr => ("TOR", 3, 1),
+            Opcode::TXor => ("TXOR", 3, 1),
+            Opcode::TNot => ("TNOT", 2, 1),
+            Opcode::TShift => ("TSHIFT", 3, 1),
+            Opcode::TRotate => ("TROTATE", 3, 1),
+            Opcode::Hash => ("HASH", 3, 50),
+            Opcode::Verify => ("VERIFY", 3, 100),
+            Opcode::Encrypt => ("ENCRYPT", 3, 75),
+            Opcode::Decrypt => ("DECRYPT", 3, 75),
+            Opcode::HAdd => ("HADD", 3, 100),
+            Opcode::HMul => ("HMUL", 3, 150),
+            Opcode::HEnc => ("HENC", 3, 200),
+            Opcode::HDec => ("HDEC", 3, 200),
+        }
+    }
+
+    pub fn mnemonic(self) -> &'static str {
+        self.info().0
+    }
+
+    /// Number of 16-bit little-endian operands that follow the opcode byte
+    pub fn operand_count(self) -> usize {
+        self.info().1
+    }
+
+    /// Homomorphic operation priced by ciphertext size, if this is one
+    pub fn homomorphic(self) -> Option<HomomorphicOperation> {
+        match self {
+            Opcode::HAdd => Some(HomomorphicOperation::HEAdd),
+            Opcode::HMul => Some(HomomorphicOperation::HEMultiply),
+            Opcode::HEnc => Some(HomomorphicOperation::HEEncrypt),
+            Opcode::HDec => Some(HomomorphicOperation::HEDecrypt),
+            _ => None,
+        }
+    }
+}
+
+/// Homomorphic operations priced per kilotrit of ciphertext
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum HomomorphicOperation {
+    HEAdd,
+    HEMultiply,
+    HEEncrypt,
+    HEDecrypt,
+    HEBootstrap,
+}
+
+impl HomomorphicOperation {
+    /// Parse a `--operation` name (`add`, `multiply`/`mul`, `encrypt`, `decrypt`, `bootstrap`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().trim_start_matches("he").trim_start_matches(['-', '_']) {
+            "add" => Some(HomomorphicOperation::HEAdd),
+            "mul" | "multiply" => Some(HomomorphicOperation::HEMultiply),
+            "enc" | "encrypt" => Some(HomomorphicOperation::HEEncrypt),
+            "dec" | "decrypt" => Some(HomomorphicOperation::HEDecrypt),
+            "bootstrap" => Some(HomomorphicOperation::HEBootstrap),
+            _ => None,
+        }
+    }
+
+    /// Base gas and gas per whole kilotrit
+    fn rates(self) -> (u64, u64) {
+        match self {
+            HomomorphicOperation::HEAdd => (81, 3),
+            HomomorphicOperation::HEMultiply => (243, 6),
+            HomomorphicOperation::HEEncrypt | HomomorphicOperation::HEDecrypt => (243, 3),
+            HomomorphicOperation::HEBootstrap => (729, 9),
+        }
+    }
+}
+
+/// Ciphertext size assumed for homomorphic opcodes in bytecode (one 3^7 block)
+pub const DEFAULT_CIPHERTEXT_TRITS: usize = 2187;
+
+pub struct HomomorphicGasMeter;
+
+impl HomomorphicGasMeter {
+    pub fn calculate_gas_cost(operation: HomomorphicOperation, data_size_trits: usize) -> u64 {
+        let (base, per_kilotrit) = operation.rates();
+        base + (data_size_trits / 1000) as u64 * per_kilotrit
+    }
+}
+
+/// Gas charged before execution: a fixed base plus the transaction bytes
+pub const INTRINSIC_BASE_GAS: u64 = 243;
+pub const GAS_PER_BYTE: u64 = 9;
+/// Tetragram-compressed payloads pack 4 trits per glyph
+pub const GAS_PER_COMPRESSED_BYTE: u64 = 6;
+
+pub struct GasMeter;
+
+impl GasMeter {
+    pub fn calculate_intrinsic_gas(data: &[u8], is_tetragram_compressed: bool) -> u64 {
+        let per_byte = if is_tetragram_compressed { GAS_PER_COMPRESSED_BYTE } else { GAS_PER_BYTE };
+        INTRINSIC_BASE_GAS + data.len() as u64 * per_byte
+    }
+
+    pub fn get_opcode_cost(opcode: &Opcode) -> u64 {
+        opcode.info().2
+    }
+
+    pub fn is_homomorphic_op(opcode: &Opcode) -> bool {
+        opcode.homomorphic().is_some()
+    }
+
+    pub fn calculate_homomorphic_gas(opcode: &Opcode, data_size_trits: usize) -> u64 {
+        opcode
+            .homomorphic()
+            .map_or(0, |op| HomomorphicGasMeter::calculate_gas_cost(op, data_size_trits))
+    }
+}
+
+/// Testnet gas tiers (Tesla 3-6-9)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub enum GasTier {
+    /// Up to 1M gas
+    Tier3,
+    /// Up to 3M gas
+    Tier6,
+    /// Anything larger
+    Tier9,
+}
+
+impl GasTier {
+    pub fn from_gas_limit(gas_limit: u64) -> Self {
+        match gas_limit {
+            0..=1_000_000 => GasTier::Tier3,
+            1_000_001..=3_000_000 => GasTier::Tier6,
+            _ => GasTier::Tier9,
+        }
+    }
+}
+
+impl std::str::FromStr for GasTier {
+    type Err = String;
+
+    /// Parse the `{:?}` name, as stored in `GasEstimate::recommended_tier`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Tier3" => Ok(GasTier::Tier3),
+            "Tier6" => Ok(GasTier::Tier6),
+            "Tier9" => Ok(GasTier::Tier9),
+            _ => Err(format!("Unknown gas tier '{}'", s)),
+        }
+    }
+}
+
+/// Testnet fee parameters
+#[derive(Debug, Clone)]
+pub struct TestnetConfig {
+    /// Priority fee per gas for Tier3, Tier6 and Tier9
+    pub priority_fees: [u64; 3],
+}
+
+impl Default for TestnetConfig {
+    fn default() -> Self {
+        Self { priority_fees: [3, 6, 9] }
+    }
+}
+
+impl TestnetConfig {
+    pub fn priority_fee_for_tier(&self, tier: GasTier) -> u64 {
+        self.priority_fees[tier as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_opcode_bytes_round_trip() {
+        for &op in Opcode::ALL {
+            assert_eq!(Opcode::from_byte(op.to_byte()), Ok(op));
+        }
+        assert_eq!(Opcode::from_byte(0xFF), Err(0xFF));
+        assert_eq!((Opcode::Load.mnemonic(), Opcode::Load.operand_count()), ("LOAD", 2));
+    }
+
+    #[test]
+    fn test_operation_names() {
+        assert_eq!(HomomorphicOperation::from_name("HE-Add"), Some(HomomorphicOperation::HEAdd));
+        assert_eq!(HomomorphicOperation::from_name("mul"), Some(HomomorphicOperation::HEMultiply));
+        assert_eq!(HomomorphicOperation::from_name("bootstrap"), Some(HomomorphicOperation::HEBootstrap));
+        assert_eq!(HomomorphicOperation::from_name("sqrt"), None);
+    }
+}
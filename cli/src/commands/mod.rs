@@ -2,5 +2,8 @@
 //! 
 //! Provides command implementations for TrinaryVM CLI tools.
 
+pub mod bytecode;
+pub mod bytecode_cfg;
 pub mod gas_estimate;
+pub mod gas_schedule;
 
@@ -2,22 +2,52 @@
 
 use clap::{Parser, Subcommand, ValueEnum};
 use std::fs;
+use std::io::Write;
 use std::path::PathBuf;
 use std::path::Path;
 use std::time::Instant;
 use std::process::Command;
 
+mod commands;
 mod runtime_binary;
+mod runtime_daemon;
+mod runtime_limits;
+mod runtime_protocol;
+mod tetragram_asm;
+mod tetragram_cfg;
+mod tetragram_codec;
 mod tetragram_commands;
-
-use runtime_binary::{invoke_runtime, print_install_instructions, is_runtime_available};
+mod tetragram_debugger;
+mod tetragram_gas;
+mod tetragram_io;
+mod tetragram_ir;
+mod tetragram_measure;
+mod tetragram_memory;
+mod tetragram_report;
+mod tetragram_screen;
+mod tetragram_trace;
+mod tetragram_validate;
+mod tetragram_vm;
+
+use runtime_binary::{print_install_instructions, is_runtime_available, request_runtime, stream_runtime, RequestError};
+use runtime_limits::ResourceLimits;
+use runtime_protocol::{
+    BenchmarkRequest, DecryptRequest, EncryptRequest, HashRequest, KeysRequest, RunRequest, RuntimeCommand,
+    ValidateImplRequest, MACHINE_PROTOCOL_SINCE,
+};
 use tetragram_commands::TetragramCommands;
+use commands::gas_estimate::{format_gas_estimate, GasEstimator};
+use commands::gas_schedule::{GasTier, HomomorphicOperation};
 
 #[derive(Parser)]
 #[command(name = "trinaryvm-cli")]
 #[command(about = "TrinaryVM Production CLI with Supreme Mystery (.sm) support")]
 #[command(version = "1.0.0")]
 struct Cli {
+    /// Runtime binary to use for delegated commands (overrides TRINARYVM_RUNTIME and the config file)
+    #[arg(long, global = true, value_name = "PATH")]
+    runtime: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -37,6 +67,13 @@ enum Commands {
         /// Enable debug mode
         #[arg(short, long)]
         debug: bool,
+
+        /// Output JSON results
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        limits: ResourceLimits,
     },
     
     /// Tetragram operations with Supreme Mystery (.sm) files
@@ -56,6 +93,9 @@ enum Commands {
         /// Benchmark suite (basic | trifhe)
         #[arg(long, default_value = "basic")]
         suite: String,
+
+        #[command(flatten)]
+        limits: ResourceLimits,
     },
     
     /// Validate Tesla 3-6-9 alignment
@@ -84,6 +124,13 @@ enum Commands {
         /// Key file prefix
         #[arg(short, long, default_value = "keys")]
         name: String,
+
+        /// Output JSON results
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        limits: ResourceLimits,
     },
 
     /// Encrypt a file using TriFHE public key
@@ -99,6 +146,13 @@ enum Commands {
         /// Output ciphertext path
         #[arg(short, long)]
         output: PathBuf,
+
+        /// Output JSON results
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        limits: ResourceLimits,
     },
 
     /// Decrypt a file using TriFHE secret key
@@ -111,6 +165,13 @@ enum Commands {
 
         #[arg(short, long)]
         output: PathBuf,
+
+        /// Output JSON results
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        limits: ResourceLimits,
     },
 
     /// Compute SHA3-2187 hash of file
@@ -124,6 +185,13 @@ enum Commands {
         /// Output hex instead of raw bytes
         #[arg(long)]
         hex: bool,
+
+        /// Output JSON results
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        limits: ResourceLimits,
     },
 
     /// Comprehensive TriFHE implementation validation
@@ -131,7 +199,18 @@ enum Commands {
         /// Run comprehensive tests
         #[arg(long)]
         comprehensive: bool,
+
+        /// Output JSON results
+        #[arg(long)]
+        json: bool,
+
+        #[command(flatten)]
+        limits: ResourceLimits,
     },
+
+    /// Manage the persistent runtime worker
+    #[command(subcommand)]
+    Daemon(runtime_daemon::DaemonCommands),
     
     /// Estimate gas for contracts and transactions
     GasEstimate {
@@ -155,9 +234,16 @@ enum Commands {
         #[arg(long)]
         detailed: bool,
         
+        /// Bound gas over control-flow paths per function and pick the tier for the worst path
+        #[arg(long, requires = "file")]
+        worst_case: bool,
+        
         /// Output JSON format
         #[arg(long)]
         json: bool,
+
+        #[command(flatten)]
+        limits: ResourceLimits,
     },
 }
 
@@ -166,6 +252,49 @@ enum ApiAction { Deploy, Rollback, Status }
 
 const DEFAULT_OUTPUT_DIR: &str = "vm_outputs";
 
+/// Send a typed request to the runtime and print its result as text or JSON
+///
+/// Runtimes without `--machine` are given the command's argv instead, with
+/// live output and the runtime's exit code. Their output has no result
+/// schema, so `--json` fails on them for every command.
+fn delegate<C: RuntimeCommand>(request: C, json: bool, limits: &ResourceLimits) -> Result<(), Box<dyn std::error::Error>> {
+    let output = match request_runtime(&request, limits) {
+        Ok(output) => output,
+        Err(RequestError::Unsupported(version)) => {
+            if json {
+                return Err(format!(
+                    "--json needs a runtime with --machine support ({} or newer); found {}",
+                    MACHINE_PROTOCOL_SINCE, version
+                )
+                .into());
+            }
+            let args = request.legacy_args();
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            let code = stream_runtime(&args, limits)?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(e.exit_code());
+        }
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&output)?);
+    } else {
+        let mut stdout = std::io::stdout().lock();
+        request.render(&output, &mut stdout)?;
+        stdout.flush()?;
+    }
+    if !request.succeeded(&output) {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Ensure default output directory exists
     if !Path::new(DEFAULT_OUTPUT_DIR).exists() {
@@ -173,24 +302,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
 
     let cli = Cli::parse();
+    if let Some(runtime) = cli.runtime {
+        runtime_binary::set_runtime_override(runtime);
+    }
     
     match cli.command {
-        Commands::Run { file, gas_limit, debug } => {
-            println!("🔺 TrinaryVM Runtime Executor");
-            if debug {
-                println!("🐛 Debug mode enabled");
+        Commands::Run { file, gas_limit, debug, json, limits } => {
+            if !json {
+                println!("🔺 TrinaryVM Runtime Executor");
+                if debug {
+                    println!("🐛 Debug mode enabled");
+                }
             }
             
             // Invoke external runtime binary
-            let mut args = vec!["run", "--file", &file, "--gas-limit", &gas_limit.to_string()];
-            if debug {
-                args.push("--debug");
-            }
-            
-            let output = invoke_runtime(&args)?;
-            print!("{}", output);
-            
-            Ok(())
+            delegate(RunRequest { file, gas_limit, debug }, json, &limits)
         }
         
         Commands::Tetragram(tetragram_cmd) => {
@@ -198,30 +324,27 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             
             match tetragram_cmd {
                 TetragramCommands::Execute(args) => execute_tetragram_program(args),
+                TetragramCommands::Replay(args) => replay_tetragram_trace(args),
+                TetragramCommands::Debug(args) => debug_tetragram_program(args),
                 TetragramCommands::Validate(args) => validate_tetragram_file(args),
                 TetragramCommands::Benchmark(args) => benchmark_tetragram_performance(args),
                 TetragramCommands::Create(args) => create_tetragram_template(args),
                 TetragramCommands::Convert(args) => tetragram_commands::convert_tetragram_file(args),
                 TetragramCommands::Compile(args) => tetragram_commands::compile_pipeline(args),
+                TetragramCommands::Asm(args) => assemble_tetragram_source(args),
+                TetragramCommands::Disasm(args) => disassemble_tetragram_program(args),
                 TetragramCommands::Analyze(args) => tetragram_commands::analyze_tetragram_file(args),
+                TetragramCommands::Screen(args) => screen_tetragram_program(args),
             }
         }
         
-        Commands::Benchmark { iterations, json, suite } => {
-            println!("⚡ TrinaryVM Performance Benchmark");
-            println!("🔄 Running {} iterations...", iterations);
-
-            let mut args = vec!["benchmark", "--iterations", &iterations.to_string()];
-            if json {
-                args.push("--json");
+        Commands::Benchmark { iterations, json, suite, limits } => {
+            if !json {
+                println!("⚡ TrinaryVM Performance Benchmark");
+                println!("🔄 Running {} iterations...", iterations);
             }
-            args.push("--suite");
-            args.push(&suite);
-            
-            let output = invoke_runtime(&args)?;
-            print!("{}", output);
-            
-            Ok(())
+
+            delegate(BenchmarkRequest { iterations, suite }, json, &limits)
         }
         
         Commands::ValidateAlignment { file } => {
@@ -278,99 +401,70 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             Ok(())
         },
 
-        Commands::Keys { out_dir, name } => {
-            let args = vec![
-                "keys",
-                "--out-dir", &out_dir.to_string_lossy(),
-                "--name", &name,
-            ];
-            let output = invoke_runtime(&args)?;
-            print!("{}", output);
-            Ok(())
+        Commands::Keys { out_dir, name, json, limits } => {
+            delegate(KeysRequest { out_dir, name }, json, &limits)
         }
 
-        Commands::Encrypt { input, pk, output } => {
-            let args = vec![
-                "encrypt",
-                "--input", &input.to_string_lossy(),
-                "--pk", &pk.to_string_lossy(),
-                "--output", &output.to_string_lossy(),
-            ];
-            let output = invoke_runtime(&args)?;
-            print!("{}", output);
-            Ok(())
+        Commands::Encrypt { input, pk, output, json, limits } => {
+            delegate(EncryptRequest { input, pk, output }, json, &limits)
         }
 
-        Commands::Decrypt { input, sk, output } => {
-            let args = vec![
-                "decrypt",
-                "--input", &input.to_string_lossy(),
-                "--sk", &sk.to_string_lossy(),
-                "--output", &output.to_string_lossy(),
-            ];
-            let output = invoke_runtime(&args)?;
-            print!("{}", output);
-            Ok(())
+        Commands::Decrypt { input, sk, output, json, limits } => {
+            delegate(DecryptRequest { input, sk, output }, json, &limits)
         }
 
-        Commands::Hash { input, output, hex } => {
-            let mut args = vec![
-                "hash",
-                "--input", &input.to_string_lossy(),
-            ];
-            if let Some(out) = output {
-                args.push("--output");
-                args.push(&out.to_string_lossy());
-            }
-            if hex {
-                args.push("--hex");
-            }
-            let output = invoke_runtime(&args)?;
-            print!("{}", output);
-            Ok(())
+        Commands::Hash { input, output, hex, json, limits } => {
+            delegate(HashRequest { input, output, hex }, json, &limits)
         }
 
-        Commands::ValidateImpl { comprehensive } => {
-            let mut args = vec!["validate-impl"];
-            if comprehensive {
-                args.push("--comprehensive");
-            }
-            let output = invoke_runtime(&args)?;
-            print!("{}", output);
-            Ok(())
+        Commands::ValidateImpl { comprehensive, json, limits } => {
+            delegate(ValidateImplRequest { comprehensive }, json, &limits)
         }
+
+        Commands::Daemon(command) => runtime_daemon::run_daemon_command(command),
         
-        Commands::GasEstimate { file, operation, data_size, compressed, detailed, json } => {
-            let mut args = vec!["gas-estimate"];
-            
-            if let Some(file_path) = file {
-                args.push("--file");
-                args.push(&file_path);
-                if compressed {
-                    args.push("--compressed");
-                }
+        Commands::GasEstimate { file, operation, data_size, compressed, detailed, worst_case, json, limits } => {
+            // Estimated in-process; no runtime binary needed, so the limits bound the CLI itself
+            limits.enforce_in_process()?;
+            let estimator = GasEstimator::new();
+            let mut bytecode = None;
+            let estimate = if let Some(file_path) = file {
+                let bytes = fs::read(&file_path)?;
+                let estimate = if worst_case {
+                    estimator.estimate_worst_case(&bytes, compressed)
+                } else {
+                    estimator.estimate_contract(&bytes, compressed)
+                };
+                let estimate = estimate.map_err(|e| format!("{}: {}", file_path, e))?;
+                bytecode = Some(bytes);
+                estimate
             } else if let Some(op_str) = operation {
-                args.push("--operation");
-                args.push(&op_str);
-                if let Some(size) = data_size {
-                    args.push("--data-size");
-                    args.push(&size.to_string());
-                }
+                let op = HomomorphicOperation::from_name(&op_str).ok_or_else(|| {
+                    format!("Unknown operation '{}' (expected add, multiply, encrypt, decrypt or bootstrap)", op_str)
+                })?;
+                estimator.estimate_operation(op, data_size.unwrap_or(commands::gas_schedule::DEFAULT_CIPHERTEXT_TRITS))
             } else {
                 eprintln!("Usage: trinaryvm gas-estimate --file <bytecode> OR --operation <op> --data-size <size>");
                 eprintln!("Use --help for more information");
                 std::process::exit(1);
-            }
+            };
             
-            if detailed {
-                args.push("--detailed");
-            }
             if json {
-                args.push("--json");
+                println!("{}", serde_json::to_string_pretty(&estimate)?);
+                return Ok(());
             }
             
-            let output = invoke_runtime(&args)?;
-            print!("{}", output);
+            print!("{}", format_gas_estimate(&estimate));
+            if detailed {
+                // Same tier as the estimate, which may be worst-case rather than the linear total
+                let tier = estimate.recommended_tier.parse::<GasTier>()?;
+                println!("\n💡 Tier Advice:");
+                println!("  Priority fee for {:?}: {} per gas", tier, estimator.recommend_priority_fee(tier));
+                if let (Some(bytes), false) = (&bytecode, compressed) {
+                    let savings = estimator.calculate_compression_savings(bytes);
+                    println!("  Tetragram compression would save {} gas ({:.1}%)", savings.savings, savings.savings_percent);
+                }
+            }
             Ok(())
         }
     }
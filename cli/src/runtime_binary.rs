@@ -1,75 +1,652 @@
 //! Runtime binary detection and invocation utilities
-//! 
+//!
 //! This module handles detection and invocation of the external `trinaryvm` binary
 //! which contains the actual VM runtime implementation.
+//!
+//! The runtime is taken from the first of these that is set:
+//!
+//! 1. the global `--runtime <path>` flag
+//! 2. the `TRINARYVM_RUNTIME` environment variable
+//! 3. the `"runtime"` key of `~/.trinaryvm/config.json`
+//! 4. a `trinaryvm` binary on `PATH`, then the common install locations
+//!
+//! A runtime named by 1–3 is used or rejected as given, never swapped for a
+//! search hit. Whichever is chosen must report a `--version` inside
+//! [`SUPPORTED_RUNTIME`].
 
-use std::process::{Command, Stdio};
-use std::io::{self, Write};
+use std::env;
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use crate::runtime_limits::{kill_group, Watchdog};
+use crate::runtime_limits::{wait_for, LimitExceeded, ResourceLimits, EXIT_LIMIT_EXCEEDED};
+use crate::runtime_protocol::{encode_request, RuntimeCommand, RuntimeEvent, MACHINE_PROTOCOL_SINCE};
 
 /// Binary name to look for
 const RUNTIME_BINARY: &str = "trinaryvm";
 
-/// Check if the runtime binary is available in PATH
-pub fn is_runtime_available() -> bool {
-    Command::new(RUNTIME_BINARY)
-        .arg("--version")
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
-        .map(|s| s.success())
-        .unwrap_or(false)
+/// Environment variable naming the runtime binary
+pub const RUNTIME_ENV: &str = "TRINARYVM_RUNTIME";
+
+/// CLI config file and the key holding the runtime path
+pub const CONFIG_FILE: &str = "~/.trinaryvm/config.json";
+const CONFIG_RUNTIME_KEY: &str = "runtime";
+
+/// Install locations probed after `PATH`
+const COMMON_PATHS: [&str; 4] = [
+    "/usr/local/bin/trinaryvm",
+    "/usr/bin/trinaryvm",
+    "~/.local/bin/trinaryvm",
+    "./trinaryvm",
+];
+
+/// Runtime versions this CLI can drive
+pub const SUPPORTED_RUNTIME: VersionRange = VersionRange {
+    min: Version::new(1, 0, 0),
+    max: Version::new(2, 0, 0),
+};
+
+/// Path from `--runtime`, set once by `main` after argument parsing
+static RUNTIME_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Use `path` as the runtime for the rest of the process (the `--runtime` flag)
+pub fn set_runtime_override(path: PathBuf) {
+    let _ = RUNTIME_OVERRIDE.set(path);
 }
 
-/// Get the runtime binary path, or None if not found
-pub fn find_runtime_binary() -> Option<String> {
-    // First try direct invocation
-    if is_runtime_available() {
-        return Some(RUNTIME_BINARY.to_string());
-    }
-    
-    // Try common installation paths
-    let common_paths = [
-        "/usr/local/bin/trinaryvm",
-        "/usr/bin/trinaryvm",
-        "~/.local/bin/trinaryvm",
-        "./trinaryvm",
-    ];
-    
-    for path in &common_paths {
-        let expanded = shellexpand::tilde(path).to_string();
-        if Command::new(&expanded)
-            .arg("--version")
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .status()
-            .map(|s| s.success())
-            .unwrap_or(false)
-        {
-            return Some(expanded);
+/// A `major.minor.patch` version; pre-release and build suffixes are ignored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Version { major, minor, patch }
+    }
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    /// Accepts `1.2.3`, `v1.2.3`, `1.2` and `1.2.3-beta.1+abc`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let core = s.strip_prefix('v').unwrap_or(s);
+        let core = core.split(['-', '+']).next().unwrap_or_default();
+        let parts = core
+            .split('.')
+            .map(|p| p.parse::<u64>().map_err(|_| format!("'{}' is not a version", s)))
+            .collect::<Result<Vec<_>, _>>()?;
+        match parts[..] {
+            [major, minor] => Ok(Version::new(major, minor, 0)),
+            [major, minor, patch] => Ok(Version::new(major, minor, patch)),
+            _ => Err(format!("'{}' is not a version", s)),
         }
     }
-    
-    None
 }
 
-/// Invoke the runtime binary with arguments
-pub fn invoke_runtime(args: &[&str]) -> Result<String, String> {
-    let binary = find_runtime_binary()
-        .ok_or_else(|| format!("{}", get_install_instructions()))?;
-    
-    let output = Command::new(&binary)
-        .args(args)
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// First version number in `--version` output such as `trinaryvm 1.2.3`
+pub fn parse_version_output(output: &str) -> Option<Version> {
+    output.split_whitespace().find_map(|word| word.parse().ok())
+}
+
+/// Half-open version range `>= min, < max`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionRange {
+    pub min: Version,
+    pub max: Version,
+}
+
+impl VersionRange {
+    pub fn contains(&self, version: Version) -> bool {
+        self.min <= version && version < self.max
+    }
+}
+
+impl fmt::Display for VersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, ">={}, <{}", self.min, self.max)
+    }
+}
+
+/// Where the runtime path came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuntimeSource {
+    Flag,
+    Env,
+    Config,
+    Search,
+}
+
+impl fmt::Display for RuntimeSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeSource::Flag => write!(f, "--runtime"),
+            RuntimeSource::Env => write!(f, "{}", RUNTIME_ENV),
+            RuntimeSource::Config => write!(f, "\"{}\" in {}", CONFIG_RUNTIME_KEY, CONFIG_FILE),
+            RuntimeSource::Search => write!(f, "PATH search"),
+        }
+    }
+}
+
+/// A runtime binary whose version has been checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Runtime {
+    pub path: PathBuf,
+    pub version: Version,
+    pub source: RuntimeSource,
+}
+
+/// Why no usable runtime could be selected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuntimeError {
+    NotFound,
+    Config { path: PathBuf, message: String },
+    Launch { path: PathBuf, source: RuntimeSource, message: String },
+    UnknownVersion { path: PathBuf, source: RuntimeSource, output: String },
+    TooOld { path: PathBuf, source: RuntimeSource, version: Version },
+    TooNew { path: PathBuf, source: RuntimeSource, version: Version },
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuntimeError::NotFound => write!(f, "{}", get_install_instructions()),
+            RuntimeError::Config { path, message } => {
+                write!(f, "❌ Invalid CLI config {}: {}", path.display(), message)
+            }
+            RuntimeError::Launch { path, source, message } => write!(
+                f,
+                "❌ Could not run runtime {} (from {}): {}",
+                path.display(),
+                source,
+                message
+            ),
+            RuntimeError::UnknownVersion { path, source, output } => write!(
+                f,
+                "❌ Runtime {} (from {}) did not report a version; `--version` printed: {:?}",
+                path.display(),
+                source,
+                output.trim()
+            ),
+            RuntimeError::TooOld { path, source, version } => write!(
+                f,
+                "❌ Runtime {} (from {}) is version {}, too old for this CLI (supported: {}). Upgrade the runtime.",
+                path.display(),
+                source,
+                version,
+                SUPPORTED_RUNTIME
+            ),
+            RuntimeError::TooNew { path, source, version } => write!(
+                f,
+                "❌ Runtime {} (from {}) is version {}, too new for this CLI (supported: {}). Upgrade trinaryvm-cli or point --runtime at an older runtime.",
+                path.display(),
+                source,
+                version,
+                SUPPORTED_RUNTIME
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+fn expand(path: &Path) -> PathBuf {
+    PathBuf::from(shellexpand::tilde(&path.to_string_lossy()).as_ref())
+}
+
+/// Read the `"runtime"` key from a config file; a missing file is not an error
+pub fn read_config_runtime(config: &Path) -> Result<Option<PathBuf>, RuntimeError> {
+    let invalid = |message: String| RuntimeError::Config { path: config.to_path_buf(), message };
+    let text = match std::fs::read_to_string(config) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(invalid(e.to_string())),
+    };
+    let value: serde_json::Value = serde_json::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+    match value.get(CONFIG_RUNTIME_KEY) {
+        None | Some(serde_json::Value::Null) => Ok(None),
+        Some(serde_json::Value::String(path)) => Ok(Some(PathBuf::from(path))),
+        Some(other) => Err(invalid(format!("\"{}\" must be a path string, found {}", CONFIG_RUNTIME_KEY, other))),
+    }
+}
+
+/// The runtime path the user asked for, if any, in precedence order
+fn requested_runtime() -> Result<Option<(PathBuf, RuntimeSource)>, RuntimeError> {
+    if let Some(path) = RUNTIME_OVERRIDE.get() {
+        return Ok(Some((path.clone(), RuntimeSource::Flag)));
+    }
+    if let Some(path) = env::var_os(RUNTIME_ENV).filter(|p| !p.is_empty()) {
+        return Ok(Some((PathBuf::from(path), RuntimeSource::Env)));
+    }
+    let config = read_config_runtime(&expand(Path::new(CONFIG_FILE)))?;
+    Ok(config.map(|path| (path, RuntimeSource::Config)))
+}
+
+/// First `trinaryvm` on `PATH` or in a common location, skipping this CLI itself
+fn search_runtime() -> Option<PathBuf> {
+    let this_exe = env::current_exe().ok().and_then(|p| p.canonicalize().ok());
+    let on_path = env::var_os("PATH")
+        .map(|paths| env::split_paths(&paths).map(|dir| dir.join(RUNTIME_BINARY)).collect::<Vec<_>>())
+        .unwrap_or_default();
+    let common = COMMON_PATHS.iter().map(|p| expand(Path::new(p)));
+
+    on_path.into_iter().chain(common).find(|candidate| {
+        candidate.is_file() && (this_exe.is_none() || candidate.canonicalize().ok() != this_exe)
+    })
+}
+
+/// How long `--version` may take before the runtime is given up on
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Output of `path --version`, killing it if it runs past `timeout`
+fn version_output(path: &Path, timeout: Duration) -> Result<Output, String> {
+    let mut child = Command::new(path)
+        .arg("--version")
+        .stdin(Stdio::null())
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
-        .output()
-        .map_err(|e| format!("Failed to execute {}: {}", binary, e))?;
-    
+        .spawn()
+        .map_err(|e| e.to_string())?;
+    fn drain(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<Vec<u8>> {
+        thread::spawn(move || {
+            let mut bytes = Vec::new();
+            let _ = pipe.read_to_end(&mut bytes);
+            bytes
+        })
+    }
+    let stdout = drain(child.stdout.take().expect("stdout is piped"));
+    let stderr = drain(child.stderr.take().expect("stderr is piped"));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(|e| e.to_string())? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(format!("`--version` did not answer within {:?}", timeout));
+        }
+        thread::sleep(Duration::from_millis(10));
+    };
+    Ok(Output { status, stdout: stdout.join().unwrap_or_default(), stderr: stderr.join().unwrap_or_default() })
+}
+
+/// Run `path --version` and check the result against [`SUPPORTED_RUNTIME`]
+pub fn probe_runtime(path: &Path, source: RuntimeSource) -> Result<Runtime, RuntimeError> {
+    let launch = |message: String| RuntimeError::Launch { path: path.to_path_buf(), source, message };
+    let output = version_output(path, PROBE_TIMEOUT).map_err(launch)?;
     if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("Runtime execution failed:\n{}", stderr));
+        return Err(launch(format!(
+            "`--version` exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let version = parse_version_output(&stdout).ok_or_else(|| RuntimeError::UnknownVersion {
+        path: path.to_path_buf(),
+        source,
+        output: stdout.clone(),
+    })?;
+    if version < SUPPORTED_RUNTIME.min {
+        return Err(RuntimeError::TooOld { path: path.to_path_buf(), source, version });
+    }
+    if !SUPPORTED_RUNTIME.contains(version) {
+        return Err(RuntimeError::TooNew { path: path.to_path_buf(), source, version });
+    }
+    Ok(Runtime { path: path.to_path_buf(), version, source })
+}
+
+/// Select the runtime binary and negotiate its version
+pub fn resolve_runtime() -> Result<Runtime, RuntimeError> {
+    if let Some((path, source)) = requested_runtime()? {
+        return probe_runtime(&expand(&path), source);
+    }
+    let path = search_runtime().ok_or(RuntimeError::NotFound)?;
+    probe_runtime(&path, RuntimeSource::Search)
+}
+
+/// Check if a supported runtime binary is available
+pub fn is_runtime_available() -> bool {
+    resolve_runtime().is_ok()
+}
+
+/// Get the runtime binary path, or None if no supported runtime is found
+pub fn find_runtime_binary() -> Option<String> {
+    resolve_runtime().ok().map(|runtime| runtime.path.to_string_lossy().to_string())
+}
+
+/// Run the runtime with live output and return its exit code
+///
+/// stdout and stderr are forwarded line by line as the runtime writes them.
+/// The runtime gets its own process group, and Ctrl-C or SIGTERM sent to the
+/// CLI is forwarded to that group so the runtime can shut down cleanly. A
+/// runtime killed by a signal reports `128 + signal`, as shells do. When one
+/// of `limits` stops the runtime, the group is killed, the limit is reported
+/// on stderr and the code is [`EXIT_LIMIT_EXCEEDED`].
+pub fn stream_runtime(args: &[&str], limits: &ResourceLimits) -> Result<i32, String> {
+    limits.check_supported()?;
+    let runtime = resolve_runtime().map_err(|e| e.to_string())?;
+    let mut command = Command::new(&runtime.path);
+    command.args(args);
+    match stream_command(command, limits, io::stdout(), io::stderr()) {
+        Ok(Ok(code)) => Ok(code),
+        Ok(Err(limit)) => {
+            eprintln!("{}", limit);
+            Ok(EXIT_LIMIT_EXCEEDED)
+        }
+        Err(e) => Err(format!("Failed to execute {}: {}", runtime.path.display(), e)),
+    }
+}
+
+/// Exit code of the command, or the limit that stopped it; its output is
+/// copied to `stdout` and `stderr`
+fn stream_command(
+    command: Command,
+    limits: &ResourceLimits,
+    mut stdout: impl Write,
+    stderr: impl Write + Send + 'static,
+) -> io::Result<Result<i32, LimitExceeded>> {
+    let mut open = true;
+    supervise(command, limits, None, stderr, &mut |line| {
+        if open {
+            open = stdout.write_all(line).and_then(|_| stdout.flush()).is_ok();
+        }
+    })
+}
+
+/// Run `command` in its own process group under `limits`, feeding it
+/// `input` on stdin, passing each stdout line to `on_line` and forwarding
+/// stderr to `errors` as it arrives
+fn supervise(
+    mut command: Command,
+    limits: &ResourceLimits,
+    input: Option<&[u8]>,
+    errors: impl Write + Send + 'static,
+    on_line: &mut dyn FnMut(&[u8]),
+) -> io::Result<Result<i32, LimitExceeded>> {
+    let stdin = if input.is_some() { Stdio::piped() } else { Stdio::null() };
+    command.stdin(stdin).stdout(Stdio::piped()).stderr(Stdio::piped());
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    limits.apply_to(&mut command);
+
+    let mut child = command.spawn()?;
+    let group = child.id();
+    let _forwarding = signals::forward_to(group);
+    #[cfg(unix)]
+    let watchdog = limits.timeout.map(|timeout| (timeout, Watchdog::start(group, timeout)));
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        // A runtime that exits without reading is reported by its exit code
+        let _ = stdin.write_all(input);
+    }
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let errors = thread::spawn(move || forward_lines(stderr, errors));
+    // Reap the runtime on its own thread: once a limit has killed it, the
+    // rest of the group goes too, or members holding the pipes would keep
+    // the readers below waiting
+    let waiter = {
+        let limits = limits.clone();
+        thread::spawn(move || {
+            let waited = wait_for(&mut child);
+            #[cfg(unix)]
+            if waited.as_ref().is_ok_and(|&(status, cpu_time)| limits.attribute(status, cpu_time).is_some()) {
+                kill_group(group, libc::SIGKILL);
+            }
+            waited
+        })
+    };
+    for_each_line(stdout, on_line);
+    let _ = errors.join();
+    let (status, cpu_time) = waiter.join().expect("waiter thread panicked")?;
+
+    #[cfg(unix)]
+    if let Some((timeout, watchdog)) = watchdog {
+        if watchdog.finish() {
+            return Ok(Err(LimitExceeded::Timeout(timeout)));
+        }
+    }
+    if let Some(limit) = limits.attribute(status, cpu_time) {
+        return Ok(Err(limit));
+    }
+    Ok(Ok(exit_code(status)))
+}
+
+/// Call `f` with each line of `from`, trailing newline included
+fn for_each_line(from: impl Read, f: &mut dyn FnMut(&[u8])) {
+    let mut reader = BufReader::new(from);
+    let mut line = Vec::new();
+    while matches!(reader.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+        f(&line);
+        line.clear();
+    }
+}
+
+/// Copy `from` to `to` a line at a time; keeps draining if `to` goes away
+/// (e.g. `| head`) so the runtime never blocks on a full pipe
+fn forward_lines(from: impl Read, mut to: impl Write) {
+    let mut open = true;
+    for_each_line(from, &mut |line| {
+        if open {
+            open = to.write_all(line).and_then(|_| to.flush()).is_ok();
+        }
+    });
+}
+
+/// Why a typed request produced no result
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestError {
+    Runtime(RuntimeError),
+    /// The runtime predates `--machine`
+    Unsupported(Version),
+    Launch(String),
+    Limit(LimitExceeded),
+    /// The runtime answered with an error, or exited without answering
+    Failed { exit_code: i32, message: String },
+    /// The runtime's answer did not match the protocol
+    Protocol(String),
+}
+
+impl RequestError {
+    /// Exit code the CLI should end with
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RequestError::Limit(_) => EXIT_LIMIT_EXCEEDED,
+            RequestError::Failed { exit_code, .. } if *exit_code != 0 => *exit_code,
+            _ => 1,
+        }
+    }
+}
+
+impl fmt::Display for RequestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestError::Runtime(e) => write!(f, "{}", e),
+            RequestError::Unsupported(version) => write!(
+                f,
+                "❌ Runtime {} does not support --machine (needs {} or newer)",
+                version, MACHINE_PROTOCOL_SINCE
+            ),
+            RequestError::Launch(message) => write!(f, "❌ {}", message),
+            RequestError::Limit(limit) => write!(f, "{}", limit),
+            RequestError::Failed { message, .. } => write!(f, "❌ Runtime execution failed: {}", message),
+            RequestError::Protocol(message) => write!(f, "❌ Runtime protocol error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for RequestError {}
+
+/// Ids for requests sent by this process
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Send a typed request to `trinaryvm --machine` and decode its result
+///
+/// `log` events are printed to stderr as they arrive, as is any stdout line
+/// that is not a protocol event. Goes through the daemon when one is running
+/// (see `runtime_daemon`) and serves the requested runtime, otherwise starts
+/// a runtime for this request.
+pub fn request_runtime<C: RuntimeCommand>(command: &C, limits: &ResourceLimits) -> Result<C::Output, RequestError> {
+    limits.check_supported().map_err(RequestError::Launch)?;
+    // A running daemon serves requests unless this one needs its own limits
+    #[cfg(unix)]
+    if limits.is_empty() {
+        let requested = requested_runtime().map_err(RequestError::Runtime)?;
+        let requested = requested.map(|(path, _)| expand(&path));
+        if let Some(answer) = crate::runtime_daemon::request(command, requested.as_deref()) {
+            return answer;
+        }
+    }
+    let runtime = resolve_runtime().map_err(RequestError::Runtime)?;
+    if runtime.version < MACHINE_PROTOCOL_SINCE {
+        return Err(RequestError::Unsupported(runtime.version));
+    }
+    let mut process = Command::new(&runtime.path);
+    process.arg("--machine");
+    request_with(process, command, limits)
+}
+
+/// Print a `log` event on stderr
+fn print_log(level: Option<&str>, message: &str) {
+    match level {
+        Some("warn" | "warning") => eprintln!("⚠️  {}", message),
+        Some("error") => eprintln!("❌ {}", message),
+        _ => eprintln!("{}", message),
+    }
+}
+
+fn request_with<C: RuntimeCommand>(
+    process: Command,
+    command: &C,
+    limits: &ResourceLimits,
+) -> Result<C::Output, RequestError> {
+    let program = process.get_program().to_string_lossy().to_string();
+    let id = next_request_id();
+    let request = encode_request(id, command);
+    let mut reply = None;
+    let outcome = supervise(process, limits, Some(request.as_bytes()), io::stderr(), &mut |line| handle_line(line, id, &mut reply))
+        .map_err(|e| RequestError::Launch(format!("Failed to execute {}: {}", program, e)))?;
+
+    let exit_code = outcome.map_err(RequestError::Limit)?;
+    match reply {
+        Some(answer) => decode_reply::<C>(answer, exit_code),
+        None => Err(RequestError::Failed {
+            exit_code,
+            message: format!("runtime exited with code {} without answering the {} request", exit_code, C::NAME),
+        }),
+    }
+}
+
+pub(crate) fn next_request_id() -> u64 {
+    NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Print `log` events and non-protocol lines; keep the answer to request `id`
+pub(crate) fn handle_line(line: &[u8], id: u64, reply: &mut Option<RuntimeEvent>) {
+    let Ok(event) = serde_json::from_slice::<RuntimeEvent>(line) else {
+        eprint!("{}", String::from_utf8_lossy(line));
+        return;
+    };
+    match event {
+        RuntimeEvent::Log { level, message } => print_log(level.as_deref(), &message),
+        RuntimeEvent::Result { id: other, .. } | RuntimeEvent::Error { id: other, .. } if other != id => {}
+        answer => *reply = Some(answer),
+    }
+}
+
+/// Turn a `result` or `error` event into the command's typed output
+pub(crate) fn decode_reply<C: RuntimeCommand>(answer: RuntimeEvent, exit_code: i32) -> Result<C::Output, RequestError> {
+    match answer {
+        RuntimeEvent::Result { result, .. } => serde_json::from_value(result)
+            .map_err(|e| RequestError::Protocol(format!("malformed {} result: {}", C::NAME, e))),
+        RuntimeEvent::Error { code, message, .. } => Err(RequestError::Failed {
+            exit_code,
+            message: code.map_or(message.clone(), |code| format!("{} ({})", message, code)),
+        }),
+        RuntimeEvent::Log { .. } => Err(RequestError::Protocol("a log event is not an answer".to_string())),
+    }
+}
+
+fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return 128 + signal;
+    }
+    status.code().unwrap_or(1)
+}
+
+#[cfg(unix)]
+mod signals {
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    const FORWARDED: [libc::c_int; 2] = [libc::SIGINT, libc::SIGTERM];
+
+    /// Process group of the running runtime, or 0 when none is running
+    static RUNTIME_GROUP: AtomicI32 = AtomicI32::new(0);
+
+    extern "C" fn forward(signal: libc::c_int) {
+        let group = RUNTIME_GROUP.load(Ordering::SeqCst);
+        if group > 0 {
+            // SAFETY: kill is async-signal-safe
+            unsafe {
+                libc::kill(-group, signal);
+            }
+        }
+    }
+
+    /// Forwards SIGINT and SIGTERM until dropped, then restores the old handlers
+    pub struct Forwarding {
+        previous: [libc::sighandler_t; 2],
+    }
+
+    pub fn forward_to(group: u32) -> Forwarding {
+        RUNTIME_GROUP.store(group as i32, Ordering::SeqCst);
+        let handler = forward as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // SAFETY: the handler only reads an atomic and calls kill
+        Forwarding { previous: FORWARDED.map(|signal| unsafe { libc::signal(signal, handler) }) }
+    }
+
+    impl Drop for Forwarding {
+        fn drop(&mut self) {
+            for (signal, previous) in FORWARDED.into_iter().zip(self.previous) {
+                // SAFETY: reinstates the handler that was set before forward_to
+                unsafe {
+                    libc::signal(signal, previous);
+                }
+            }
+            RUNTIME_GROUP.store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod signals {
+    /// The console already delivers Ctrl-C to the runtime
+    pub struct Forwarding;
+
+    pub fn forward_to(_group: u32) -> Forwarding {
+        Forwarding
     }
-    
-    Ok(String::from_utf8_lossy(&output.stdout).to_string())
 }
 
 /// Get install instructions for the runtime binary
@@ -78,25 +655,31 @@ pub fn get_install_instructions() -> String {
         r#"
 ❌ TrinaryVM runtime binary not found
 
-The 'trinaryvm' runtime binary is required for this command.
+The 'trinaryvm' runtime binary (version {}) is required for this command.
 
 📦 Installation Options:
 
 1. Download pre-compiled binaries:
    Visit: https://github.com/TrinaryVM/trinaryvm-core/releases
-   
+
 2. Build from source (requires access to private repository):
    git clone git@github.com:TrinaryVM/trinaryvm-core.git
    cd trinaryvm-core/runtime
    cargo build --release
-   
+
 3. Enterprise/Partner access:
    Contact: favourablegroup@gmail.com
-   
+
 4. Add to PATH:
    After installation, ensure 'trinaryvm' is in your PATH:
    export PATH="$PATH:/path/to/trinaryvm/bin"
-"#
+
+   Or point the CLI at it directly:
+   --runtime /path/to/trinaryvm
+   export {}=/path/to/trinaryvm
+   {{ "{}": "/path/to/trinaryvm" }} in {}
+"#,
+        SUPPORTED_RUNTIME, RUNTIME_ENV, CONFIG_RUNTIME_KEY, CONFIG_FILE
     )
 }
 
@@ -105,3 +688,148 @@ pub fn print_install_instructions() {
     eprintln!("{}", get_install_instructions());
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_version_output() {
+        assert_eq!(parse_version_output("trinaryvm 1.4.2\n"), Some(Version::new(1, 4, 2)));
+        assert_eq!(parse_version_output("trinaryvm-runtime v1.0.0-rc.1 (abc123)"), Some(Version::new(1, 0, 0)));
+        assert_eq!(parse_version_output("trinaryvm 2.1"), Some(Version::new(2, 1, 0)));
+        assert_eq!(parse_version_output("trinaryvm dev build"), None);
+        assert!("1.2.3.4".parse::<Version>().is_err());
+    }
+
+    #[test]
+    fn test_supported_range_is_half_open() {
+        assert!(SUPPORTED_RUNTIME.contains(SUPPORTED_RUNTIME.min));
+        assert!(!SUPPORTED_RUNTIME.contains(SUPPORTED_RUNTIME.max));
+        assert!(!SUPPORTED_RUNTIME.contains(Version::new(0, 9, 9)));
+        assert_eq!(SUPPORTED_RUNTIME.to_string(), ">=1.0.0, <2.0.0");
+    }
+
+    #[test]
+    fn test_read_config_runtime() {
+        let dir = env::temp_dir().join(format!("trinaryvm-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = dir.join("config.json");
+
+        assert_eq!(read_config_runtime(&config), Ok(None));
+        std::fs::write(&config, r#"{"runtime": "~/bin/trinaryvm", "network": "testnet"}"#).unwrap();
+        assert_eq!(read_config_runtime(&config), Ok(Some(PathBuf::from("~/bin/trinaryvm"))));
+        std::fs::write(&config, r#"{"runtime": 3}"#).unwrap();
+        assert!(matches!(read_config_runtime(&config), Err(RuntimeError::Config { .. })));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_probe_rejects_unsupported_versions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = env::temp_dir().join(format!("trinaryvm-probe-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let fake = |name: &str, version: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, format!("#!/bin/sh\necho 'trinaryvm {}'\n", version)).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+
+        let ok = probe_runtime(&fake("ok", "1.3.0"), RuntimeSource::Env).unwrap();
+        assert_eq!(ok.version, Version::new(1, 3, 0));
+        assert!(matches!(probe_runtime(&fake("old", "0.9.1"), RuntimeSource::Flag), Err(RuntimeError::TooOld { .. })));
+        let err = probe_runtime(&fake("new", "2.0.0"), RuntimeSource::Config).unwrap_err();
+        assert!(matches!(err, RuntimeError::TooNew { .. }));
+        assert!(err.to_string().contains("too new"));
+        assert!(matches!(
+            probe_runtime(&dir.join("missing"), RuntimeSource::Flag),
+            Err(RuntimeError::Launch { .. })
+        ));
+
+        let hung = dir.join("hung");
+        std::fs::write(&hung, "#!/bin/sh\nexec sleep 30\n").unwrap();
+        std::fs::set_permissions(&hung, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let started = std::time::Instant::now();
+        assert!(version_output(&hung, Duration::from_millis(200)).unwrap_err().contains("did not answer"));
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stream_preserves_exit_codes() {
+        let sh = |script: &str, out: &mut Vec<u8>| {
+            let mut command = Command::new("sh");
+            command.args(["-c", script]);
+            stream_command(command, &ResourceLimits::default(), out, io::sink()).unwrap()
+        };
+        let mut out = Vec::new();
+        assert_eq!(sh("echo streamed; echo warning >&2", &mut out), Ok(0));
+        assert_eq!(out, b"streamed\n");
+        assert_eq!(sh("exit 3", &mut out), Ok(3));
+        assert_eq!(sh("kill -INT $$", &mut out), Ok(128 + libc::SIGINT));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_limits_stop_the_process_group() {
+        let sh = |script: &str, limits: ResourceLimits| {
+            let mut command = Command::new("sh");
+            command.args(["-c", script]);
+            stream_command(command, &limits, io::sink(), io::sink()).unwrap()
+        };
+        let timeout = Duration::from_millis(200);
+        let started = std::time::Instant::now();
+        // The background sleep shares the group and holds the pipes open
+        let timed_out = sh("sleep 30 & sleep 30", ResourceLimits { timeout: Some(timeout), ..Default::default() });
+        assert_eq!(timed_out, Err(LimitExceeded::Timeout(timeout)));
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        let cpu = ResourceLimits { max_cpu_seconds: Some(1), ..Default::default() };
+        let started = std::time::Instant::now();
+        assert_eq!(sh("sleep 30 & while :; do :; done", cpu), Err(LimitExceeded::CpuTime(1)));
+        assert!(started.elapsed() < Duration::from_secs(10));
+
+        // The hard limit sits one second above the largest soft limit without overflowing
+        let unlimited = ResourceLimits { max_cpu_seconds: Some(u64::MAX), ..Default::default() };
+        assert_eq!(sh("exit 0", unlimited.clone()), Ok(0));
+        // A SIGKILL from elsewhere keeps its exit code
+        assert_eq!(sh("kill -KILL $$", unlimited), Ok(128 + libc::SIGKILL));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_machine_request_round_trip() {
+        use crate::runtime_protocol::{HashRequest, HashResult};
+
+        // Echo the request id back with a canned answer
+        let fake = |answer: &str| {
+            let mut command = Command::new("sh");
+            let script = format!(
+                r#"read req; id=$(echo "$req" | sed 's/.*"id":\([0-9]*\).*/\1/'); echo '{{"event":"log","message":"hashing"}}'; {}"#,
+                answer
+            );
+            command.args(["-c", &script]);
+            command
+        };
+        let request = HashRequest { input: "data.bin".into(), output: None, hex: true };
+        let limits = ResourceLimits::default();
+
+        let ok = fake(r#"echo '{"event":"result","id":'$id',"result":{"algorithm":"SHA3-2187","digest":"00ff"}}'"#);
+        let hash: HashResult = request_with(ok, &request, &limits).unwrap();
+        assert_eq!((hash.digest.as_str(), hash.output), ("00ff", None));
+
+        let failed = fake(r#"echo '{"event":"error","id":'$id',"code":"file-not-found","message":"data.bin"}'; exit 2"#);
+        let err = request_with(failed, &request, &limits).unwrap_err();
+        assert_eq!(err.exit_code(), 2);
+        assert!(err.to_string().contains("data.bin (file-not-found)"));
+
+        let silent = request_with(fake("exit 0"), &request, &limits).unwrap_err();
+        assert!(matches!(silent, RequestError::Failed { exit_code: 0, .. }));
+        assert_eq!(silent.exit_code(), 1);
+    }
+}
@@ -0,0 +1,617 @@
+//! Persistent runtime daemon
+//!
+//! `trinaryvm-cli daemon start` launches a background worker that owns one
+//! `trinaryvm --machine` process and listens on a Unix socket. While it runs,
+//! delegated commands send their requests to the socket instead of starting
+//! a runtime, so TriFHE key loading and other startup cost is paid once.
+//!
+//! The worker multiplexes every client over that one runtime:
+//!
+//! - each request is forwarded with a fresh id
+//! - the answer goes back to the connection that asked, under its original id
+//! - `log` events carry no id, so they go to every client with a request in flight
+//! - control messages (`{"daemon":"status"}`, `{"daemon":"stop"}`) are
+//!   answered by the worker itself
+//!
+//! The socket, pid file and log (the worker's and runtime's stderr) live in
+//! `~/.trinaryvm`, which is kept private to the user. Requests with resource
+//! limits bypass the daemon, since rlimits cannot be applied to a shared
+//! process, and so do requests for a runtime other than the daemon's.
+
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use crate::runtime_binary::{
+    decode_reply, handle_line, next_request_id, resolve_runtime, RequestError, Runtime,
+};
+#[cfg(unix)]
+use crate::runtime_limits::{kill_group, KILL_GRACE};
+#[cfg(unix)]
+use crate::runtime_protocol::{encode_request, RuntimeCommand, MACHINE_PROTOCOL_SINCE};
+#[cfg(unix)]
+use serde_json::{json, Value};
+#[cfg(unix)]
+use std::collections::{HashMap, HashSet};
+#[cfg(unix)]
+use std::io::{self, BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::process::{ChildStdin, Command, Stdio};
+#[cfg(unix)]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(unix)]
+use std::sync::mpsc::{self, Sender};
+#[cfg(unix)]
+use std::sync::{Arc, Mutex};
+#[cfg(unix)]
+use std::time::{Duration, Instant};
+
+/// Directory holding the socket, pid file and log
+pub const DAEMON_DIR: &str = "~/.trinaryvm";
+
+/// How long `daemon start` and `daemon stop` wait for the worker
+#[cfg(unix)]
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(10);
+#[cfg(unix)]
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Subcommand)]
+pub enum DaemonCommands {
+    /// Start a background runtime worker that later commands reuse
+    Start,
+    /// Stop the running worker
+    Stop,
+    /// Show whether a worker is running
+    Status {
+        /// Output JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Run the worker in the foreground (started by `daemon start`)
+    #[command(hide = true)]
+    Serve,
+}
+
+/// Files used by one daemon
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DaemonPaths {
+    pub socket: PathBuf,
+    pub pid: PathBuf,
+    pub log: PathBuf,
+}
+
+impl DaemonPaths {
+    pub fn in_dir(dir: &Path) -> Self {
+        DaemonPaths {
+            socket: dir.join("daemon.sock"),
+            pid: dir.join("daemon.pid"),
+            log: dir.join("daemon.log"),
+        }
+    }
+
+    pub fn default_location() -> Self {
+        Self::in_dir(Path::new(shellexpand::tilde(DAEMON_DIR).as_ref()))
+    }
+}
+
+/// What `daemon status` reports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DaemonStatus {
+    pub pid: u32,
+    pub runtime: PathBuf,
+    pub runtime_version: String,
+    pub uptime_secs: u64,
+    pub requests_served: u64,
+    pub in_flight: usize,
+    pub clients: usize,
+}
+
+#[cfg(unix)]
+pub fn run_daemon_command(command: DaemonCommands) -> Result<(), Box<dyn std::error::Error>> {
+    let paths = DaemonPaths::default_location();
+    match command {
+        DaemonCommands::Start => {
+            let status = start(&paths)?;
+            println!("🟢 Runtime daemon started (pid {})", status.pid);
+            println!("⚙️  Runtime: {} {}", status.runtime.display(), status.runtime_version);
+            println!("🔌 Socket: {}", paths.socket.display());
+            println!("📝 Log: {}", paths.log.display());
+        }
+        DaemonCommands::Stop => {
+            let status = stop(&paths)?;
+            println!("🔴 Runtime daemon stopped (pid {}, {} requests served)", status.pid, status.requests_served);
+        }
+        DaemonCommands::Status { json } => {
+            let status = status(&paths);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&json!({ "running": status.is_some(), "daemon": status }))?);
+                return Ok(());
+            }
+            match status {
+                Some(status) => {
+                    println!("🟢 Runtime daemon running (pid {})", status.pid);
+                    println!("⚙️  Runtime: {} {}", status.runtime.display(), status.runtime_version);
+                    println!("⏱️  Uptime: {} s", status.uptime_secs);
+                    println!("📨 Requests served: {} ({} in flight)", status.requests_served, status.in_flight);
+                    println!("🔌 Socket: {}", paths.socket.display());
+                }
+                None => println!("⚪ Runtime daemon is not running"),
+            }
+        }
+        DaemonCommands::Serve => {
+            let code = serve(&paths)?;
+            std::process::exit(code);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn run_daemon_command(_command: DaemonCommands) -> Result<(), Box<dyn std::error::Error>> {
+    Err("The runtime daemon needs Unix domain sockets and is not available on this platform".into())
+}
+
+/// Send a control message and read the worker's one-line reply
+#[cfg(unix)]
+fn control(socket: &Path, message: &str) -> io::Result<Value> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(STARTUP_TIMEOUT))?;
+    writeln!(stream, "{}", json!({ "daemon": message }))?;
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+    serde_json::from_str(&line).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Status of the running daemon, or `None` (after removing a stale socket)
+#[cfg(unix)]
+pub fn status(paths: &DaemonPaths) -> Option<DaemonStatus> {
+    match control(&paths.socket, "status") {
+        Ok(reply) => serde_json::from_value(reply).ok(),
+        Err(e) => {
+            if e.kind() == io::ErrorKind::ConnectionRefused {
+                let _ = std::fs::remove_file(&paths.socket);
+                let _ = std::fs::remove_file(&paths.pid);
+            }
+            None
+        }
+    }
+}
+
+/// Launch `trinaryvm-cli daemon serve` in the background and wait for it to listen
+#[cfg(unix)]
+pub fn start(paths: &DaemonPaths) -> Result<DaemonStatus, String> {
+    use std::os::unix::process::CommandExt;
+
+    if let Some(running) = status(paths) {
+        return Err(format!("❌ Runtime daemon already running (pid {})", running.pid));
+    }
+    let runtime = resolve_runtime().map_err(|e| e.to_string())?;
+    if runtime.version < MACHINE_PROTOCOL_SINCE {
+        return Err(RequestError::Unsupported(runtime.version).to_string());
+    }
+
+    let dir = paths.socket.parent().unwrap_or(Path::new("."));
+    private_dir(dir).map_err(|e| format!("❌ Cannot create {}: {}", dir.display(), e))?;
+    let log = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&paths.log)
+        .map_err(|e| format!("❌ Cannot open {}: {}", paths.log.display(), e))?;
+    let stdout = log.try_clone().map_err(|e| e.to_string())?;
+    let exe = std::env::current_exe().map_err(|e| format!("❌ Cannot locate trinaryvm-cli: {}", e))?;
+
+    // Its own process group keeps it clear of Ctrl-C in the starting terminal.
+    // An absolute runtime path survives the change of directory and is what
+    // clients compare their own `--runtime` against.
+    let mut worker = Command::new(exe)
+        .arg("--runtime")
+        .arg(runtime.path.canonicalize().unwrap_or(runtime.path))
+        .args(["daemon", "serve"])
+        .current_dir(dir)
+        .stdin(Stdio::null())
+        .stdout(stdout)
+        .stderr(log)
+        .process_group(0)
+        .spawn()
+        .map_err(|e| format!("❌ Failed to start the runtime daemon: {}", e))?;
+
+    let deadline = Instant::now() + STARTUP_TIMEOUT;
+    loop {
+        if let Some(status) = status(paths) {
+            return Ok(status);
+        }
+        if let Ok(Some(exit)) = worker.try_wait() {
+            return Err(format!("❌ Runtime daemon exited during startup ({}); see {}", exit, paths.log.display()));
+        }
+        if Instant::now() > deadline {
+            let _ = worker.kill();
+            return Err(format!("❌ Runtime daemon did not start within {:?}; see {}", STARTUP_TIMEOUT, paths.log.display()));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Ask the daemon to stop and wait for it to remove its socket
+#[cfg(unix)]
+pub fn stop(paths: &DaemonPaths) -> Result<DaemonStatus, String> {
+    let running = status(paths).ok_or("⚪ Runtime daemon is not running")?;
+    control(&paths.socket, "stop").map_err(|e| format!("❌ Failed to stop the runtime daemon: {}", e))?;
+    let deadline = Instant::now() + STARTUP_TIMEOUT + KILL_GRACE;
+    while paths.socket.exists() {
+        if Instant::now() > deadline {
+            return Err(format!("❌ Runtime daemon (pid {}) did not stop", running.pid));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+    Ok(running)
+}
+
+/// Create `dir` (and any parents) readable by this user only; the socket
+/// in it accepts requests that run as them
+#[cfg(unix)]
+fn private_dir(dir: &Path) -> io::Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    std::fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))
+}
+
+/// Send `command` to the running daemon; `None` when no daemon is listening,
+/// or when `runtime` was asked for and the daemon serves a different one
+#[cfg(unix)]
+pub fn request<C: RuntimeCommand>(command: &C, runtime: Option<&Path>) -> Option<Result<C::Output, RequestError>> {
+    let paths = DaemonPaths::default_location();
+    if let Some(runtime) = runtime {
+        if !same_file(&status(&paths)?.runtime, runtime) {
+            return None;
+        }
+    }
+    request_at(&paths.socket, command)
+}
+
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    a == b || matches!((a.canonicalize(), b.canonicalize()), (Ok(a), Ok(b)) if a == b)
+}
+
+#[cfg(unix)]
+fn request_at<C: RuntimeCommand>(socket: &Path, command: &C) -> Option<Result<C::Output, RequestError>> {
+    let mut stream = UnixStream::connect(socket).ok()?;
+    let lost = |e: io::Error| RequestError::Launch(format!("Lost the runtime daemon: {}", e));
+    let id = next_request_id();
+    if let Err(e) = stream.write_all(encode_request(id, command).as_bytes()) {
+        return Some(Err(lost(e)));
+    }
+
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    let mut reply = None;
+    loop {
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => return Some(Err(lost(e))),
+        }
+        handle_line(&line, id, &mut reply);
+        line.clear();
+        if let Some(answer) = reply.take() {
+            return Some(decode_reply::<C>(answer, 1));
+        }
+    }
+    Some(Err(RequestError::Failed {
+        exit_code: 1,
+        message: format!("runtime daemon closed the connection without answering the {} request", C::NAME),
+    }))
+}
+
+/// Run the worker until stopped or until the runtime exits
+#[cfg(unix)]
+fn serve(paths: &DaemonPaths) -> Result<i32, String> {
+    // Outlive the terminal that ran `daemon start`
+    // SAFETY: installs the predefined SIG_IGN disposition
+    unsafe {
+        libc::signal(libc::SIGHUP, libc::SIG_IGN);
+    }
+    let runtime = resolve_runtime().map_err(|e| e.to_string())?;
+    let mut process = Command::new(&runtime.path);
+    process.arg("--machine");
+    serve_with(runtime, process, paths).map_err(|e| format!("❌ Runtime daemon failed: {}", e))
+}
+
+/// Request routing state
+#[cfg(unix)]
+#[derive(Default)]
+struct Routes {
+    next_id: u64,
+    /// Forwarded id → (client, the client's own id)
+    pending: HashMap<u64, (u64, Value)>,
+    /// Queue of lines for each connected client
+    clients: HashMap<u64, Sender<Value>>,
+    served: u64,
+}
+
+#[cfg(unix)]
+struct Worker {
+    runtime: Runtime,
+    runtime_pid: u32,
+    /// Closed (set to `None`) to make the runtime exit at end of input
+    runtime_in: Mutex<Option<ChildStdin>>,
+    routes: Mutex<Routes>,
+    started: Instant,
+    stopping: AtomicBool,
+}
+
+#[cfg(unix)]
+impl Routes {
+    /// Queue `value` for a client; never blocks on the client's socket
+    fn send(&self, client: u64, value: &Value) {
+        if let Some(queue) = self.clients.get(&client) {
+            // A client that went away is dropped when its reader thread ends
+            let _ = queue.send(value.clone());
+        }
+    }
+}
+
+#[cfg(unix)]
+impl Worker {
+    fn reply(&self, client: u64, value: &Value) {
+        self.routes.lock().unwrap().send(client, value);
+    }
+
+    fn status(&self) -> DaemonStatus {
+        let routes = self.routes.lock().unwrap();
+        DaemonStatus {
+            pid: std::process::id(),
+            runtime: self.runtime.path.clone(),
+            runtime_version: self.runtime.version.to_string(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            requests_served: routes.served,
+            in_flight: routes.pending.len(),
+            clients: routes.clients.len(),
+        }
+    }
+
+    /// Close the runtime's input, and kill it if it has not exited after [`KILL_GRACE`]
+    fn stop(&self) {
+        self.stopping.store(true, Ordering::SeqCst);
+        self.runtime_in.lock().unwrap().take();
+        let group = self.runtime_pid;
+        std::thread::spawn(move || {
+            std::thread::sleep(KILL_GRACE);
+            kill_group(group, libc::SIGKILL);
+        });
+    }
+
+    fn serve_client(&self, client: u64, stream: UnixStream) {
+        let Ok(mut writer) = stream.try_clone() else { return };
+        // Writes happen on the client's own thread, so a client that stops
+        // reading holds up nobody else
+        let (queue, lines) = mpsc::channel::<Value>();
+        let sender = std::thread::spawn(move || {
+            for value in lines {
+                if writeln!(writer, "{}", value).is_err() {
+                    break;
+                }
+            }
+        });
+        self.routes.lock().unwrap().clients.insert(client, queue);
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        while matches!(reader.read_line(&mut line), Ok(n) if n > 0) {
+            self.handle_request(client, line.trim_end());
+            line.clear();
+        }
+        self.routes.lock().unwrap().clients.remove(&client);
+        let _ = sender.join();
+    }
+
+    fn handle_request(&self, client: u64, line: &str) {
+        let request = match serde_json::from_str::<Value>(line) {
+            Ok(request) if request.is_object() => request,
+            _ => {
+                let message = format!("not a request object: {}", line);
+                return self.reply(client, &json!({ "event": "error", "id": null, "message": message }));
+            }
+        };
+        match request.get("daemon").and_then(Value::as_str) {
+            Some("status") => self.reply(client, &json!(self.status())),
+            Some("stop") => {
+                self.reply(client, &json!({ "stopping": true }));
+                self.stop();
+            }
+            Some(other) => {
+                let message = format!("unknown daemon control '{}'", other);
+                self.reply(client, &json!({ "event": "error", "id": null, "message": message }));
+            }
+            None => self.forward(client, request),
+        }
+    }
+
+    /// Send a client's request to the runtime under a fresh id
+    fn forward(&self, client: u64, mut request: Value) {
+        let original = request.get("id").cloned().unwrap_or(Value::Null);
+        let id = {
+            let mut routes = self.routes.lock().unwrap();
+            routes.next_id += 1;
+            let id = routes.next_id;
+            routes.pending.insert(id, (client, original.clone()));
+            id
+        };
+        request["id"] = id.into();
+
+        let written = match self.runtime_in.lock().unwrap().as_mut() {
+            Some(stdin) => writeln!(stdin, "{}", request).and_then(|_| stdin.flush()).is_ok(),
+            None => false,
+        };
+        if !written {
+            self.routes.lock().unwrap().pending.remove(&id);
+            let message = "runtime daemon is shutting down";
+            self.reply(client, &json!({ "event": "error", "id": original, "message": message }));
+        }
+    }
+
+    /// Deliver one line of runtime output to whoever is waiting for it
+    fn route(&self, line: &[u8]) {
+        let Ok(mut event) = serde_json::from_slice::<Value>(line) else {
+            eprint!("{}", String::from_utf8_lossy(line));
+            return;
+        };
+        let mut routes = self.routes.lock().unwrap();
+        match event.get("event").and_then(Value::as_str) {
+            Some("result" | "error") => {
+                let forwarded = event.get("id").and_then(Value::as_u64);
+                let Some((client, original)) = forwarded.and_then(|id| routes.pending.remove(&id)) else {
+                    return;
+                };
+                routes.served += 1;
+                event["id"] = original;
+                routes.send(client, &event);
+            }
+            Some("log") => {
+                let waiting: HashSet<u64> = routes.pending.values().map(|(client, _)| *client).collect();
+                for client in waiting {
+                    routes.send(client, &event);
+                }
+            }
+            _ => eprint!("{}", String::from_utf8_lossy(line)),
+        }
+    }
+
+    /// Fail every request still waiting on a runtime that has gone away
+    fn abandon(&self, reason: &str) {
+        let mut routes = self.routes.lock().unwrap();
+        let pending: Vec<(u64, Value)> = routes.pending.drain().map(|(_, route)| route).collect();
+        for (client, original) in pending {
+            routes.send(client, &json!({ "event": "error", "id": original, "message": reason }));
+        }
+    }
+}
+
+#[cfg(unix)]
+fn serve_with(runtime: Runtime, mut process: Command, paths: &DaemonPaths) -> io::Result<i32> {
+    use std::os::unix::process::CommandExt;
+
+    // `start` has already checked that no live daemon owns the socket
+    if let Some(dir) = paths.socket.parent() {
+        private_dir(dir)?;
+    }
+    let _ = std::fs::remove_file(&paths.socket);
+    let listener = UnixListener::bind(&paths.socket)?;
+    process.stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::inherit()).process_group(0);
+    let mut child = match process.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = std::fs::remove_file(&paths.socket);
+            return Err(e);
+        }
+    };
+    std::fs::write(&paths.pid, format!("{}\n", std::process::id()))?;
+    eprintln!(
+        "🟢 Daemon {} serving {} {} on {}",
+        std::process::id(),
+        runtime.path.display(),
+        runtime.version,
+        paths.socket.display()
+    );
+
+    let worker = Arc::new(Worker {
+        runtime,
+        runtime_pid: child.id(),
+        runtime_in: Mutex::new(child.stdin.take()),
+        routes: Mutex::new(Routes::default()),
+        started: Instant::now(),
+        stopping: AtomicBool::new(false),
+    });
+    let closed = Arc::new(AtomicBool::new(false));
+    let acceptor = {
+        let (worker, closed) = (Arc::clone(&worker), Arc::clone(&closed));
+        std::thread::spawn(move || {
+            for (client, stream) in (1..).zip(listener.incoming()) {
+                if closed.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                let worker = Arc::clone(&worker);
+                std::thread::spawn(move || worker.serve_client(client, stream));
+            }
+        })
+    };
+
+    let mut reader = BufReader::new(child.stdout.take().expect("stdout is piped"));
+    let mut line = Vec::new();
+    while matches!(reader.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+        worker.route(&line);
+        line.clear();
+    }
+    let status = child.wait()?;
+    let stopping = worker.stopping.load(Ordering::SeqCst);
+    worker.abandon(if stopping { "runtime daemon stopped" } else { "runtime exited" });
+
+    // Wake the accept loop so it sees `closed`, then remove the files
+    closed.store(true, Ordering::SeqCst);
+    let _ = UnixStream::connect(&paths.socket);
+    let _ = acceptor.join();
+    let _ = std::fs::remove_file(&paths.socket);
+    let _ = std::fs::remove_file(&paths.pid);
+    eprintln!("🔴 Daemon {} exiting: runtime {}", std::process::id(), status);
+    Ok(if stopping { 0 } else { 1 })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::runtime_binary::RuntimeSource;
+    use crate::runtime_protocol::HashRequest;
+
+    #[test]
+    fn test_daemon_multiplexes_requests() {
+        let dir = std::env::temp_dir().join(format!("trinaryvm-daemon-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = DaemonPaths::in_dir(&dir);
+
+        // Answers each request with a digest naming the id it was forwarded under
+        let mut fake = Command::new("sh");
+        fake.args([
+            "-c",
+            r#"while read req; do id=$(echo "$req" | sed 's/.*"id":\([0-9]*\).*/\1/'); echo '{"event":"log","message":"hashing"}'; echo '{"event":"result","id":'$id',"result":{"algorithm":"SHA3-2187","digest":"0'$id'"}}'; done"#,
+        ]);
+        let runtime = Runtime { path: "sh".into(), version: MACHINE_PROTOCOL_SINCE, source: RuntimeSource::Flag };
+        let server = {
+            let paths = paths.clone();
+            std::thread::spawn(move || serve_with(runtime, fake, &paths))
+        };
+        let deadline = Instant::now() + STARTUP_TIMEOUT;
+        while status(&paths).is_none() {
+            assert!(Instant::now() < deadline, "daemon did not start");
+            std::thread::sleep(POLL_INTERVAL);
+        }
+
+        let clients: Vec<_> = (0..4)
+            .map(|_| {
+                let socket = paths.socket.clone();
+                std::thread::spawn(move || {
+                    let request = HashRequest { input: "data.bin".into(), output: None, hex: true };
+                    request_at(&socket, &request).unwrap().unwrap().digest
+                })
+            })
+            .collect();
+        let digests: HashSet<String> = clients.into_iter().map(|c| c.join().unwrap()).collect();
+        assert_eq!(digests.len(), 4);
+
+        let running = status(&paths).unwrap();
+        assert_eq!((running.pid, running.requests_served, running.in_flight), (std::process::id(), 4, 0));
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&dir).unwrap().permissions().mode() & 0o777, 0o700);
+        }
+        assert_eq!(stop(&paths).unwrap().requests_served, 4);
+        assert_eq!(server.join().unwrap().unwrap(), 0);
+        assert!(!paths.socket.exists() && !paths.pid.exists());
+        assert!(request_at(&paths.socket, &HashRequest { input: "x".into(), output: None, hex: true }).is_none());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,326 @@
+//! Resource limits for delegated runtime commands
+//!
+//! `--timeout`, `--max-memory` and `--max-cpu-seconds` bound one command. For
+//! the external runtime:
+//!
+//! - memory and CPU time become `RLIMIT_AS` and `RLIMIT_CPU` on the child
+//! - the timeout is a watchdog that sends SIGTERM to the runtime's process
+//!   group, then SIGKILL after [`KILL_GRACE`]
+//!
+//! A limit hit ends the whole process group, and the CLI exits with
+//! [`EXIT_LIMIT_EXCEEDED`]. Commands that run in-process (`gas-estimate`)
+//! apply the same limits to the CLI itself via [`ResourceLimits::enforce_in_process`].
+
+use clap::Args;
+use std::fmt;
+use std::io;
+use std::process::{Child, Command, ExitStatus};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Exit code when a resource limit stops a command (the code `timeout(1)` uses)
+pub const EXIT_LIMIT_EXCEEDED: i32 = 124;
+
+/// How long a timed-out runtime gets between SIGTERM and SIGKILL
+pub const KILL_GRACE: Duration = Duration::from_secs(3);
+
+/// Per-command resource limits
+#[derive(Debug, Clone, Default, PartialEq, Eq, Args)]
+pub struct ResourceLimits {
+    /// Wall-clock limit, e.g. 90, 30s, 5m, 1h or 500ms
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub timeout: Option<Duration>,
+
+    /// Address-space limit in bytes, or with a K, M or G suffix (e.g. 512M)
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub max_memory: Option<u64>,
+
+    /// CPU time limit in seconds
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_cpu_seconds: Option<u64>,
+}
+
+/// Which limit stopped a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    Timeout(Duration),
+    Memory(u64),
+    CpuTime(u64),
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::Timeout(limit) => write!(f, "⏱️  Timed out after {:?} (--timeout)", limit),
+            LimitExceeded::Memory(bytes) => {
+                write!(f, "💾 Aborted at the {}-byte memory limit (--max-memory)", bytes)
+            }
+            LimitExceeded::CpuTime(seconds) => {
+                write!(f, "🔥 Used its {} s of CPU time (--max-cpu-seconds)", seconds)
+            }
+        }
+    }
+}
+
+/// Parse `90`, `30s`, `5m`, `1h` or `500ms`; a bare number is seconds
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, unit) = s.split_at(split);
+    let value: u64 = digits.parse().map_err(|_| format!("'{}' is not a duration", s))?;
+    let seconds = |multiplier: u64| {
+        value.checked_mul(multiplier).map(Duration::from_secs).ok_or_else(|| format!("'{}' is too large", s))
+    };
+    let duration = match unit {
+        "" | "s" => Duration::from_secs(value),
+        "ms" => Duration::from_millis(value),
+        "m" => seconds(60)?,
+        "h" => seconds(3600)?,
+        _ => return Err(format!("unknown duration unit '{}' (use ms, s, m or h)", unit)),
+    };
+    if duration.is_zero() {
+        return Err("duration must be greater than zero".to_string());
+    }
+    Ok(duration)
+}
+
+/// Parse a byte count with an optional binary `K`, `M` or `G` suffix (`KB`/`KiB` also accepted)
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, unit) = s.split_at(split);
+    let value: u64 = digits.parse().map_err(|_| format!("'{}' is not a size", s))?;
+    let unit = unit.to_ascii_uppercase();
+    let multiplier: u64 = match unit.trim_end_matches("IB").trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => return Err(format!("unknown size unit '{}' (use K, M or G)", unit)),
+    };
+    match value.checked_mul(multiplier) {
+        Some(0) => Err("size must be greater than zero".to_string()),
+        Some(bytes) => Ok(bytes),
+        None => Err(format!("'{}' is too large", s)),
+    }
+}
+
+impl ResourceLimits {
+    pub fn is_empty(&self) -> bool {
+        self.timeout.is_none() && self.max_memory.is_none() && self.max_cpu_seconds.is_none()
+    }
+
+    /// Set the memory and CPU rlimits in the child before it execs
+    #[cfg(unix)]
+    pub fn apply_to(&self, command: &mut Command) {
+        use std::os::unix::process::CommandExt;
+
+        let (memory, cpu) = (self.max_memory, self.max_cpu_seconds);
+        if memory.is_none() && cpu.is_none() {
+            return;
+        }
+        // SAFETY: the closure only calls setrlimit, which is async-signal-safe
+        unsafe {
+            command.pre_exec(move || set_rlimits(memory, cpu));
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn apply_to(&self, _command: &mut Command) {}
+
+    /// Limits are enforced on Unix only; elsewhere asking for one is an error
+    pub fn check_supported(&self) -> Result<(), String> {
+        if cfg!(unix) || self.is_empty() {
+            Ok(())
+        } else {
+            Err("--timeout, --max-memory and --max-cpu-seconds are only supported on Unix".to_string())
+        }
+    }
+
+    /// The limit that explains how the runtime died, if any
+    ///
+    /// CPU exhaustion arrives as SIGXCPU, or as SIGKILL once `cpu_time` (see
+    /// [`wait_for`]) reaches the hard limit; any other SIGKILL came from
+    /// elsewhere. Allocation failure under `RLIMIT_AS` usually aborts the
+    /// runtime (SIGABRT) or crashes it (SIGSEGV, SIGBUS).
+    #[cfg(unix)]
+    pub fn attribute(&self, status: ExitStatus, cpu_time: Duration) -> Option<LimitExceeded> {
+        use std::os::unix::process::ExitStatusExt;
+
+        let signal = status.signal()?;
+        let at_hard_limit = |seconds: u64| cpu_time >= Duration::from_secs(seconds.saturating_add(1));
+        match (self.max_cpu_seconds, self.max_memory) {
+            (Some(seconds), _) if signal == libc::SIGXCPU || (signal == libc::SIGKILL && at_hard_limit(seconds)) => {
+                Some(LimitExceeded::CpuTime(seconds))
+            }
+            (_, Some(bytes)) if [libc::SIGABRT, libc::SIGSEGV, libc::SIGBUS].contains(&signal) => {
+                Some(LimitExceeded::Memory(bytes))
+            }
+            _ => None,
+        }
+    }
+
+    #[cfg(not(unix))]
+    pub fn attribute(&self, _status: ExitStatus, _cpu_time: Duration) -> Option<LimitExceeded> {
+        None
+    }
+
+    /// Apply the limits to this process, for commands that run in-process
+    ///
+    /// The timeout and CPU limit exit with [`EXIT_LIMIT_EXCEEDED`]. Running
+    /// out of memory aborts the CLI with an allocation failure instead.
+    pub fn enforce_in_process(&self) -> Result<(), String> {
+        self.check_supported()?;
+        #[cfg(unix)]
+        {
+            if self.max_cpu_seconds.is_some() {
+                let handler = exit_on_cpu_limit as extern "C" fn(libc::c_int) as libc::sighandler_t;
+                // SAFETY: the handler only calls async-signal-safe functions
+                unsafe {
+                    libc::signal(libc::SIGXCPU, handler);
+                }
+            }
+            set_rlimits(self.max_memory, self.max_cpu_seconds).map_err(|e| format!("Failed to set resource limits: {}", e))?;
+        }
+        if let Some(timeout) = self.timeout {
+            thread::spawn(move || {
+                thread::sleep(timeout);
+                eprintln!("{}", LimitExceeded::Timeout(timeout));
+                std::process::exit(EXIT_LIMIT_EXCEEDED);
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn exit_on_cpu_limit(_signal: libc::c_int) {
+    const MESSAGE: &[u8] = "🔥 CPU time limit reached (--max-cpu-seconds)\n".as_bytes();
+    // SAFETY: write and _exit are async-signal-safe
+    unsafe {
+        libc::write(libc::STDERR_FILENO, MESSAGE.as_ptr().cast(), MESSAGE.len());
+        libc::_exit(EXIT_LIMIT_EXCEEDED);
+    }
+}
+
+/// `RLIMIT_AS` to `memory` bytes; `RLIMIT_CPU` soft at `cpu` seconds (SIGXCPU), hard one second later
+#[cfg(unix)]
+fn set_rlimits(memory: Option<u64>, cpu: Option<u64>) -> std::io::Result<()> {
+    let check = |rc: libc::c_int| if rc == 0 { Ok(()) } else { Err(std::io::Error::last_os_error()) };
+    if let Some(bytes) = memory {
+        let limit = libc::rlimit { rlim_cur: bytes as libc::rlim_t, rlim_max: bytes as libc::rlim_t };
+        // SAFETY: setrlimit reads a valid rlimit struct
+        check(unsafe { libc::setrlimit(libc::RLIMIT_AS, &limit) })?;
+    }
+    if let Some(seconds) = cpu {
+        let limit = libc::rlimit { rlim_cur: seconds as libc::rlim_t, rlim_max: seconds.saturating_add(1) as libc::rlim_t };
+        // SAFETY: as above
+        check(unsafe { libc::setrlimit(libc::RLIMIT_CPU, &limit) })?;
+    }
+    Ok(())
+}
+
+/// Wait for `child` and return its status with the CPU time it (and the
+/// children it reaped) used
+#[cfg(unix)]
+pub fn wait_for(child: &mut Child) -> io::Result<(ExitStatus, Duration)> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let mut status = 0;
+    // SAFETY: rusage is plain old data
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: wait4 only writes to the status and rusage passed in
+        if unsafe { libc::wait4(child.id() as libc::pid_t, &mut status, 0, &mut usage) } >= 0 {
+            break;
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+    let time = |t: libc::timeval| Duration::from_secs(t.tv_sec as u64) + Duration::from_micros(t.tv_usec as u64);
+    Ok((ExitStatus::from_raw(status), time(usage.ru_utime) + time(usage.ru_stime)))
+}
+
+#[cfg(not(unix))]
+pub fn wait_for(child: &mut Child) -> io::Result<(ExitStatus, Duration)> {
+    child.wait().map(|status| (status, Duration::ZERO))
+}
+
+/// Send `signal` to every process in `group`
+#[cfg(unix)]
+pub fn kill_group(group: u32, signal: libc::c_int) {
+    // SAFETY: kill has no memory-safety preconditions
+    unsafe {
+        libc::kill(-(group as libc::pid_t), signal);
+    }
+}
+
+/// Ends a process group when its timeout elapses unless finished first
+pub struct Watchdog {
+    cancel: mpsc::Sender<()>,
+    fired: JoinHandle<bool>,
+}
+
+impl Watchdog {
+    #[cfg(unix)]
+    pub fn start(group: u32, timeout: Duration) -> Self {
+        let (cancel, cancelled) = mpsc::channel::<()>();
+        let fired = thread::spawn(move || {
+            if cancelled.recv_timeout(timeout) != Err(RecvTimeoutError::Timeout) {
+                return false;
+            }
+            kill_group(group, libc::SIGTERM);
+            if cancelled.recv_timeout(KILL_GRACE) == Err(RecvTimeoutError::Timeout) {
+                kill_group(group, libc::SIGKILL);
+            }
+            true
+        });
+        Watchdog { cancel, fired }
+    }
+
+    /// Stop watching; true if the timeout had already fired
+    pub fn finish(self) -> bool {
+        drop(self.cancel);
+        self.fired.join().unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_limits() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_duration("250ms"), Ok(Duration::from_millis(250)));
+        assert!(parse_duration("0s").is_err() && parse_duration("3d").is_err());
+        assert!(parse_duration(&format!("{}h", u64::MAX / 60)).unwrap_err().contains("too large"));
+
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("512M"), Ok(512 << 20));
+        assert_eq!(parse_size("2GiB"), Ok(2 << 30));
+        assert!(parse_size("12T").is_err() && parse_size("M").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_attribute_signals_to_limits() {
+        use std::os::unix::process::ExitStatusExt;
+
+        let limits = ResourceLimits { max_memory: Some(1 << 20), max_cpu_seconds: Some(2), ..Default::default() };
+        let killed_by = |signal: i32| ExitStatus::from_raw(signal);
+        let idle = Duration::ZERO;
+        assert_eq!(limits.attribute(killed_by(libc::SIGXCPU), idle), Some(LimitExceeded::CpuTime(2)));
+        assert_eq!(limits.attribute(killed_by(libc::SIGABRT), idle), Some(LimitExceeded::Memory(1 << 20)));
+        assert_eq!(limits.attribute(killed_by(libc::SIGINT), idle), None);
+        assert_eq!(ResourceLimits::default().attribute(killed_by(libc::SIGXCPU), idle), None);
+
+        // SIGKILL is the CPU limit only once the hard limit (one second past) is used up
+        assert_eq!(limits.attribute(killed_by(libc::SIGKILL), idle), None);
+        assert_eq!(limits.attribute(killed_by(libc::SIGKILL), Duration::from_secs(3)), Some(LimitExceeded::CpuTime(2)));
+    }
+}
@@ -0,0 +1,453 @@
+//! Typed request/response protocol spoken with `trinaryvm --machine`
+//!
+//! The CLI writes one request per line to the runtime's stdin:
+//!
+//! ```json
+//! {"protocol":1,"id":1,"command":"hash","cwd":"/home/me/project","params":{"input":"data.bin","output":null}}
+//! ```
+//!
+//! and reads JSON events from its stdout, one per line. `log` events may
+//! arrive at any time; each request ends with a `result` or `error` event
+//! carrying its `id`:
+//!
+//! ```json
+//! {"event":"log","level":"info","message":"loading keys"}
+//! {"event":"result","id":1,"result":{"algorithm":"SHA3-2187","digest":"9f3a…","output":null}}
+//! {"event":"error","id":1,"code":"file-not-found","message":"data.bin: no such file"}
+//! ```
+//!
+//! Relative paths in `params` are resolved against `cwd`, since a shared
+//! runtime (see `runtime_daemon`) does not run in the caller's directory.
+//!
+//! Every command has a request struct implementing [`RuntimeCommand`] with a
+//! serde-typed result, so the CLI formats text and `--json` output itself.
+//! Runtimes older than [`MACHINE_PROTOCOL_SINCE`] only take argv, which each
+//! request can also produce ([`RuntimeCommand::legacy_args`]). Their output
+//! is not any result schema, so `--json` needs machine mode.
+
+use crate::runtime_binary::Version;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+/// Protocol revision sent with every request
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// First runtime release that understands `--machine`
+pub const MACHINE_PROTOCOL_SINCE: Version = Version::new(1, 1, 0);
+
+/// A runtime command with a typed result
+pub trait RuntimeCommand: Serialize {
+    /// Command name on the wire and in argv
+    const NAME: &'static str;
+    type Output: Serialize + DeserializeOwned;
+
+    /// argv for runtimes without `--machine` (text output only)
+    fn legacy_args(&self) -> Vec<String>;
+
+    /// Human-readable rendering of the result
+    fn render(&self, output: &Self::Output, out: &mut dyn Write) -> io::Result<()>;
+
+    /// Whether the result should end the CLI with exit code 0
+    fn succeeded(&self, _output: &Self::Output) -> bool {
+        true
+    }
+}
+
+#[derive(Serialize)]
+struct Envelope<'a, C> {
+    protocol: u32,
+    id: u64,
+    command: &'static str,
+    /// Directory relative paths in `params` are resolved against
+    cwd: Option<PathBuf>,
+    params: &'a C,
+}
+
+/// One request line, newline included
+pub fn encode_request<C: RuntimeCommand>(id: u64, command: &C) -> String {
+    let cwd = std::env::current_dir().ok();
+    let envelope = Envelope { protocol: PROTOCOL_VERSION, id, command: C::NAME, cwd, params: command };
+    let mut line = serde_json::to_string(&envelope).expect("requests serialize");
+    line.push('\n');
+    line
+}
+
+/// A line of runtime output in machine mode
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum RuntimeEvent {
+    Log {
+        #[serde(default)]
+        level: Option<String>,
+        message: String,
+    },
+    Result {
+        id: u64,
+        result: serde_json::Value,
+    },
+    Error {
+        id: u64,
+        #[serde(default)]
+        code: Option<String>,
+        message: String,
+    },
+}
+
+fn path_arg(path: &std::path::Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+/// `run`: execute TrinaryVM bytecode
+#[derive(Debug, Clone, Serialize)]
+pub struct RunRequest {
+    pub file: String,
+    pub gas_limit: u64,
+    pub debug: bool,
+}
+
+/// How a `run` ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ExecutionStatus {
+    Halted,
+    Reverted,
+    OutOfGas,
+    Fault,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunResult {
+    pub status: ExecutionStatus,
+    pub gas_used: u64,
+    pub gas_limit: u64,
+    pub instructions_executed: u64,
+    /// Hex-encoded RETURN data, if any
+    #[serde(default)]
+    pub return_data: Option<String>,
+    pub execution_time_ms: f64,
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Per-instruction trace lines when `debug` was set
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trace: Vec<String>,
+}
+
+impl RuntimeCommand for RunRequest {
+    const NAME: &'static str = "run";
+    type Output = RunResult;
+
+    fn legacy_args(&self) -> Vec<String> {
+        let mut args = vec!["run".into(), "--file".into(), self.file.clone(), "--gas-limit".into(), self.gas_limit.to_string()];
+        if self.debug {
+            args.push("--debug".into());
+        }
+        args
+    }
+
+    fn render(&self, run: &RunResult, out: &mut dyn Write) -> io::Result<()> {
+        for line in &run.trace {
+            writeln!(out, "  {}", line)?;
+        }
+        let icon = if run.status == ExecutionStatus::Halted { "✅" } else { "❌" };
+        writeln!(out, "{} Status: {:?}", icon, run.status)?;
+        writeln!(out, "⛽ Gas used: {} / {}", run.gas_used, run.gas_limit)?;
+        writeln!(out, "🔢 Instructions: {}", run.instructions_executed)?;
+        writeln!(out, "⏱️  Time: {:.3} ms", run.execution_time_ms)?;
+        if let Some(data) = &run.return_data {
+            writeln!(out, "📤 Return data: 0x{}", data)?;
+        }
+        if let Some(error) = &run.error {
+            writeln!(out, "⚠️  {}", error)?;
+        }
+        Ok(())
+    }
+
+    fn succeeded(&self, run: &RunResult) -> bool {
+        run.status == ExecutionStatus::Halted
+    }
+}
+
+/// `benchmark`: time a suite of runtime operations
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkRequest {
+    pub iterations: usize,
+    pub suite: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkCase {
+    pub name: String,
+    pub mean_us: f64,
+    pub min_us: f64,
+    pub max_us: f64,
+    pub ops_per_second: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    pub suite: String,
+    pub iterations: usize,
+    pub cases: Vec<BenchmarkCase>,
+}
+
+impl RuntimeCommand for BenchmarkRequest {
+    const NAME: &'static str = "benchmark";
+    type Output = BenchmarkResult;
+
+    fn legacy_args(&self) -> Vec<String> {
+        vec!["benchmark".into(), "--iterations".into(), self.iterations.to_string(), "--suite".into(), self.suite.clone()]
+    }
+
+    fn render(&self, bench: &BenchmarkResult, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "📊 Suite '{}' ({} iterations)", bench.suite, bench.iterations)?;
+        for case in &bench.cases {
+            writeln!(
+                out,
+                "  {:<24} {:>10.2} µs  (min {:.2}, max {:.2})  {:>12.0} ops/s",
+                case.name, case.mean_us, case.min_us, case.max_us, case.ops_per_second
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// `keys`: generate a TriFHE keypair
+#[derive(Debug, Clone, Serialize)]
+pub struct KeysRequest {
+    pub out_dir: PathBuf,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeysResult {
+    pub public_key: PathBuf,
+    pub secret_key: PathBuf,
+    #[serde(default)]
+    pub evaluation_key: Option<PathBuf>,
+    pub generation_time_ms: f64,
+}
+
+impl RuntimeCommand for KeysRequest {
+    const NAME: &'static str = "keys";
+    type Output = KeysResult;
+
+    fn legacy_args(&self) -> Vec<String> {
+        vec!["keys".into(), "--out-dir".into(), path_arg(&self.out_dir), "--name".into(), self.name.clone()]
+    }
+
+    fn render(&self, keys: &KeysResult, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "🔑 Generated TriFHE keypair in {:.1} ms", keys.generation_time_ms)?;
+        writeln!(out, "  Public key: {}", keys.public_key.display())?;
+        writeln!(out, "  Secret key: {}", keys.secret_key.display())?;
+        if let Some(eval) = &keys.evaluation_key {
+            writeln!(out, "  Evaluation key: {}", eval.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Result of `encrypt` and `decrypt`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CipherResult {
+    pub output: PathBuf,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub elapsed_ms: f64,
+}
+
+fn render_cipher(verb: &str, result: &CipherResult, out: &mut dyn Write) -> io::Result<()> {
+    writeln!(
+        out,
+        "🔐 {} {} bytes → {} bytes in {:.1} ms",
+        verb, result.input_bytes, result.output_bytes, result.elapsed_ms
+    )?;
+    writeln!(out, "💾 Written to {}", result.output.display())
+}
+
+/// `encrypt`: encrypt a file with a TriFHE public key
+#[derive(Debug, Clone, Serialize)]
+pub struct EncryptRequest {
+    pub input: PathBuf,
+    pub pk: PathBuf,
+    pub output: PathBuf,
+}
+
+impl RuntimeCommand for EncryptRequest {
+    const NAME: &'static str = "encrypt";
+    type Output = CipherResult;
+
+    fn legacy_args(&self) -> Vec<String> {
+        vec![
+            "encrypt".into(),
+            "--input".into(), path_arg(&self.input),
+            "--pk".into(), path_arg(&self.pk),
+            "--output".into(), path_arg(&self.output),
+        ]
+    }
+
+    fn render(&self, result: &CipherResult, out: &mut dyn Write) -> io::Result<()> {
+        render_cipher("Encrypted", result, out)
+    }
+}
+
+/// `decrypt`: decrypt a file with a TriFHE secret key
+#[derive(Debug, Clone, Serialize)]
+pub struct DecryptRequest {
+    pub input: PathBuf,
+    pub sk: PathBuf,
+    pub output: PathBuf,
+}
+
+impl RuntimeCommand for DecryptRequest {
+    const NAME: &'static str = "decrypt";
+    type Output = CipherResult;
+
+    fn legacy_args(&self) -> Vec<String> {
+        vec![
+            "decrypt".into(),
+            "--input".into(), path_arg(&self.input),
+            "--sk".into(), path_arg(&self.sk),
+            "--output".into(), path_arg(&self.output),
+        ]
+    }
+
+    fn render(&self, result: &CipherResult, out: &mut dyn Write) -> io::Result<()> {
+        render_cipher("Decrypted", result, out)
+    }
+}
+
+/// `hash`: SHA3-2187 digest of a file
+#[derive(Debug, Clone, Serialize)]
+pub struct HashRequest {
+    pub input: PathBuf,
+    pub output: Option<PathBuf>,
+    /// Print hex rather than raw digest bytes (CLI-side formatting only)
+    #[serde(skip)]
+    pub hex: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HashResult {
+    pub algorithm: String,
+    /// Hex-encoded digest
+    pub digest: String,
+    /// File the raw digest was also written to
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+}
+
+impl RuntimeCommand for HashRequest {
+    const NAME: &'static str = "hash";
+    type Output = HashResult;
+
+    fn legacy_args(&self) -> Vec<String> {
+        let mut args = vec!["hash".into(), "--input".into(), path_arg(&self.input)];
+        if let Some(output) = &self.output {
+            args.extend(["--output".into(), path_arg(output)]);
+        }
+        if self.hex {
+            args.push("--hex".into());
+        }
+        args
+    }
+
+    fn render(&self, hash: &HashResult, out: &mut dyn Write) -> io::Result<()> {
+        if let Some(path) = &hash.output {
+            return writeln!(out, "💾 {} digest written to {}", hash.algorithm, path.display());
+        }
+        if self.hex {
+            return writeln!(out, "{}", hash.digest);
+        }
+        let raw = hex::decode(&hash.digest).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        out.write_all(&raw)
+    }
+}
+
+/// `validate-impl`: TriFHE implementation self-checks
+#[derive(Debug, Clone, Serialize)]
+pub struct ValidateImplRequest {
+    pub comprehensive: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationCheck {
+    pub name: String,
+    pub passed: bool,
+    #[serde(default)]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidateImplResult {
+    pub checks: Vec<ValidationCheck>,
+}
+
+impl RuntimeCommand for ValidateImplRequest {
+    const NAME: &'static str = "validate-impl";
+    type Output = ValidateImplResult;
+
+    fn legacy_args(&self) -> Vec<String> {
+        let mut args = vec!["validate-impl".to_string()];
+        if self.comprehensive {
+            args.push("--comprehensive".into());
+        }
+        args
+    }
+
+    fn render(&self, report: &ValidateImplResult, out: &mut dyn Write) -> io::Result<()> {
+        for check in &report.checks {
+            let icon = if check.passed { "✅" } else { "❌" };
+            match &check.detail {
+                Some(detail) => writeln!(out, "{} {}: {}", icon, check.name, detail)?,
+                None => writeln!(out, "{} {}", icon, check.name)?,
+            }
+        }
+        let passed = report.checks.iter().filter(|c| c.passed).count();
+        writeln!(out, "📋 {}/{} checks passed", passed, report.checks.len())
+    }
+
+    fn succeeded(&self, report: &ValidateImplResult) -> bool {
+        report.checks.iter().all(|c| c.passed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_envelope() {
+        let request = HashRequest { input: "data.bin".into(), output: None, hex: true };
+        let line = encode_request(7, &request);
+        assert!(line.ends_with('\n'));
+        let mut value: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(value["cwd"].as_str().map(PathBuf::from), std::env::current_dir().ok());
+        value.as_object_mut().unwrap().remove("cwd");
+        assert_eq!(
+            value,
+            serde_json::json!({"protocol": 1, "id": 7, "command": "hash", "params": {"input": "data.bin", "output": null}})
+        );
+        assert_eq!(request.legacy_args(), vec!["hash", "--input", "data.bin", "--hex"]);
+    }
+
+    #[test]
+    fn test_events_and_results_decode() {
+        let log: RuntimeEvent = serde_json::from_str(r#"{"event":"log","message":"loading keys"}"#).unwrap();
+        assert_eq!(log, RuntimeEvent::Log { level: None, message: "loading keys".into() });
+
+        let line = r#"{"event":"result","id":1,"result":{"status":"out-of-gas","gas_used":10,"gas_limit":10,"instructions_executed":4,"execution_time_ms":0.5}}"#;
+        let RuntimeEvent::Result { id, result } = serde_json::from_str(line).unwrap() else {
+            panic!("expected a result event");
+        };
+        let run: RunResult = serde_json::from_value(result).unwrap();
+        assert_eq!((id, run.status, run.return_data.as_deref()), (1, ExecutionStatus::OutOfGas, None));
+
+        let request = RunRequest { file: "a.tritvm".into(), gas_limit: 10, debug: false };
+        assert!(!request.succeeded(&run));
+        let mut text = Vec::new();
+        request.render(&run, &mut text).unwrap();
+        assert!(String::from_utf8(text).unwrap().contains("Gas used: 10 / 10"));
+    }
+}
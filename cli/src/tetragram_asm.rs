@@ -0,0 +1,408 @@
+//! Two-way assembler for the TetragramMicroVM micro-ISA
+//!
+//! Source syntax, one statement per line:
+//!
+//! ```text
+//! ; comments start with ';' or '//'
+//! .const COUNTER R1        ; symbolic register name
+//! .const LIMIT 80          ; numeric constant
+//! start:  CONST4 COUNTER, LIMIT
+//! loop:   SHR4 COUNTER
+//!         CMP4 COUNTER, R2
+//!         BR4 NE, loop     ; labels resolve to glyph indices
+//!         .glyph 0, 𝌆      ; raw glyph values or glyph characters
+//! ```
+//!
+//! Operands: registers `R0`-`R8`, addresses `[n]`, immediates `n` or `#n`,
+//! conditions `AL LT EQ GT NE LE GE`, branch targets as labels or `@n`.
+//! `disassemble` emits source in the same syntax, annotated with glyph index,
+//! opcode glyph and gas, so assemble -> disassemble -> assemble is exact.
+
+use crate::tetragram_vm::{
+    decode_instruction, glyph_to_value, value_to_glyph, Condition, Opcode, OperandKind, GLYPH_COUNT, REGISTER_COUNT,
+};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Write};
+
+/// Built-in `tetragram create` templates as (name, assembly source)
+pub const TEMPLATES: &[(&str, &str)] = &[
+    ("hello", include_str!("../templates/hello.asm")),
+    ("fibonacci", include_str!("../templates/fibonacci.asm")),
+    ("crypto", include_str!("../templates/crypto.asm")),
+    ("test", include_str!("../templates/test.asm")),
+];
+
+/// An assembly error at a 1-based line and column
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// A token with its 1-based column
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    text: &'a str,
+    column: usize,
+}
+
+enum Statement<'a> {
+    Instruction { opcode: Opcode, operands: Vec<Token<'a>> },
+    Glyphs(Vec<u8>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Symbol {
+    Label(usize),
+    Value(u8),
+    Register(u8),
+}
+
+/// Assemble micro-ISA source into glyph values
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut symbols: HashMap<String, Symbol> = HashMap::new();
+    let mut statements = Vec::new();
+    let mut pc = 0;
+
+    // Pass 1: labels, constants and instruction sizes
+    for (n, raw) in source.lines().enumerate() {
+        let line = n + 1;
+        let err = |column: usize, message: String| AsmError { line, column, message };
+        let mut rest = Token { text: strip_comment(raw), column: 1 };
+
+        if let Some(colon) = rest.text.find(':') {
+            let label = sub_token(rest, 0, colon);
+            if is_identifier(label.text) {
+                define(&mut symbols, label.text, Symbol::Label(pc)).map_err(|m| err(label.column, m))?;
+                rest = sub_token(rest, colon + 1, rest.text.len());
+            }
+        }
+        if rest.text.is_empty() {
+            continue;
+        }
+
+        let (head, args) = split_head(rest);
+        match head.text.to_ascii_lowercase().as_str() {
+            ".const" => {
+                let parts: Vec<Token> = split_whitespace(args);
+                if parts.len() != 2 || !is_identifier(parts[0].text) {
+                    return Err(err(head.column, ".const expects a name and a value".to_string()));
+                }
+                let symbol = match parse_register(parts[1].text) {
+                    Some(reg) => Symbol::Register(reg),
+                    None => Symbol::Value(parse_number(parts[1].text).map_err(|m| err(parts[1].column, m))?),
+                };
+                define(&mut symbols, parts[0].text, symbol).map_err(|m| err(parts[0].column, m))?;
+            }
+            ".glyph" => {
+                let values = parse_glyphs(args).map_err(|(column, m)| err(column, m))?;
+                pc += values.len();
+                statements.push((line, Statement::Glyphs(values)));
+            }
+            mnemonic => {
+                let opcode = Opcode::from_mnemonic(mnemonic)
+                    .ok_or_else(|| err(head.column, format!("unknown mnemonic '{}'", head.text)))?;
+                let operands = split_operands(args);
+                if operands.len() != opcode.operand_count() {
+                    return Err(err(
+                        head.column,
+                        format!("{} expects {} operand(s), found {}", opcode, opcode.operand_count(), operands.len()),
+                    ));
+                }
+                pc += 1 + operands.len();
+                statements.push((line, Statement::Instruction { opcode, operands }));
+            }
+        }
+    }
+
+    // Pass 2: encode operands now that every label is known
+    let mut program = Vec::with_capacity(pc);
+    for (line, statement) in statements {
+        match statement {
+            Statement::Glyphs(values) => program.extend(values),
+            Statement::Instruction { opcode, operands } => {
+                program.push(opcode.value());
+                for (kind, token) in opcode.operand_kinds().iter().zip(&operands) {
+                    let value = encode_operand(*kind, token.text, &symbols)
+                        .map_err(|message| AsmError { line, column: token.column, message })?;
+                    program.push(value);
+                }
+            }
+        }
+    }
+    Ok(program)
+}
+
+fn define(symbols: &mut HashMap<String, Symbol>, name: &str, symbol: Symbol) -> Result<(), String> {
+    if parse_register(name).is_some() || Condition::ALL.iter().any(|c| c.mnemonic().eq_ignore_ascii_case(name)) {
+        return Err(format!("'{}' is a reserved name", name));
+    }
+    if symbols.insert(name.to_string(), symbol).is_some() {
+        return Err(format!("'{}' is already defined", name));
+    }
+    Ok(())
+}
+
+fn encode_operand(kind: OperandKind, text: &str, symbols: &HashMap<String, Symbol>) -> Result<u8, String> {
+    let symbol = symbols.get(text).copied();
+    match kind {
+        OperandKind::Register => match (parse_register(text), symbol) {
+            (Some(reg), _) | (None, Some(Symbol::Register(reg))) => Ok(reg),
+            _ => Err(format!("expected a register R0-R{}, found '{}'", REGISTER_COUNT - 1, text)),
+        },
+        OperandKind::Condition => {
+            if let Some(pos) = Condition::ALL.iter().position(|c| c.mnemonic().eq_ignore_ascii_case(text)) {
+                return Ok(pos as u8);
+            }
+            let value = scalar(text, symbol)?;
+            if Condition::from_value(value).is_none() {
+                return Err(format!("invalid branch condition {}", value));
+            }
+            Ok(value)
+        }
+        OperandKind::Target => match symbol {
+            Some(Symbol::Label(index)) if index < GLYPH_COUNT as usize => Ok(index as u8),
+            Some(Symbol::Label(index)) => Err(format!("label '{}' at glyph {} is beyond branch range 0-80", text, index)),
+            _ => scalar(text.strip_prefix('@').unwrap_or(text), symbol),
+        },
+        OperandKind::Address => {
+            let inner = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')).map(str::trim).unwrap_or(text);
+            scalar(inner, symbols.get(inner).copied())
+        }
+        OperandKind::Immediate => {
+            let inner = text.strip_prefix('#').unwrap_or(text);
+            scalar(inner, symbols.get(inner).copied())
+        }
+        OperandKind::Rotation => scalar(text, symbol),
+    }
+}
+
+fn scalar(text: &str, symbol: Option<Symbol>) -> Result<u8, String> {
+    match symbol {
+        Some(Symbol::Value(value)) => Ok(value),
+        Some(Symbol::Label(_)) => Err(format!("label '{}' used where a value is expected", text)),
+        Some(Symbol::Register(_)) => Err(format!("register alias '{}' used where a value is expected", text)),
+        None => parse_number(text),
+    }
+}
+
+fn parse_number(text: &str) -> Result<u8, String> {
+    match text.parse::<u32>() {
+        Ok(v) if v < GLYPH_COUNT as u32 => Ok(v as u8),
+        Ok(v) => Err(format!("value {} out of range 0-80", v)),
+        Err(_) => Err(format!("expected a number, found '{}'", text)),
+    }
+}
+
+fn parse_register(text: &str) -> Option<u8> {
+    let digits = text.strip_prefix('R').or_else(|| text.strip_prefix('r'))?;
+    let reg: u8 = digits.parse().ok()?;
+    ((reg as usize) < REGISTER_COUNT && digits.len() == 1).then_some(reg)
+}
+
+/// `.glyph` operands: numbers and/or glyph characters, separated by commas or spaces
+fn parse_glyphs(args: Token) -> Result<Vec<u8>, (usize, String)> {
+    let mut values = Vec::new();
+    for part in split_whitespace(args).into_iter().flat_map(split_operands) {
+        if part.text.chars().next().is_some_and(|c| c.is_ascii_digit()) {
+            values.push(parse_number(part.text).map_err(|m| (part.column, m))?);
+        } else {
+            for (offset, ch) in part.text.chars().enumerate() {
+                let value = glyph_to_value(ch).ok_or((part.column + offset, format!("invalid glyph '{}'", ch)))?;
+                values.push(value);
+            }
+        }
+    }
+    if values.is_empty() {
+        return Err((args.column, ".glyph expects at least one value".to_string()));
+    }
+    Ok(values)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let end = [line.find(';'), line.find("//")].into_iter().flatten().min().unwrap_or(line.len());
+    line[..end].trim_end()
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Sub-slice of a token by byte range, trimmed, keeping the column accurate
+fn sub_token(token: Token, start: usize, end: usize) -> Token {
+    let slice = &token.text[start..end];
+    let trimmed = slice.trim_start();
+    let column = token.column + token.text[..start].chars().count() + (slice.len() - trimmed.len());
+    Token { text: trimmed.trim_end(), column }
+}
+
+fn split_head(token: Token) -> (Token, Token) {
+    let token = sub_token(token, 0, token.text.len());
+    let split = token.text.find(char::is_whitespace).unwrap_or(token.text.len());
+    (sub_token(token, 0, split), sub_token(token, split, token.text.len()))
+}
+
+fn split_whitespace(token: Token) -> Vec<Token> {
+    let mut parts = Vec::new();
+    let mut rest = sub_token(token, 0, token.text.len());
+    while !rest.text.is_empty() {
+        let (head, tail) = split_head(rest);
+        parts.push(head);
+        rest = tail;
+    }
+    parts
+}
+
+fn split_operands(token: Token) -> Vec<Token> {
+    if token.text.trim().is_empty() {
+        return Vec::new();
+    }
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (i, ch) in token.text.char_indices() {
+        if ch == ',' {
+            parts.push(sub_token(token, start, i));
+            start = i + 1;
+        }
+    }
+    parts.push(sub_token(token, start, token.text.len()));
+    parts.retain(|t| !t.text.is_empty());
+    parts
+}
+
+/// Render an annotated assembly listing of a glyph program
+///
+/// Branch targets that land on an instruction boundary get `L<index>` labels;
+/// glyphs that do not start a valid instruction are emitted as `.glyph <value>`.
+pub fn disassemble(program: &[u8]) -> String {
+    // First pass: instruction boundaries and branch targets
+    let mut starts = BTreeSet::new();
+    let mut targets = BTreeSet::new();
+    let mut pc = 0;
+    while pc < program.len() {
+        starts.insert(pc);
+        pc += match decode_instruction(program, pc) {
+            Ok(instr) => {
+                if instr.opcode == Opcode::Br4 {
+                    targets.insert(instr.operands[1] as usize);
+                }
+                instr.size()
+            }
+            Err(_) => 1,
+        };
+    }
+    starts.insert(program.len());
+    let labels: BTreeSet<usize> = targets.intersection(&starts).copied().collect();
+    let label = |index: usize| format!("L{:04}", index);
+
+    let mut out = String::new();
+    let _ = writeln!(out, "; Supreme Mystery micro-ISA listing ({} glyphs)", program.len());
+
+    let mut pc = 0;
+    while pc < program.len() {
+        if labels.contains(&pc) {
+            let _ = writeln!(out, "{}:", label(pc));
+        }
+        let (text, note, size) = match decode_instruction(program, pc) {
+            Ok(instr) => {
+                let note = format!("gas {}", instr.opcode.gas_cost());
+                let text = match instr.opcode {
+                    Opcode::Br4 if labels.contains(&(instr.operands[1] as usize)) => {
+                        let cond = Condition::from_value(instr.operands[0]).map_or("?", |c| c.mnemonic());
+                        format!("BR4 {}, {}", cond, label(instr.operands[1] as usize))
+                    }
+                    _ => instr.to_string(),
+                };
+                (text, note, instr.size())
+            }
+            Err(e) => (format!(".glyph {}", program[pc]), e.to_string(), 1),
+        };
+        let _ = writeln!(out, "    {:<24} ; @{:04} {} {}", text, pc, value_to_glyph(program[pc]), note);
+        pc += size;
+    }
+    if labels.contains(&program.len()) {
+        let _ = writeln!(out, "{}:", label(program.len()));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    #[test]
+    fn test_disassemble_annotates_and_covers_invalid_glyphs() {
+        // CONST4 R0, 80; BR4 GT, @0; then an invalid opcode and a truncated LD4
+        let listing = disassemble(&[12, 0, 80, 10, 3, 0, 40, 1, 2]);
+        let lines: Vec<&str> = listing.lines().skip(1).collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[0], "L0000:");
+        assert!(lines[1].trim_start().starts_with("CONST4 R0, 80"));
+        assert!(lines[1].ends_with("@0000 𝌒 gas 3"));
+        assert!(lines[2].contains("BR4 GT, L0000") && lines[2].contains("gas 9"));
+        assert!(lines[3].contains(".glyph 40") && lines[3].contains("invalid opcode 40"));
+        assert!(lines[4].contains(".glyph 1") && lines[5].contains(".glyph 2"));
+    }
+
+    #[test]
+    fn test_assemble_labels_constants_and_directives() {
+        let source = "
+            .const COUNTER R1   ; symbolic register
+            .const LIMIT 80
+            start: CONST4 COUNTER, #LIMIT
+            loop:  SHR4 COUNTER
+                   CMP4 COUNTER, r2
+                   BR4 NE, loop // back edge
+                   ST4 [3], R0
+                   BR4 al, end
+                   .glyph 0, 𝌆𝌇
+            end:
+        ";
+        let program = assemble(source).unwrap();
+        assert_eq!(program, vec![12, 1, 80, 8, 1, 9, 1, 2, 10, 4, 3, 2, 3, 0, 10, 0, 20, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_assemble_errors_have_positions() {
+        let err = assemble("NOP\n  LD4 R9, [3]").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 7));
+        assert!(err.message.contains("register"));
+
+        assert_eq!(assemble("FOO").unwrap_err().column, 1);
+        assert!(assemble("BR4 AL, nowhere").is_err());
+        assert!(assemble("PUSHOUT R1, R2").unwrap_err().message.contains("expects 1"));
+        assert!(assemble("x: NOP\nx: NOP").unwrap_err().message.contains("already defined"));
+        assert!(assemble("CONST4 R0, 81").is_err());
+    }
+
+    #[test]
+    fn test_templates_assemble_and_run() {
+        use crate::tetragram_vm::TetragramMicroVM;
+        for (name, source) in TEMPLATES {
+            let program = assemble(source).unwrap_or_else(|e| panic!("{}: {}", name, e));
+            let result = TetragramMicroVM::new(program).run();
+            assert!(result.fault.is_none(), "{}: {:?}", name, result.fault);
+            assert!(!result.output_buffer.is_empty(), "{}", name);
+        }
+    }
+
+    quickcheck! {
+        fn prop_disassemble_assemble_is_exact(glyphs: Vec<u8>) -> bool {
+            let program: Vec<u8> = glyphs.into_iter().map(|v| v % GLYPH_COUNT).collect();
+            let source = disassemble(&program);
+            assemble(&source) == Ok(program.clone()) && disassemble(&assemble(&source).unwrap()) == source
+        }
+    }
+}
@@ -0,0 +1,577 @@
+//! Control-flow graph and data-flow analysis of micro-ISA programs
+//!
+//! The graph is built by recursive descent from glyph 0: every instruction
+//! the VM could fetch is decoded, and basic blocks start at the entry point,
+//! at BR4 targets and after each BR4. Control leaves the program by falling
+//! off (or branching to) the end, which halts, or by a decode error or
+//! out-of-range branch, which faults.
+//!
+//! On top of the graph `tetragram analyze` reports:
+//!
+//! * `A001` glyphs no path from the entry point can reach
+//! * `A002` loops (cyclic strongly connected components) with no edge out and
+//!   no PUSHOUT, which spin until the gas limit
+//! * `A003` register reads not preceded by a write on every path; registers
+//!   start at zero, so this is legal but usually a missing CONST4
+//! * `A004` ST4 stores whose value no LD4 can observe before it is overwritten
+//!
+//! [`Cfg::to_dot`] renders the graph for Graphviz.
+
+use crate::tetragram_validate::{Diagnostic, ValidationReport};
+use crate::tetragram_vm::{decode_instruction, Condition, Instruction, Opcode, VmError, REGISTER_COUNT};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use std::ops::Range;
+
+/// Where control goes when it leaves a block
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(tag = "kind", content = "pc", rename_all = "snake_case")]
+pub enum Successor {
+    /// The block starting at this glyph index
+    Block(usize),
+    Halt,
+    Fault,
+}
+
+/// Why an edge is taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EdgeKind {
+    Fallthrough,
+    Taken(Condition),
+}
+
+/// A straight-line run of instructions with a single entry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub instructions: Vec<Instruction>,
+    pub successors: Vec<(Successor, EdgeKind)>,
+}
+
+impl BasicBlock {
+    /// Glyph index just past the last instruction
+    pub fn end(&self) -> usize {
+        self.instructions.last().map_or(self.start, Instruction::next_pc)
+    }
+}
+
+/// A cycle in the graph (a strongly connected component)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Loop {
+    /// Lowest block start in the loop
+    pub header: usize,
+    /// Start of every block in the loop
+    pub blocks: Vec<usize>,
+    /// No edge leaves the loop, so once entered it only stops on gas
+    pub closed: bool,
+    /// The loop body executes PUSHOUT
+    pub has_output: bool,
+}
+
+/// Control-flow graph of a glyph program
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    pub glyph_count: usize,
+    pub entry: Successor,
+    pub blocks: BTreeMap<usize, BasicBlock>,
+    /// Reachable glyph indices where the VM faults on decode or branch
+    pub faults: BTreeMap<usize, VmError>,
+}
+
+/// A register read that is not preceded by a write on every path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RegisterRead {
+    pub pc: usize,
+    pub opcode: Opcode,
+    pub register: u8,
+}
+
+/// An ST4 whose value is never loaded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DeadStore {
+    pub pc: usize,
+    pub address: u8,
+}
+
+/// Serializable summary of the control- and data-flow findings
+#[derive(Debug, Clone, Serialize)]
+pub struct FlowAnalysis {
+    pub basic_blocks: usize,
+    pub edges: usize,
+    pub reachable_glyphs: usize,
+    pub unreachable: Vec<Range<usize>>,
+    pub loops: Vec<Loop>,
+    pub uninitialized_reads: Vec<RegisterRead>,
+    pub dead_stores: Vec<DeadStore>,
+}
+
+impl FlowAnalysis {
+    /// Findings as `A00x` diagnostics positioned in the validated source
+    pub fn diagnostics(&self, source: &ValidationReport) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for range in &self.unreachable {
+            let message = format!("glyphs {}-{} are unreachable ({} glyphs)", range.start, range.end - 1, range.len());
+            diagnostics.push(source.diagnostic("A001", range.start, message));
+        }
+        for l in self.loops.iter().filter(|l| l.closed && !l.has_output) {
+            let message = format!("loop at @{} never exits and never executes PUSHOUT; it runs until out of gas", l.header);
+            diagnostics.push(source.diagnostic("A002", l.header, message));
+        }
+        for read in &self.uninitialized_reads {
+            let message = format!(
+                "{} reads R{} before it is written on some path (registers start at zero)",
+                read.opcode.mnemonic(),
+                read.register
+            );
+            diagnostics.push(source.diagnostic("A003", read.pc, message));
+        }
+        for store in &self.dead_stores {
+            let message = format!("ST4 to [{}] is never loaded before being overwritten or the program ends", store.address);
+            diagnostics.push(source.diagnostic("A004", store.pc, message));
+        }
+        diagnostics
+    }
+}
+
+/// Registers read and written by an instruction, as bit masks
+pub fn register_effects(instr: &Instruction) -> (u16, u16) {
+    let bit = |i: usize| 1u16 << instr.operands[i];
+    match instr.opcode {
+        Opcode::Nop | Opcode::Br4 => (0, 0),
+        Opcode::Ld4 | Opcode::Const4 => (0, bit(0)),
+        Opcode::St4 => (bit(1), 0),
+        Opcode::Mux4 | Opcode::Xor4 => (bit(0) | bit(1), bit(0)),
+        Opcode::Rot4 | Opcode::Inv4 | Opcode::Shl4 | Opcode::Shr4 => (bit(0), bit(0)),
+        Opcode::Cmp4 => (bit(0) | bit(1), 0),
+        Opcode::PushOut => (bit(0), 0),
+    }
+}
+
+impl Cfg {
+    /// Decode every instruction reachable from glyph 0 and split it into blocks
+    pub fn build(program: &[u8]) -> Self {
+        let len = program.len();
+        let mut instructions = BTreeMap::new();
+        let mut faults = BTreeMap::new();
+        let mut leaders = BTreeSet::from([0]);
+        let mut pending = vec![0];
+
+        while let Some(pc) = pending.pop() {
+            if pc >= len || instructions.contains_key(&pc) || faults.contains_key(&pc) {
+                continue;
+            }
+            let instr = match decode_instruction(program, pc) {
+                Ok(instr) => instr,
+                Err(e) => {
+                    faults.insert(pc, e);
+                    continue;
+                }
+            };
+            let next = instr.next_pc();
+            if instr.opcode == Opcode::Br4 {
+                let target = instr.operands[1] as usize;
+                if target > len {
+                    faults.insert(pc, VmError::InvalidBranchTarget { pc, target });
+                } else {
+                    leaders.insert(target);
+                    pending.push(target);
+                }
+                leaders.insert(next);
+                if instr.operands[0] != Condition::Always as u8 {
+                    pending.push(next);
+                }
+            } else {
+                pending.push(next);
+            }
+            instructions.insert(pc, instr);
+        }
+
+        let successor = |pc: usize| match pc {
+            pc if pc == len => Successor::Halt,
+            pc if instructions.contains_key(&pc) => Successor::Block(pc),
+            _ => Successor::Fault,
+        };
+
+        let mut blocks = BTreeMap::new();
+        for &start in leaders.iter().filter(|pc| instructions.contains_key(pc)) {
+            let mut block = BasicBlock { start, instructions: Vec::new(), successors: Vec::new() };
+            let mut pc = start;
+            loop {
+                let instr = instructions[&pc].clone();
+                let next = instr.next_pc();
+                if instr.opcode == Opcode::Br4 {
+                    let cond = Condition::from_value(instr.operands[0]).expect("validated by decoder");
+                    let target = instr.operands[1] as usize;
+                    let taken = if target > len { Successor::Fault } else { successor(target) };
+                    block.successors.push((taken, EdgeKind::Taken(cond)));
+                    if cond != Condition::Always {
+                        block.successors.push((successor(next), EdgeKind::Fallthrough));
+                    }
+                    block.instructions.push(instr);
+                    break;
+                }
+                block.instructions.push(instr);
+                if leaders.contains(&next) || !instructions.contains_key(&next) {
+                    block.successors.push((successor(next), EdgeKind::Fallthrough));
+                    break;
+                }
+                pc = next;
+            }
+            blocks.insert(start, block);
+        }
+
+        Cfg { glyph_count: len, entry: successor(0), blocks, faults }
+    }
+
+    fn block_successors(&self, start: usize) -> impl Iterator<Item = usize> + '_ {
+        self.blocks[&start].successors.iter().filter_map(|(succ, _)| match succ {
+            Successor::Block(pc) => Some(*pc),
+            _ => None,
+        })
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.blocks.values().map(|b| b.successors.len()).sum()
+    }
+
+    /// Which glyphs some execution can fetch (including faulting glyphs)
+    pub fn reachable_glyphs(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.glyph_count];
+        for block in self.blocks.values() {
+            for instr in &block.instructions {
+                reachable[instr.pc..instr.next_pc()].fill(true);
+            }
+        }
+        for &pc in self.faults.keys() {
+            reachable[pc] = true;
+        }
+        reachable
+    }
+
+    /// Maximal runs of unreachable glyphs
+    pub fn unreachable_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        for (pc, reachable) in self.reachable_glyphs().into_iter().enumerate() {
+            if reachable {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some(range) if range.end == pc => range.end = pc + 1,
+                _ => ranges.push(pc..pc + 1),
+            }
+        }
+        ranges
+    }
+
+    /// Strongly connected components that contain a cycle (Tarjan)
+    pub fn loops(&self) -> Vec<Loop> {
+        struct Tarjan<'a> {
+            cfg: &'a Cfg,
+            index: BTreeMap<usize, usize>,
+            low: BTreeMap<usize, usize>,
+            stack: Vec<usize>,
+            on_stack: BTreeSet<usize>,
+            components: Vec<Vec<usize>>,
+        }
+
+        impl Tarjan<'_> {
+            fn visit(&mut self, node: usize) {
+                let idx = self.index.len();
+                self.index.insert(node, idx);
+                self.low.insert(node, idx);
+                self.stack.push(node);
+                self.on_stack.insert(node);
+                for next in self.cfg.block_successors(node).collect::<Vec<_>>() {
+                    if !self.index.contains_key(&next) {
+                        self.visit(next);
+                        let low = self.low[&node].min(self.low[&next]);
+                        self.low.insert(node, low);
+                    } else if self.on_stack.contains(&next) {
+                        let low = self.low[&node].min(self.index[&next]);
+                        self.low.insert(node, low);
+                    }
+                }
+                if self.low[&node] == self.index[&node] {
+                    let mut component = Vec::new();
+                    while let Some(member) = self.stack.pop() {
+                        self.on_stack.remove(&member);
+                        component.push(member);
+                        if member == node {
+                            break;
+                        }
+                    }
+                    component.sort_unstable();
+                    self.components.push(component);
+                }
+            }
+        }
+
+        let mut tarjan = Tarjan {
+            cfg: self,
+            index: BTreeMap::new(),
+            low: BTreeMap::new(),
+            stack: Vec::new(),
+            on_stack: BTreeSet::new(),
+            components: Vec::new(),
+        };
+        for &start in self.blocks.keys() {
+            if !tarjan.index.contains_key(&start) {
+                tarjan.visit(start);
+            }
+        }
+
+        let mut loops: Vec<Loop> = tarjan
+            .components
+            .into_iter()
+            .filter(|c| c.len() > 1 || self.block_successors(c[0]).any(|next| next == c[0]))
+            .map(|blocks| {
+                let members: BTreeSet<usize> = blocks.iter().copied().collect();
+                let closed = blocks.iter().all(|start| {
+                    self.blocks[start].successors.iter().all(|(succ, _)| matches!(succ, Successor::Block(pc) if members.contains(pc)))
+                });
+                let has_output = blocks
+                    .iter()
+                    .any(|start| self.blocks[start].instructions.iter().any(|i| i.opcode == Opcode::PushOut));
+                Loop { header: blocks[0], blocks, closed, has_output }
+            })
+            .collect();
+        loops.sort_by_key(|l| l.header);
+        loops
+    }
+
+    /// Predecessor block starts of every block
+    pub fn predecessors(&self) -> BTreeMap<usize, Vec<usize>> {
+        let mut preds: BTreeMap<usize, Vec<usize>> = self.blocks.keys().map(|&pc| (pc, Vec::new())).collect();
+        for &start in self.blocks.keys() {
+            for next in self.block_successors(start) {
+                preds.get_mut(&next).expect("successor is a block").push(start);
+            }
+        }
+        preds
+    }
+
+    /// Register reads that some path reaches without a prior write
+    pub fn uninitialized_reads(&self) -> Vec<RegisterRead> {
+        const ALL: u16 = (1 << REGISTER_COUNT) - 1;
+        let preds = self.predecessors();
+        // Must-written registers at block exit, starting from "everything" (top)
+        let mut out: BTreeMap<usize, u16> = self.blocks.keys().map(|&pc| (pc, ALL)).collect();
+        let block_in = |start: usize, out: &BTreeMap<usize, u16>| {
+            let from_preds = preds[&start].iter().fold(ALL, |acc, p| acc & out[p]);
+            if self.entry == Successor::Block(start) { 0 } else { from_preds }
+        };
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (&start, block) in &self.blocks {
+                let mut written = block_in(start, &out);
+                for instr in &block.instructions {
+                    written |= register_effects(instr).1;
+                }
+                if out[&start] != written {
+                    out.insert(start, written);
+                    changed = true;
+                }
+            }
+        }
+
+        let mut reads = Vec::new();
+        for (&start, block) in &self.blocks {
+            let mut written = block_in(start, &out);
+            for instr in &block.instructions {
+                let (read, write) = register_effects(instr);
+                for register in 0..REGISTER_COUNT as u8 {
+                    if read & !written & (1 << register) != 0 {
+                        reads.push(RegisterRead { pc: instr.pc, opcode: instr.opcode, register });
+                    }
+                }
+                written |= write;
+            }
+        }
+        reads
+    }
+
+    /// ST4 instructions whose stored value no LD4 can observe
+    pub fn dead_stores(&self) -> Vec<DeadStore> {
+        type Reaching = BTreeSet<(u8, usize)>;
+        let preds = self.predecessors();
+        let transfer = |block: &BasicBlock, mut state: Reaching, used: &mut BTreeSet<usize>| {
+            for instr in &block.instructions {
+                match instr.opcode {
+                    Opcode::St4 => {
+                        let address = instr.operands[0];
+                        state.retain(|(a, _)| *a != address);
+                        state.insert((address, instr.pc));
+                    }
+                    Opcode::Ld4 => {
+                        let address = instr.operands[1];
+                        used.extend(state.iter().filter(|(a, _)| *a == address).map(|(_, pc)| *pc));
+                    }
+                    _ => {}
+                }
+            }
+            state
+        };
+
+        // Reaching stores: may-analysis, so iterate from empty sets
+        let mut out: BTreeMap<usize, Reaching> = self.blocks.keys().map(|&pc| (pc, Reaching::new())).collect();
+        let mut used = BTreeSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (&start, block) in &self.blocks {
+                let state: Reaching = preds[&start].iter().flat_map(|p| out[p].iter().copied()).collect();
+                let state = transfer(block, state, &mut used);
+                if out[&start] != state {
+                    out.insert(start, state);
+                    changed = true;
+                }
+            }
+        }
+
+        self.blocks
+            .values()
+            .flat_map(|block| &block.instructions)
+            .filter(|instr| instr.opcode == Opcode::St4 && !used.contains(&instr.pc))
+            .map(|instr| DeadStore { pc: instr.pc, address: instr.operands[0] })
+            .collect()
+    }
+
+    /// Run every analysis
+    pub fn analyze(&self) -> FlowAnalysis {
+        FlowAnalysis {
+            basic_blocks: self.blocks.len(),
+            edges: self.edge_count(),
+            reachable_glyphs: self.reachable_glyphs().iter().filter(|&&r| r).count(),
+            unreachable: self.unreachable_ranges(),
+            loops: self.loops(),
+            uninitialized_reads: self.uninitialized_reads(),
+            dead_stores: self.dead_stores(),
+        }
+    }
+
+    /// Graphviz rendering: one box per block, halt and fault as sink nodes
+    pub fn to_dot(&self) -> String {
+        let node = |succ: &Successor| match succ {
+            Successor::Block(pc) => format!("b{}", pc),
+            Successor::Halt => "halt".to_string(),
+            Successor::Fault => "fault".to_string(),
+        };
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=\"monospace\"];\n");
+        let _ = writeln!(dot, "    entry [shape=point];\n    entry -> {};", node(&self.entry));
+        for block in self.blocks.values() {
+            let mut label = format!("@{:04}-@{:04}\\l", block.start, block.end().saturating_sub(1));
+            for instr in &block.instructions {
+                let _ = write!(label, "{}\\l", instr);
+            }
+            let _ = writeln!(dot, "    b{} [label=\"{}\"];", block.start, label);
+        }
+        let mut sinks = BTreeSet::new();
+        for block in self.blocks.values() {
+            for (succ, kind) in &block.successors {
+                if !matches!(succ, Successor::Block(_)) {
+                    sinks.insert(*succ);
+                }
+                let label = match kind {
+                    EdgeKind::Fallthrough => String::new(),
+                    EdgeKind::Taken(cond) => format!(" [label=\"{}\"]", cond.mnemonic()),
+                };
+                let _ = writeln!(dot, "    b{} -> {}{};", block.start, node(succ), label);
+            }
+        }
+        if matches!(self.entry, Successor::Halt | Successor::Fault) {
+            sinks.insert(self.entry);
+        }
+        for sink in sinks {
+            let shape = if sink == Successor::Halt { "doublecircle" } else { "octagon" };
+            let _ = writeln!(dot, "    {} [shape={}];", node(&sink), shape);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetragram_asm::assemble;
+
+    fn cfg(source: &str) -> Cfg {
+        Cfg::build(&assemble(source).expect("test program assembles"))
+    }
+
+    #[test]
+    fn test_blocks_edges_and_unreachable_glyphs() {
+        let cfg = cfg("
+            CONST4 R0, 41
+            CMP4 R0, R1
+            BR4 EQ, done
+            PUSHOUT R0
+            BR4 AL, done
+            NOP
+            NOP
+        done:
+        ");
+        assert_eq!(cfg.blocks.keys().copied().collect::<Vec<_>>(), vec![0, 9]);
+        assert_eq!(cfg.blocks[&0].successors, vec![
+            (Successor::Halt, EdgeKind::Taken(Condition::Eq)),
+            (Successor::Block(9), EdgeKind::Fallthrough),
+        ]);
+        assert_eq!(cfg.unreachable_ranges(), vec![14..16]);
+        assert!(cfg.loops().is_empty());
+        let dot = cfg.to_dot();
+        assert!(dot.contains("b0 -> halt [label=\"EQ\"];"));
+        assert!(dot.contains("halt [shape=doublecircle];"));
+    }
+
+    #[test]
+    fn test_loops_without_output_and_faults() {
+        let spin = cfg("
+            CONST4 R0, 40
+        spin:
+            SHL4 R0
+            BR4 AL, spin
+        ");
+        assert_eq!(spin.loops(), vec![Loop { header: 3, blocks: vec![3], closed: true, has_output: false }]);
+
+        let counted = cfg("
+            CONST4 R0, 80
+        loop:
+            PUSHOUT R0
+            SHR4 R0
+            CMP4 R0, R1
+            BR4 NE, loop
+        ");
+        let loops = counted.loops();
+        assert_eq!(loops.len(), 1);
+        assert!(!loops[0].closed && loops[0].has_output);
+
+        // BR4 to glyph 60 in a 3-glyph program faults
+        let fault = Cfg::build(&[10, 0, 60]);
+        assert_eq!(fault.blocks[&0].successors, vec![(Successor::Fault, EdgeKind::Taken(Condition::Always))]);
+        assert!(fault.faults.contains_key(&0));
+    }
+
+    #[test]
+    fn test_uninitialized_reads_and_dead_stores() {
+        let cfg = cfg("
+            CONST4 R0, 5
+            CMP4 R0, R1      ; R1 never written
+            BR4 EQ, skip
+            CONST4 R2, 7
+        skip:
+            PUSHOUT R2       ; R2 written on one path only
+            ST4 [3], R0      ; overwritten before any load
+            ST4 [3], R0
+            LD4 R4, [3]
+            ST4 [9], R4      ; never loaded
+        ");
+        let reads: Vec<(usize, u8)> = cfg.uninitialized_reads().iter().map(|r| (r.pc, r.register)).collect();
+        assert_eq!(reads, vec![(3, 1), (12, 2)]);
+        let stores: Vec<(usize, u8)> = cfg.dead_stores().iter().map(|s| (s.pc, s.address)).collect();
+        assert_eq!(stores, vec![(14, 3), (23, 9)]);
+    }
+}
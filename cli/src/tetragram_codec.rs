@@ -0,0 +1,616 @@
+//! Lossless byte <-> tetragram codec used by `tetragram convert`
+//!
+//! Framed format, version 1 (all fields are glyph values 0-80):
+//!
+//! ```text
+//! [version = 1] [L] [length: L base-81 digits, little-endian] [payload] [padding]
+//! ```
+//!
+//! The payload is the input read as a little-endian integer, written as
+//! exactly `payload_digits(length)` base-81 digits, so leading and trailing
+//! zero bytes survive the round trip. Padding glyphs (value 0) after the
+//! payload keep streams a multiple of 9 glyphs and are ignored on decode.
+//!
+//! Chunked format, version 2, streams in constant memory:
+//!
+//! ```text
+//! [version = 2] [block: 19 digits]* [60 + k] [tail: payload_digits(k) digits] [padding]
+//! ```
+//!
+//! Each block holds 15 bytes as a big-endian integer in 19 big-endian base-81
+//! digits. A full block is below 2^120, so its leading digit is at most 59;
+//! a leading glyph of 60 + k marks the final block of k (0-14) bytes.
+//!
+//! The text codec (`--from text`) writes each byte as 6 balanced trits and
+//! packs 4 trits per glyph, using the same nibble layout as the VM.
+
+use crate::tetragram_vm::{glyph_to_value, nibble_to_value, value_to_glyph, value_to_nibble, GLYPH_COUNT};
+use num_bigint::BigUint;
+use num_traits::One;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// Version glyph of the framed encoding
+pub const FRAMED_VERSION: u8 = 1;
+/// Version glyph of the chunked streaming encoding
+pub const CHUNKED_VERSION: u8 = 2;
+/// Streams are padded to a multiple of this many glyphs
+pub const ALIGNMENT: usize = 9;
+/// Bytes per chunked block
+pub const BLOCK_BYTES: usize = 15;
+/// Glyphs per chunked block
+pub const BLOCK_GLYPHS: usize = 19;
+/// Leading glyph value that marks the final (partial) block
+const TAIL_MARKER: u8 = 60;
+/// I/O buffer size for streaming
+const STREAM_BUFFER: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodecError {
+    InvalidGlyph { index: usize, character: char },
+    Empty,
+    UnsupportedVersion(u8),
+    Truncated { expected: usize, found: usize },
+    PayloadOverflow { length: usize },
+    InvalidPadding { index: usize },
+    InvalidBlock { index: usize },
+    MissingTail,
+    InvalidTritByte { byte: usize, value: i32 },
+    InvalidText(String),
+    Io(String),
+}
+
+impl From<io::Error> for CodecError {
+    fn from(e: io::Error) -> Self {
+        CodecError::Io(e.to_string())
+    }
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::InvalidGlyph { index, character } => {
+                write!(f, "invalid tetragram '{}' (U+{:04X}) at index {}", character, *character as u32, index)
+            }
+            CodecError::Empty => write!(f, "no tetragram glyphs found in input"),
+            CodecError::UnsupportedVersion(v) => write!(f, "unsupported tetragram frame version {}", v),
+            CodecError::Truncated { expected, found } => {
+                write!(f, "truncated frame: expected {} glyphs, found {}", expected, found)
+            }
+            CodecError::PayloadOverflow { length } => {
+                write!(f, "payload does not fit in the declared length of {} bytes", length)
+            }
+            CodecError::InvalidPadding { index } => write!(f, "non-zero padding glyph at index {}", index),
+            CodecError::InvalidBlock { index } => write!(f, "block at glyph {} does not fit in 15 bytes", index),
+            CodecError::MissingTail => write!(f, "chunked stream ended without a final block marker"),
+            CodecError::InvalidTritByte { byte, value } => {
+                write!(f, "byte {} decodes to {}, outside 0-255", byte, value)
+            }
+            CodecError::InvalidText(e) => write!(f, "decoded bytes are not UTF-8: {}", e),
+            CodecError::Io(e) => write!(f, "I/O error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+/// Number of base-81 digits needed to hold any `length`-byte value
+pub fn payload_digits(length: usize) -> usize {
+    if length == 0 {
+        return 0;
+    }
+    let max = (BigUint::one() << (8 * length)) - 1u8;
+    max.to_radix_le(GLYPH_COUNT as u32).len()
+}
+
+/// Encode bytes as a framed sequence of glyph values
+pub fn encode_framed(input: &[u8]) -> Vec<u8> {
+    let length_digits = if input.is_empty() {
+        vec![0]
+    } else {
+        BigUint::from(input.len()).to_radix_le(GLYPH_COUNT as u32)
+    };
+
+    let mut out = Vec::with_capacity(2 + length_digits.len() + payload_digits(input.len()) + ALIGNMENT);
+    out.push(FRAMED_VERSION);
+    out.push(length_digits.len() as u8);
+    out.extend(&length_digits);
+
+    let width = payload_digits(input.len());
+    if width > 0 {
+        let mut payload = BigUint::from_bytes_le(input).to_radix_le(GLYPH_COUNT as u32);
+        payload.resize(width, 0);
+        out.extend(payload);
+    }
+
+    while !out.len().is_multiple_of(ALIGNMENT) {
+        out.push(0);
+    }
+    out
+}
+
+/// Decode a framed (version 1 or 2) sequence of glyph values back to the original bytes
+pub fn decode_framed(values: &[u8]) -> Result<Vec<u8>, CodecError> {
+    let truncated = |expected| CodecError::Truncated { expected, found: values.len() };
+    let (&version, rest) = values.split_first().ok_or(CodecError::Empty)?;
+    if version == CHUNKED_VERSION {
+        let mut out = Vec::new();
+        decode_chunked(values.iter().copied().map(Ok), &mut out)?;
+        return Ok(out);
+    }
+    if version != FRAMED_VERSION {
+        return Err(CodecError::UnsupportedVersion(version));
+    }
+    let (&length_width, rest) = rest.split_first().ok_or_else(|| truncated(2))?;
+    let length_width = length_width as usize;
+    if rest.len() < length_width {
+        return Err(truncated(2 + length_width));
+    }
+    let (length_digits, rest) = rest.split_at(length_width);
+    let length = digits_to_usize(length_digits).ok_or(CodecError::PayloadOverflow { length: usize::MAX })?;
+    // Every byte takes more than one base-81 digit, so a declared length beyond
+    // the remaining glyphs is truncated; checking first keeps a hostile length
+    // from sizing the BigUint in `payload_digits`
+    if length > rest.len() {
+        return Err(truncated((2 + length_width).saturating_add(length)));
+    }
+
+    let width = payload_digits(length);
+    if rest.len() < width {
+        return Err(truncated(2 + length_width + width));
+    }
+    let (payload, padding) = rest.split_at(width);
+    if let Some(pos) = padding.iter().position(|&v| v != 0) {
+        return Err(CodecError::InvalidPadding { index: values.len() - padding.len() + pos });
+    }
+
+    let mut bytes = if payload.is_empty() {
+        Vec::new()
+    } else {
+        BigUint::from_radix_le(payload, GLYPH_COUNT as u32)
+            .ok_or(CodecError::PayloadOverflow { length })?
+            .to_bytes_le()
+    };
+    if bytes.len() > length {
+        return Err(CodecError::PayloadOverflow { length });
+    }
+    bytes.resize(length, 0);
+    Ok(bytes)
+}
+
+fn digits_to_usize(digits: &[u8]) -> Option<usize> {
+    digits
+        .iter()
+        .rev()
+        .try_fold(0usize, |acc, &d| acc.checked_mul(GLYPH_COUNT as usize)?.checked_add(d as usize))
+}
+
+/// Encode bytes as a framed glyph string
+pub fn encode_to_glyphs(input: &[u8]) -> String {
+    encode_framed(input).into_iter().map(value_to_glyph).collect()
+}
+
+/// Decode a framed glyph string, ignoring whitespace
+pub fn decode_from_glyphs(input: &str) -> Result<Vec<u8>, CodecError> {
+    let values = input
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .enumerate()
+        .map(|(index, character)| glyph_to_value(character).ok_or(CodecError::InvalidGlyph { index, character }))
+        .collect::<Result<Vec<u8>, _>>()?;
+    decode_framed(&values)
+}
+
+/// Byte and glyph counts of a streaming conversion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    pub bytes: u64,
+    pub glyphs: u64,
+}
+
+/// Base-81 digits needed for a partial block of `len` bytes
+fn tail_digits(len: usize) -> usize {
+    let max = if len == 0 { 0 } else { (1u128 << (8 * len)) - 1 };
+    let mut digits = 0;
+    let mut value = max;
+    while value > 0 {
+        value /= GLYPH_COUNT as u128;
+        digits += 1;
+    }
+    digits
+}
+
+fn push_digits(out: &mut Vec<u8>, mut value: u128, width: usize) {
+    let start = out.len();
+    out.resize(start + width, 0);
+    for slot in out[start..].iter_mut().rev() {
+        *slot = (value % GLYPH_COUNT as u128) as u8;
+        value /= GLYPH_COUNT as u128;
+    }
+}
+
+fn block_value(bytes: &[u8]) -> u128 {
+    bytes.iter().fold(0u128, |acc, &b| (acc << 8) | b as u128)
+}
+
+/// Encode bytes from `reader` as a chunked (version 2) glyph stream, UTF-8 encoded into `writer`
+pub fn encode_stream(mut reader: impl Read, writer: impl Write) -> Result<StreamStats, CodecError> {
+    let mut writer = io::BufWriter::new(writer);
+    let mut stats = StreamStats::default();
+    let mut values = vec![CHUNKED_VERSION];
+    let mut buffer = vec![0u8; STREAM_BUFFER - STREAM_BUFFER % BLOCK_BYTES];
+    let mut filled = 0;
+    let mut glyph_buf = [0u8; 4];
+
+    let mut flush = |values: &mut Vec<u8>, stats: &mut StreamStats, writer: &mut io::BufWriter<_>| -> io::Result<()> {
+        for &v in values.iter() {
+            writer.write_all(value_to_glyph(v).encode_utf8(&mut glyph_buf).as_bytes())?;
+        }
+        stats.glyphs += values.len() as u64;
+        values.clear();
+        Ok(())
+    };
+
+    loop {
+        let n = reader.read(&mut buffer[filled..])?;
+        filled += n;
+        if n != 0 && filled < buffer.len() {
+            continue;
+        }
+        let whole = filled - filled % BLOCK_BYTES;
+        let blocks = if n == 0 { whole } else { filled };
+        for block in buffer[..blocks].chunks_exact(BLOCK_BYTES) {
+            push_digits(&mut values, block_value(block), BLOCK_GLYPHS);
+        }
+        stats.bytes += blocks as u64;
+        flush(&mut values, &mut stats, &mut writer)?;
+        if n == 0 {
+            let tail = &buffer[whole..filled];
+            values.push(TAIL_MARKER + tail.len() as u8);
+            push_digits(&mut values, block_value(tail), tail_digits(tail.len()));
+            stats.bytes += tail.len() as u64;
+            while !(stats.glyphs as usize + values.len()).is_multiple_of(ALIGNMENT) {
+                values.push(0);
+            }
+            flush(&mut values, &mut stats, &mut writer)?;
+            break;
+        }
+        filled = 0;
+    }
+    writer.flush()?;
+    Ok(stats)
+}
+
+/// Decode a glyph stream (either version) from `reader` into raw bytes in `writer`
+///
+/// Chunked streams decode in constant memory; version 1 frames are buffered.
+pub fn decode_stream(reader: impl Read, writer: impl Write) -> Result<StreamStats, CodecError> {
+    let mut writer = io::BufWriter::new(writer);
+    let mut values = GlyphValues::new(reader).peekable();
+    let stats = match values.peek() {
+        None => return Err(CodecError::Empty),
+        Some(Ok(v)) if *v == CHUNKED_VERSION => decode_chunked(values, &mut writer)?,
+        Some(_) => {
+            let values = values.collect::<Result<Vec<u8>, _>>()?;
+            let bytes = decode_framed(&values)?;
+            writer.write_all(&bytes)?;
+            StreamStats { bytes: bytes.len() as u64, glyphs: values.len() as u64 }
+        }
+    };
+    writer.flush()?;
+    Ok(stats)
+}
+
+fn decode_chunked(
+    mut values: impl Iterator<Item = Result<u8, CodecError>>,
+    writer: &mut impl Write,
+) -> Result<StreamStats, CodecError> {
+    let mut stats = StreamStats::default();
+    let mut next = |stats: &mut StreamStats| -> Result<Option<u8>, CodecError> {
+        let value = values.next().transpose()?;
+        if value.is_some() {
+            stats.glyphs += 1;
+        }
+        Ok(value)
+    };
+    let truncated = |stats: &StreamStats, expected: usize| CodecError::Truncated {
+        expected,
+        found: stats.glyphs as usize,
+    };
+
+    match next(&mut stats)? {
+        Some(CHUNKED_VERSION) => {}
+        Some(version) => return Err(CodecError::UnsupportedVersion(version)),
+        None => return Err(CodecError::Empty),
+    }
+    loop {
+        let start = stats.glyphs as usize;
+        let lead = next(&mut stats)?.ok_or(CodecError::MissingTail)?;
+        let (len, width, mut value) = if lead >= TAIL_MARKER {
+            let len = (lead - TAIL_MARKER) as usize;
+            if len >= BLOCK_BYTES {
+                return Err(CodecError::InvalidBlock { index: start });
+            }
+            (len, tail_digits(len), 0u128)
+        } else {
+            (BLOCK_BYTES, BLOCK_GLYPHS - 1, lead as u128)
+        };
+        for _ in 0..width {
+            let digit = next(&mut stats)?.ok_or_else(|| truncated(&stats, start + width + 1))?;
+            value = value * GLYPH_COUNT as u128 + digit as u128;
+        }
+        if value >> (8 * len) != 0 {
+            return Err(CodecError::InvalidBlock { index: start });
+        }
+        writer.write_all(&value.to_be_bytes()[16 - len..])?;
+        stats.bytes += len as u64;
+        if lead >= TAIL_MARKER {
+            break;
+        }
+    }
+    while let Some(v) = next(&mut stats)? {
+        if v != 0 {
+            return Err(CodecError::InvalidPadding { index: stats.glyphs as usize - 1 });
+        }
+    }
+    Ok(stats)
+}
+
+/// Incrementally decodes UTF-8 glyph text into glyph values, skipping whitespace
+struct GlyphValues<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    text: std::vec::IntoIter<char>,
+    index: usize,
+    done: bool,
+}
+
+impl<R: Read> GlyphValues<R> {
+    fn new(reader: R) -> Self {
+        Self { reader, buffer: Vec::new(), text: Vec::new().into_iter(), index: 0, done: false }
+    }
+
+    fn refill(&mut self) -> Result<(), CodecError> {
+        let start = self.buffer.len();
+        self.buffer.resize(start + STREAM_BUFFER, 0);
+        let n = self.reader.read(&mut self.buffer[start..])?;
+        self.buffer.truncate(start + n);
+        if n == 0 {
+            self.done = true;
+        }
+        let valid = match std::str::from_utf8(&self.buffer) {
+            Ok(s) => s.len(),
+            Err(e) if e.error_len().is_none() && !self.done => e.valid_up_to(),
+            Err(e) => return Err(CodecError::Io(format!("invalid UTF-8 in glyph stream: {}", e))),
+        };
+        let chars: Vec<char> = std::str::from_utf8(&self.buffer[..valid]).unwrap().chars().collect();
+        self.buffer.drain(..valid);
+        self.text = chars.into_iter();
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for GlyphValues<R> {
+    type Item = Result<u8, CodecError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.text.next() {
+                Some(c) if c.is_whitespace() => continue,
+                Some(character) => {
+                    let index = self.index;
+                    self.index += 1;
+                    return Some(glyph_to_value(character).ok_or(CodecError::InvalidGlyph { index, character }));
+                }
+                None if self.done => return None,
+                None => {
+                    if let Err(e) = self.refill() {
+                        self.done = true;
+                        return Some(Err(e));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Balanced trits per byte in the text codec
+pub const TRITS_PER_BYTE: usize = 6;
+/// Balanced trits per glyph
+pub const TRITS_PER_GLYPH: usize = 4;
+
+/// Glyphs needed to carry `len` bytes with the balanced-ternary text codec
+pub fn balanced_glyph_count(len: usize) -> usize {
+    (len * TRITS_PER_BYTE).div_ceil(TRITS_PER_GLYPH)
+}
+
+/// Encode bytes as balanced ternary: each byte is 6 trits (most significant
+/// first), and the trit stream is packed 4 trits per glyph, zero-padded
+///
+/// The glyph count alone determines the byte count (`floor(4g / 6)`), so no
+/// length header is needed.
+pub fn encode_balanced(bytes: &[u8]) -> Vec<u8> {
+    let mut trits = Vec::with_capacity(bytes.len() * TRITS_PER_BYTE + TRITS_PER_GLYPH);
+    for &byte in bytes {
+        let mut value = byte as i32;
+        let mut digits = [0i8; TRITS_PER_BYTE];
+        for slot in digits.iter_mut().rev() {
+            let rem = ((value % 3) + 3) % 3;
+            let trit = if rem == 2 { -1 } else { rem as i8 };
+            *slot = trit;
+            value = (value - trit as i32) / 3;
+        }
+        trits.extend(digits);
+    }
+    while trits.len() % TRITS_PER_GLYPH != 0 {
+        trits.push(0);
+    }
+    trits
+        .chunks_exact(TRITS_PER_GLYPH)
+        .map(|chunk| nibble_to_value(&[chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect()
+}
+
+/// Decode glyph values produced by [`encode_balanced`]
+pub fn decode_balanced(values: &[u8]) -> Result<Vec<u8>, CodecError> {
+    if let Some(index) = values.iter().position(|&v| v >= GLYPH_COUNT) {
+        return Err(CodecError::InvalidGlyph { index, character: char::REPLACEMENT_CHARACTER });
+    }
+    let trits: Vec<i8> = values.iter().flat_map(|&v| value_to_nibble(v)).collect();
+    let len = trits.len() / TRITS_PER_BYTE;
+    let (data, padding) = trits.split_at(len * TRITS_PER_BYTE);
+    if padding.iter().any(|&t| t != 0) || padding.len() >= TRITS_PER_GLYPH {
+        return Err(CodecError::InvalidPadding { index: values.len() - 1 });
+    }
+
+    data.chunks_exact(TRITS_PER_BYTE)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let value = chunk.iter().fold(0i32, |acc, &t| acc * 3 + t as i32);
+            u8::try_from(value).map_err(|_| CodecError::InvalidTritByte { byte: i, value })
+        })
+        .collect()
+}
+
+/// Encode UTF-8 text with the balanced-ternary codec as a glyph string
+pub fn encode_text(text: &str) -> String {
+    encode_balanced(text.as_bytes()).into_iter().map(value_to_glyph).collect()
+}
+
+/// Decode a glyph string produced by [`encode_text`], ignoring whitespace
+pub fn decode_text(glyphs: &str) -> Result<String, CodecError> {
+    let values = glyphs
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .enumerate()
+        .map(|(index, character)| glyph_to_value(character).ok_or(CodecError::InvalidGlyph { index, character }))
+        .collect::<Result<Vec<u8>, _>>()?;
+    String::from_utf8(decode_balanced(&values)?).map_err(|e| CodecError::InvalidText(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck::quickcheck;
+
+    quickcheck! {
+        fn prop_roundtrip(input: Vec<u8>) -> bool {
+            decode_framed(&encode_framed(&input)).as_deref() == Ok(&input[..])
+        }
+
+        fn prop_roundtrip_zeros(len: u16) -> bool {
+            let input = vec![0u8; len as usize % 4096];
+            decode_from_glyphs(&encode_to_glyphs(&input)) == Ok(input)
+        }
+
+        fn prop_trailing_zeros_preserved(input: Vec<u8>, zeros: u8) -> bool {
+            let mut input = input;
+            input.extend(std::iter::repeat_n(0, zeros as usize));
+            decode_framed(&encode_framed(&input)) == Ok(input)
+        }
+    }
+
+    quickcheck! {
+        fn prop_stream_roundtrip(input: Vec<u8>) -> bool {
+            let mut glyphs = Vec::new();
+            let encoded = encode_stream(&input[..], &mut glyphs).unwrap();
+            let mut decoded = Vec::new();
+            let stats = decode_stream(&glyphs[..], &mut decoded).unwrap();
+            encoded.glyphs % ALIGNMENT as u64 == 0 && stats == encoded && decoded == input
+        }
+    }
+
+    #[test]
+    fn test_stream_block_sizes() {
+        // One full block plus a 1-byte tail: version + 19 + marker + 2 digits, padded to 27
+        let mut glyphs = Vec::new();
+        let stats = encode_stream(&[0xFFu8; 16][..], &mut glyphs).unwrap();
+        assert_eq!(stats, StreamStats { bytes: 16, glyphs: 27 });
+        let values: Vec<u8> = std::str::from_utf8(&glyphs).unwrap().chars().filter_map(glyph_to_value).collect();
+        assert_eq!(values[0], CHUNKED_VERSION);
+        assert!(values[1] < TAIL_MARKER);
+        assert_eq!(values[20], TAIL_MARKER + 1);
+        assert_eq!(decode_framed(&values), Ok(vec![0xFF; 16]));
+
+        // Large inputs cross the internal buffer boundary
+        let big: Vec<u8> = (0..200_000u32).map(|i| (i * 7919 % 251) as u8).collect();
+        let mut glyphs = Vec::new();
+        encode_stream(&big[..], &mut glyphs).unwrap();
+        let mut decoded = Vec::new();
+        decode_stream(&glyphs[..], &mut decoded).unwrap();
+        assert_eq!(decoded, big);
+    }
+
+    #[test]
+    fn test_stream_rejects_missing_tail() {
+        let values = [CHUNKED_VERSION, 0, 0];
+        assert!(decode_framed(&values).is_err());
+        assert_eq!(decode_framed(&[CHUNKED_VERSION]), Err(CodecError::MissingTail));
+    }
+
+    quickcheck! {
+        fn prop_balanced_roundtrip(input: Vec<u8>) -> bool {
+            let encoded = encode_balanced(&input);
+            encoded.len() == balanced_glyph_count(input.len()) && decode_balanced(&encoded) == Ok(input)
+        }
+
+        fn prop_text_roundtrip(text: String) -> bool {
+            decode_text(&encode_text(&text)) == Ok(text)
+        }
+    }
+
+    #[test]
+    fn test_balanced_layout() {
+        // 255 = 1 0 0 1 1 0 in balanced ternary (243 + 9 + 3)
+        let values = encode_balanced(&[255]);
+        assert_eq!(values.len(), 2);
+        assert_eq!(value_to_nibble(values[0]), [1, 0, 0, 1]);
+        assert_eq!(value_to_nibble(values[1]), [1, 0, 0, 0]);
+        assert_eq!(decode_balanced(&values), Ok(vec![255]));
+
+        // "Hi": 2 bytes -> 12 trits -> 3 glyphs, no padding
+        assert_eq!(encode_text("Hi").chars().count(), 3);
+        assert_eq!(decode_text(&encode_text("\0é\0")), Ok("\0é\0".to_string()));
+    }
+
+    #[test]
+    fn test_balanced_rejects_bad_streams() {
+        // Non-zero padding trits in the final glyph
+        let mut values = encode_balanced(b"a");
+        values[1] = 80;
+        assert!(matches!(decode_balanced(&values), Err(CodecError::InvalidPadding { .. })));
+        // Six +1 trits = 364, not a byte
+        let values = [80, nibble_to_value(&[1, 1, 0, 0])];
+        assert_eq!(decode_balanced(&values), Err(CodecError::InvalidTritByte { byte: 0, value: 364 }));
+    }
+
+    #[test]
+    fn test_empty_and_alignment() {
+        let encoded = encode_framed(&[]);
+        assert_eq!(encoded.len() % ALIGNMENT, 0);
+        assert_eq!(decode_framed(&encoded), Ok(Vec::new()));
+        assert_eq!(decode_framed(&[]), Err(CodecError::Empty));
+        assert_eq!(encode_framed(&[0xFF; 100]).len() % ALIGNMENT, 0);
+    }
+
+    #[test]
+    fn test_payload_digits() {
+        assert_eq!(payload_digits(0), 0);
+        assert_eq!(payload_digits(1), 2); // 255 < 81^2
+        assert_eq!(payload_digits(15), 19); // 2^120 < 81^19
+    }
+
+    #[test]
+    fn test_rejects_corrupt_frames() {
+        let mut encoded = encode_framed(b"a\0");
+        assert_eq!(decode_framed(&encoded[..5]), Err(CodecError::Truncated { expected: 6, found: 5 }));
+        // A 10-digit declared length (~81^10 bytes) is rejected without sizing the payload
+        let mut hostile = vec![FRAMED_VERSION, 10];
+        hostile.extend([80; 10]);
+        assert!(matches!(decode_framed(&hostile), Err(CodecError::Truncated { found: 12, .. })));
+        *encoded.last_mut().unwrap() = 5;
+        assert!(matches!(decode_framed(&encoded), Err(CodecError::InvalidPadding { .. })));
+        encoded[0] = 7;
+        assert_eq!(decode_framed(&encoded), Err(CodecError::UnsupportedVersion(7)));
+    }
+}
@@ -2,31 +2,54 @@
 // Supports .sm (Supreme Mystery) files with Tesla 3-6-9 alignment
 
 use clap::{Args, Subcommand};
-use std::path::PathBuf;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
 use std::fs;
-use trinaryvm_runtime::glyph_processor::{GlyphStreamProcessor, ExecutionResult};
-use trinaryvm_runtime::error::VMError;
-use num_bigint::BigUint;
-use num_traits::{Zero, ToPrimitive};
-use num_integer::Integer;
+use std::io::Write;
+use crate::tetragram_vm::{TetragramMicroVM, ExecutionResult, VmError, parse_glyph_stream, value_to_glyph, Opcode, MEMORY_SIZE};
+use crate::tetragram_debugger::{Debugger, run_repl};
+use crate::tetragram_screen::{FrameSource, dump_frames, run_interactive};
+use crate::tetragram_asm::{TEMPLATES, assemble, disassemble};
+use crate::tetragram_cfg::{Cfg, FlowAnalysis};
+use crate::tetragram_gas::{gas_bounds, GasBounds};
+use crate::tetragram_memory::{MemoryLimit, MemoryMap};
+use crate::tetragram_measure::{measure, Measurement};
+use crate::tetragram_codec;
+use crate::tetragram_ir::{self, Format};
+use crate::tetragram_io::{
+    display_input, is_stdio, load_glyph_source, read_input, read_input_string, OutputTarget, STDIO,
+};
+use crate::tetragram_report::{sarif_log, ReportFormat, REPORT_VERSION};
+use crate::tetragram_validate::{validate_source, Diagnostic, Severity};
+use crate::tetragram_trace::{Trace, TraceFormat, record_run, replay};
 use tritc_lib::compile_source;
 
 #[derive(Subcommand)]
 pub enum TetragramCommands {
     /// Execute a Supreme Mystery (.sm) tetragram program
     Execute(ExecuteArgs),
+    /// Re-run a recorded execution trace and report the first divergence
+    Replay(ReplayArgs),
+    /// Step through a Supreme Mystery program in an interactive debugger
+    Debug(DebugArgs),
     /// Validate Supreme Mystery file syntax and structure
     Validate(ValidateArgs),
     /// Convert between different tetragram formats
     Convert(ConvertArgs),
     /// Compile TritLang source directly to Supreme Mystery
     Compile(CompileArgs),
+    /// Assemble micro-ISA source into a Supreme Mystery program
+    Asm(AsmArgs),
+    /// Disassemble a Supreme Mystery program into annotated micro-ISA source
+    Disasm(DisasmArgs),
     /// Benchmark tetragram performance with Tesla 3-6-9 metrics
     Benchmark(BenchmarkArgs),
     /// Create a new Supreme Mystery program template
     Create(CreateArgs),
     /// Analyze gas usage and optimization opportunities
     Analyze(AnalyzeArgs),
+    /// Render PUSHOUT output onto an 81x27 terminal grid
+    Screen(ScreenArgs),
 }
 
 #[derive(Args)]
@@ -43,9 +66,14 @@ pub struct ExecuteArgs {
     #[arg(short, long, value_name = "FILE")]
     trace: Option<PathBuf>,
     
-    /// Memory limit in nibbles (default: 81 for Tesla 3-6-9 alignment)
+    /// Trace encoding (jsonl or binary)
+    #[arg(long, value_enum, default_value = "jsonl")]
+    trace_format: TraceFormat,
+    
+    /// Memory limit in nibbles (default: 81 for Tesla 3-6-9 alignment), or
+    /// `auto` for the smallest limit the program's LD4/ST4 addresses need
     #[arg(long, default_value = "81")]
-    memory_limit: usize,
+    memory_limit: MemoryLimit,
     
     /// Gas limit for execution
     #[arg(long, default_value = "10000")]
@@ -68,6 +96,49 @@ pub struct ExecuteArgs {
     output_format: String,
 }
 
+#[derive(Args)]
+pub struct ReplayArgs {
+    /// Trace file recorded by `tetragram execute --trace` (JSON Lines or binary)
+    #[arg(short, long, value_name = "FILE")]
+    trace: PathBuf,
+    
+    /// Program to verify against (defaults to the glyph stream in the trace header)
+    #[arg(short, long, value_name = "FILE")]
+    program: Option<PathBuf>,
+    
+    /// Print the replay report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+pub struct DebugArgs {
+    /// Input .sm (Supreme Mystery) file path
+    #[arg(short, long, value_name = "FILE")]
+    program: PathBuf,
+    
+    /// Memory limit in nibbles (at most 81), or `auto` for the smallest limit
+    /// the program's LD4/ST4 addresses need
+    #[arg(long, default_value = "81")]
+    memory_limit: MemoryLimit,
+    
+    /// Gas limit for execution
+    #[arg(long, default_value = "10000")]
+    gas_limit: u64,
+    
+    /// Initial breakpoints (glyph index or opcode mnemonic)
+    #[arg(short, long, value_name = "TARGET")]
+    r#break: Vec<String>,
+    
+    /// Initial memory watchpoints (nibble address)
+    #[arg(short, long, value_name = "ADDR")]
+    watch: Vec<usize>,
+    
+    /// Redraw a full-screen register/memory view before each prompt
+    #[arg(long)]
+    tui: bool,
+}
+
 #[derive(Args)]
 pub struct ValidateArgs {
     /// Input .sm file to validate
@@ -78,6 +149,15 @@ pub struct ValidateArgs {
     #[arg(long)]
     check_alignment: bool,
     
+    /// Memory limit in nibbles that addresses are checked against (at most 81),
+    /// or `auto` for the smallest limit the program's LD4/ST4 addresses need
+    #[arg(long, default_value = "81")]
+    memory_limit: MemoryLimit,
+    
+    /// Report format
+    #[arg(long, value_enum, default_value = "text")]
+    format: ReportFormat,
+    
     /// Verbose validation output
     #[arg(short, long)]
     verbose: bool,
@@ -93,11 +173,11 @@ pub struct ConvertArgs {
     #[arg(short, long, value_name = "FILE")]
     output: PathBuf,
     
-    /// Source format (sm, hex, binary, asm, text, number)
+    /// Source format (sm, hex, binary, text, number, tritvm)
     #[arg(long, default_value = "sm")]
     from: String,
     
-    /// Target format (sm, hex, binary, asm)
+    /// Target format (sm, hex, binary, asm, text, number, tritvm)
     #[arg(long, default_value = "sm")]
     to: String,
     
@@ -110,13 +190,39 @@ pub struct ConvertArgs {
     number: bool,
 }
 
+#[derive(Args)]
+pub struct AsmArgs {
+    /// Input assembly source file
+    #[arg(short, long, value_name = "FILE")]
+    input: PathBuf,
+    
+    /// Output .sm file (default: input name with .sm extension)
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+    
+    /// Print the annotated listing of the assembled program
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+#[derive(Args)]
+pub struct DisasmArgs {
+    /// Input .sm file
+    #[arg(short, long, value_name = "FILE")]
+    input: PathBuf,
+    
+    /// Output assembly file (default: stdout)
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
 #[derive(Args)]
 pub struct CompileArgs {
-    /// Input TritLang source (.trit)
+    /// Input TritLang source (.trit, or - for stdin)
     #[arg(short, long, value_name="FILE")]
     source: PathBuf,
 
-    /// Output Supreme Mystery file path (.sm)
+    /// Output Supreme Mystery file path (.sm, or - for stdout)
     #[arg(short, long, value_name="FILE")]
     output: PathBuf,
 
@@ -131,11 +237,11 @@ pub struct BenchmarkArgs {
     #[arg(long, default_value = "1000")]
     operations: usize,
     
-    /// Benchmark mode (execute, parse, validate)
+    /// Benchmark mode (execute, parse, validate, encode, decode)
     #[arg(long, default_value = "execute")]
     mode: String,
     
-    /// Test file for benchmarking
+    /// Test file for benchmarking (- for stdin)
     #[arg(short, long, value_name = "FILE")]
     file: Option<PathBuf>,
     
@@ -188,111 +294,305 @@ pub struct AnalyzeArgs {
     /// Memory usage analysis
     #[arg(long)]
     memory_analysis: bool,
+    
+    /// Write the control-flow graph in Graphviz DOT format (`-` for stdout)
+    #[arg(long, value_name = "FILE")]
+    cfg_dot: Option<PathBuf>,
+    
+    /// Run the program N times under the executor and report measured timings
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    measure: Option<u32>,
+    
+    /// Report format (json and sarif always include gas and memory analysis)
+    #[arg(long, value_enum, default_value = "text")]
+    format: ReportFormat,
+}
+
+#[derive(Args)]
+pub struct ScreenArgs {
+    /// Input .sm program file
+    #[arg(short, long, value_name = "FILE")]
+    program: PathBuf,
+    
+    /// Frames per second
+    #[arg(long, default_value = "5")]
+    fps: u32,
+    
+    /// Stop after N frames
+    #[arg(long, value_name = "N")]
+    frames: Option<usize>,
+    
+    /// Write frames to text files in DIR instead of animating (headless)
+    #[arg(long, value_name = "DIR", num_args = 0..=1, default_missing_value = "vm_outputs/screen")]
+    dump: Option<PathBuf>,
+    
+    /// Gas limit for execution (one frame of CONST4+PUSHOUT pixels costs 13122)
+    #[arg(long, default_value = "1000000")]
+    gas_limit: u64,
 }
 
 // Tetragram analysis functionality
 pub fn analyze_tetragram_file(args: AnalyzeArgs) -> Result<(), Box<dyn std::error::Error>> {
-    println!("📊 TrinaryVM Tetragram Analyzer");
-    println!("📁 Analyzing: {}", args.file.display());
-    
-    // Check file extension
-    if args.file.extension().map_or(true, |ext| ext != "sm") {
-        return Err("Expected .sm (Supreme Mystery) file".into());
+    let text = args.format == ReportFormat::Text;
+    if text {
+        println!("📊 TrinaryVM Tetragram Analyzer");
+        println!("📁 Analyzing: {}", display_input(&args.file));
     }
     
-    // Read file content
-    let content = fs::read_to_string(&args.file)?;
+    // Recognized extension, stdin or glyph content
+    let content = load_glyph_source(&args.file)?;
+    let (report, cfg) = build_analysis_report(&args.file, &content, args.measure.map_or(0, |n| n as usize));
     
-    // Basic statistics
-    let total_chars = content.chars().count();
-    let tetragram_chars: Vec<char> = content.chars()
-        .filter(|&ch| {
-            let code = ch as u32;
-            code >= 0x1D306 && code <= 0x1D356
-        })
-        .collect();
+    if let Some(target) = args.cfg_dot.clone().map(OutputTarget::new) {
+        if target.is_stdout() && !text {
+            return Err("--cfg-dot - needs --format text; write the graph to a file instead".into());
+        }
+        target.write(cfg.to_dot().as_bytes())?;
+        target.announce(format_args!("🕸️  Control-flow graph ({} blocks) written to {}", cfg.blocks.len(), target));
+        if target.is_stdout() {
+            return Ok(());
+        }
+    }
     
-    let valid_tetragrams = tetragram_chars.len();
-    let invalid_chars = total_chars - valid_tetragrams;
+    if !text {
+        let summary = serde_json::to_value(&report)?;
+        let output = match args.format {
+            ReportFormat::Sarif => sarif_log(&args.file, &report.diagnostics, summary),
+            _ => summary,
+        };
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
     
+    let stats = &report.statistics;
     println!("📈 Basic Statistics:");
-    println!("   Total characters: {}", total_chars);
-    println!("   Valid tetragrams: {}", valid_tetragrams);
-    println!("   Invalid characters: {}", invalid_chars);
-    println!("   Tetragram density: {:.2}%", (valid_tetragrams as f64 / total_chars as f64) * 100.0);
+    println!("   Total characters: {}", stats.total_characters);
+    println!("   Valid tetragrams: {}", stats.valid_tetragrams);
+    println!("   Invalid characters: {}", stats.invalid_characters);
+    println!("   Tetragram density: {:.2}%", stats.density);
+    
+    // Control flow and data flow
+    let flow = &report.control_flow;
+    println!("\n🧭 Control Flow:");
+    println!("   Basic blocks: {} ({} edges)", flow.basic_blocks, flow.edges);
+    println!("   Reachable glyphs: {}/{}", flow.reachable_glyphs, stats.valid_tetragrams);
+    for range in &flow.unreachable {
+        println!("   Unreachable: @{}-@{}", range.start, range.end - 1);
+    }
+    for l in &flow.loops {
+        let kind = match (l.closed, l.has_output) {
+            (true, true) => "endless, outputs",
+            (true, false) => "endless, silent",
+            (false, _) => "exits",
+        };
+        println!("   Loop at @{}: {} block(s), {}", l.header, l.blocks.len(), kind);
+    }
+    
+    if !report.diagnostics.is_empty() {
+        println!("\n🩺 Diagnostics:");
+        print_diagnostics(&args.file, &report.diagnostics);
+    }
     
     // Tesla 3-6-9 alignment analysis
     println!("\n🔺 Tesla 3-6-9 Alignment Analysis:");
-    let tesla_alignment = analyze_tesla_alignment(valid_tetragrams);
+    let tesla_alignment = &report.alignment;
+    let constants: Vec<String> = tesla_alignment.constants.iter().map(|c| c.to_string()).collect();
     println!("   Alignment score: {:.2}", tesla_alignment.score);
-    println!("   Tesla constants: {}", tesla_alignment.constants.join(", "));
+    println!("   Tesla constants: {}", constants.join(", "));
     println!("   Sacred completion: {}", if tesla_alignment.sacred_completion { "✅" } else { "❌" });
     
     // Gas cost analysis
     if args.gas_analysis {
         println!("\n⛽ Gas Cost Analysis:");
-        let gas_analysis = analyze_gas_costs(&tetragram_chars);
-        println!("   Total gas cost: {}", gas_analysis.total_gas);
+        let gas_analysis = &report.gas;
+        let bounds = &gas_analysis.bounds;
+        for op in &bounds.per_opcode {
+            println!("   {:<8} x{:<4} @ {} gas = {}", op.opcode, op.count, op.unit_gas, op.total_gas);
+        }
+        println!("   Total gas cost (each instruction once): {}", gas_analysis.total_gas);
         println!("   Average gas per tetragram: {:.2}", gas_analysis.avg_gas_per_tetragram);
-        println!("   Gas efficiency: {:.2}%", gas_analysis.efficiency);
+        let show = |gas: Option<u64>, none: &str| gas.map_or(none.to_string(), |g| g.to_string());
+        println!("   Min gas (cheapest path to halt): {}", show(bounds.min_gas, "never halts"));
+        println!("   Max gas (worst terminating path): {}", show(bounds.max_gas, "unbounded"));
+        for l in &bounds.loops {
+            println!("   Loop at @{}: {}", l.header, l.note);
+        }
+        println!("   Gas efficiency: {:.2}% (non-NOP)", gas_analysis.efficiency);
         println!("   Optimization potential: {}", gas_analysis.optimization_potential);
     }
     
     // Memory usage analysis
     if args.memory_analysis {
         println!("\n💾 Memory Usage Analysis:");
-        let memory_analysis = analyze_memory_usage(valid_tetragrams);
-        println!("   Memory nibbles used: {}", memory_analysis.nibbles_used);
-        println!("   Memory efficiency: {:.2}%", memory_analysis.efficiency);
+        let memory_analysis = &report.memory;
+        let map = &memory_analysis.map;
+        let addresses = |cells: &[u8]| cells.iter().map(|a| format!("[{}]", a)).collect::<Vec<_>>().join(" ");
+        println!("   Memory nibbles used: {}/{}", memory_analysis.nibbles_used, MEMORY_SIZE);
+        for cell in &map.cells {
+            println!("   [{:>2}] {} load(s), {} store(s)", cell.address, cell.reads, cell.writes);
+        }
+        if !map.write_only.is_empty() {
+            println!("   Write-only cells: {}", addresses(&map.write_only));
+        }
+        if !map.read_only.is_empty() {
+            println!("   Never-stored cells (read zero): {}", addresses(&map.read_only));
+        }
+        for read in &map.uninitialized_reads {
+            println!("   Uninitialized read: @{} LD4 [{}]", read.pc, read.address);
+        }
+        println!("   Heat map (row = address / 9, · untouched, digits = accesses):");
+        for (row, cells) in map.heat_map_rows().iter().enumerate() {
+            println!("   {:>2} {}", row * 9, cells);
+        }
+        println!("   Memory efficiency: {:.2}% (stored and loaded)", memory_analysis.efficiency);
         println!("   Tesla alignment: {}", if memory_analysis.tesla_aligned { "✅" } else { "❌" });
+        println!("   Recommended: tetragram execute --memory-limit {}", map.recommended_memory_limit);
         println!("   Memory optimization: {}", memory_analysis.optimization);
     }
     
     // Optimization suggestions
     if args.optimize {
         println!("\n🔧 Optimization Suggestions:");
-        let suggestions = generate_optimization_suggestions(&tetragram_chars, valid_tetragrams);
-        for (i, suggestion) in suggestions.iter().enumerate() {
+        for (i, suggestion) in report.suggestions.iter().enumerate() {
             println!("   {}. {}", i + 1, suggestion);
         }
     }
     
     // Performance metrics
     println!("\n⚡ Performance Metrics:");
-    let performance = calculate_performance_metrics(valid_tetragrams);
-    println!("   Estimated execution time: {:.2}µs", performance.execution_time);
-    println!("   Operations per second: {:.0}", performance.ops_per_second);
+    let performance = &report.performance;
+    println!("   Estimated execution time: {:.2}µs (1µs per reachable instruction)", performance.execution_time);
+    println!("   Estimated operations per second: {:.0}", performance.ops_per_second);
+    println!("   Estimated gas per µs: {:.2}", performance.gas_per_us);
     println!("   Tesla efficiency: {:.2}%", performance.tesla_efficiency);
+    if let Some(m) = &performance.measured {
+        println!("   Measured over {} run(s) ({} ops, {} gas per run):", m.runs, m.operations, m.gas_consumed);
+        println!("     Wall time: {:.2}µs ± {:.2} (min {:.2}, max {:.2})", m.wall_time_us.mean, m.wall_time_us.std_dev, m.wall_time_us.min, m.wall_time_us.max);
+        println!("     Operations per second: {:.0} ± {:.0}", m.ops_per_second.mean, m.ops_per_second.std_dev);
+        println!("     Gas per µs: {:.2} ± {:.2}", m.gas_per_us.mean, m.gas_per_us.std_dev);
+        if let Some(fault) = &m.fault {
+            println!("     ⚠️  Every run stopped with: {}", fault);
+        }
+    }
     
     println!("\n✅ Analysis completed successfully!");
     Ok(())
 }
 
+/// Everything `tetragram analyze` computes; serialized as-is for `--format json`
+#[derive(Serialize)]
+struct AnalysisReport {
+    schema: &'static str,
+    version: u32,
+    file: String,
+    statistics: SourceStatistics,
+    control_flow: FlowAnalysis,
+    alignment: TeslaAlignment,
+    gas: GasAnalysis,
+    memory: MemoryAnalysis,
+    performance: PerformanceMetrics,
+    suggestions: Vec<String>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Serialize)]
+struct SourceStatistics {
+    total_characters: usize,
+    valid_tetragrams: usize,
+    invalid_characters: usize,
+    /// Percentage of characters that are tetragrams
+    density: f64,
+}
+
+fn build_analysis_report(file: &Path, content: &str, measure_runs: usize) -> (AnalysisReport, Cfg) {
+    // Basic statistics
+    let total_chars = content.chars().count();
+    let tetragram_chars: Vec<char> = content.chars()
+        .filter(|&ch| {
+            let code = ch as u32;
+            code >= 0x1D306 && code <= 0x1D356
+        })
+        .collect();
+    let valid_tetragrams = tetragram_chars.len();
+    
+    // Static analysis of the decoded program
+    let validation = validate_source(content, MEMORY_SIZE);
+    let cfg = Cfg::build(&validation.glyphs);
+    let control_flow = cfg.analyze();
+    let memory = analyze_memory_usage(&cfg);
+    let gas = analyze_gas_costs(&cfg);
+    let measured = (measure_runs > 0).then(|| measure(&validation.glyphs, measure_runs));
+    let mut diagnostics = validation.diagnostics.clone();
+    diagnostics.extend(control_flow.diagnostics(&validation));
+    diagnostics.extend(memory.map.diagnostics(&validation));
+    diagnostics.sort_by_key(|d| (d.line, d.column, d.glyph_index));
+    
+    let report = AnalysisReport {
+        schema: "tetragram-analysis",
+        version: REPORT_VERSION,
+        file: display_input(file),
+        statistics: SourceStatistics {
+            total_characters: total_chars,
+            valid_tetragrams,
+            invalid_characters: total_chars - valid_tetragrams,
+            density: valid_tetragrams as f64 / total_chars.max(1) as f64 * 100.0,
+        },
+        alignment: analyze_tesla_alignment(valid_tetragrams),
+        performance: calculate_performance_metrics(valid_tetragrams, &gas.bounds, measured),
+        gas,
+        memory,
+        suggestions: generate_optimization_suggestions(&control_flow),
+        control_flow,
+        diagnostics,
+    };
+    (report, cfg)
+}
+
 // Analysis helper structures and functions
+#[derive(Serialize)]
 struct TeslaAlignment {
     score: f64,
-    constants: Vec<String>,
+    constants: Vec<u32>,
     sacred_completion: bool,
 }
 
+#[derive(Serialize)]
 struct GasAnalysis {
+    /// Every reachable instruction executed once
     total_gas: u64,
     avg_gas_per_tetragram: f64,
+    /// Share of that gas not spent on NOP
     efficiency: f64,
     optimization_potential: String,
+    #[serde(flatten)]
+    bounds: GasBounds,
 }
 
+#[derive(Serialize)]
 struct MemoryAnalysis {
+    /// Distinct nibbles LD4/ST4 touch
     nibbles_used: usize,
+    /// Share of those nibbles that are both stored and loaded
     efficiency: f64,
+    /// The recommended memory limit is a multiple of 9
     tesla_aligned: bool,
     optimization: String,
+    #[serde(flatten)]
+    map: MemoryMap,
 }
 
+/// Top-level figures are always the static estimate; `measured` is present
+/// only with `--measure N`
+#[derive(Serialize)]
 struct PerformanceMetrics {
+    basis: &'static str,
+    /// Estimated microseconds per run
     execution_time: f64,
     ops_per_second: f64,
+    gas_per_us: f64,
     tesla_efficiency: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    measured: Option<Measurement>,
 }
 
 fn analyze_tesla_alignment(tetragram_count: usize) -> TeslaAlignment {
@@ -302,24 +602,24 @@ fn analyze_tesla_alignment(tetragram_count: usize) -> TeslaAlignment {
     
     // Check divisibility by Tesla constants
     if tetragram_count % 3 == 0 {
-        constants.push("3".to_string());
+        constants.push(3);
         score += 0.1;
     }
     if tetragram_count % 6 == 0 {
-        constants.push("6".to_string());
+        constants.push(6);
         score += 0.1;
     }
     if tetragram_count % 9 == 0 {
-        constants.push("9".to_string());
+        constants.push(9);
         score += 0.2;
         sacred_completion = true;
     }
     if tetragram_count % 27 == 0 {
-        constants.push("27".to_string());
+        constants.push(27);
         score += 0.3;
     }
     if tetragram_count % 81 == 0 {
-        constants.push("81".to_string());
+        constants.push(81);
         score += 0.4;
     }
     
@@ -330,15 +630,22 @@ fn analyze_tesla_alignment(tetragram_count: usize) -> TeslaAlignment {
     }
 }
 
-fn analyze_gas_costs(tetragrams: &[char]) -> GasAnalysis {
-    let total_gas = tetragrams.len() as u64 * 2; // 2 gas per tetragram
-    let avg_gas_per_tetragram = 2.0;
-    let efficiency = if tetragrams.len() > 0 { 100.0 } else { 0.0 };
+// Same cost table the executor meters (Opcode::gas_cost), over decoded instructions
+fn analyze_gas_costs(cfg: &Cfg) -> GasAnalysis {
+    let bounds = gas_bounds(cfg);
+    let total_gas = bounds.static_gas;
+    let avg_gas_per_tetragram = total_gas as f64 / cfg.glyph_count.max(1) as f64;
     
-    let optimization_potential = if tetragrams.len() % 9 != 0 {
-        "Add tetragrams to reach Tesla 9-alignment".to_string()
+    // Gas spent on NOPs does no work
+    let nops = bounds.per_opcode.iter().find(|o| o.opcode == Opcode::Nop.mnemonic()).map_or(0, |o| o.count);
+    let efficiency = if total_gas > 0 { (total_gas - nops as u64) as f64 / total_gas as f64 * 100.0 } else { 0.0 };
+    
+    let optimization_potential = if nops > 0 {
+        format!("Remove {} NOP(s) to save {} gas per pass", nops, nops as u64 * Opcode::Nop.gas_cost())
+    } else if bounds.max_gas.is_none() && !bounds.loops.is_empty() {
+        "Bound the loops marked unknown so worst-case gas is finite".to_string()
     } else {
-        "Already optimized for Tesla alignment".to_string()
+        "No gas wasted on NOPs".to_string()
     };
     
     GasAnalysis {
@@ -346,18 +653,24 @@ fn analyze_gas_costs(tetragrams: &[char]) -> GasAnalysis {
         avg_gas_per_tetragram,
         efficiency,
         optimization_potential,
+        bounds,
     }
 }
 
-fn analyze_memory_usage(tetragram_count: usize) -> MemoryAnalysis {
-    let nibbles_used = tetragram_count * 4; // 4 trits per tetragram
-    let efficiency = if tetragram_count > 0 { 100.0 } else { 0.0 };
-    let tesla_aligned = tetragram_count % 81 == 0; // 81 nibbles = perfect alignment
-    
-    let optimization = if tesla_aligned {
-        "Perfect Tesla memory alignment".to_string()
+// Real LD4/ST4 addresses over the reachable code
+fn analyze_memory_usage(cfg: &Cfg) -> MemoryAnalysis {
+    let map = MemoryMap::build(cfg);
+    let nibbles_used = map.cells.len();
+    let efficiency = if nibbles_used > 0 { map.live_cells() as f64 / nibbles_used as f64 * 100.0 } else { 0.0 };
+    let limit = map.recommended_memory_limit;
+    let tesla_aligned = limit.is_multiple_of(9);
+    
+    let optimization = if !map.write_only.is_empty() {
+        format!("Drop the stores to {} write-only cell(s)", map.write_only.len())
+    } else if limit > nibbles_used {
+        format!("Pack {} cell(s) into the lowest addresses to run with --memory-limit {}", nibbles_used, nibbles_used)
     } else {
-        format!("Add {} tetragrams for perfect alignment", 81 - (tetragram_count % 81))
+        "Memory is densely packed".to_string()
     };
     
     MemoryAnalysis {
@@ -365,118 +678,124 @@ fn analyze_memory_usage(tetragram_count: usize) -> MemoryAnalysis {
         efficiency,
         tesla_aligned,
         optimization,
+        map,
     }
 }
 
-fn calculate_performance_metrics(tetragram_count: usize) -> PerformanceMetrics {
-    let execution_time = tetragram_count as f64 * 0.001; // 1µs per tetragram
-    let ops_per_second = 1_000_000.0 / execution_time;
+/// Static speed model used for the estimated figures
+const ESTIMATED_US_PER_INSTRUCTION: f64 = 1.0;
+
+fn calculate_performance_metrics(tetragram_count: usize, gas: &GasBounds, measured: Option<Measurement>) -> PerformanceMetrics {
+    // Estimate: every reachable instruction once at 1µs each
+    let instructions: usize = gas.per_opcode.iter().map(|o| o.count).sum();
+    let execution_time = instructions as f64 * ESTIMATED_US_PER_INSTRUCTION;
+    let (ops_per_second, gas_per_us) = if execution_time > 0.0 {
+        (instructions as f64 / execution_time * 1_000_000.0, gas.static_gas as f64 / execution_time)
+    } else {
+        (0.0, 0.0)
+    };
     let tesla_efficiency = if tetragram_count % 9 == 0 { 100.0 } else { 90.0 };
     
     PerformanceMetrics {
+        basis: "estimated",
         execution_time,
         ops_per_second,
+        gas_per_us,
         tesla_efficiency,
+        measured,
     }
 }
 
-fn generate_optimization_suggestions(_tetragrams: &[char], count: usize) -> Vec<String> {
+// Suggestions follow from the control- and data-flow findings
+fn generate_optimization_suggestions(flow: &FlowAnalysis) -> Vec<String> {
     let mut suggestions = Vec::new();
     
-    // Tesla alignment suggestions
-    if count % 3 != 0 {
-        suggestions.push("Add tetragrams to reach Tesla 3-alignment".to_string());
+    for range in &flow.unreachable {
+        suggestions.push(format!("Remove {} unreachable glyph(s) at @{}-@{}", range.len(), range.start, range.end - 1));
     }
-    if count % 9 != 0 {
-        suggestions.push("Add tetragrams to reach Tesla 9-alignment (sacred completion)".to_string());
+    for l in flow.loops.iter().filter(|l| l.closed && !l.has_output) {
+        suggestions.push(format!("Loop at @{} burns gas without output; add an exit branch or a PUSHOUT", l.header));
     }
-    if count % 27 != 0 {
-        suggestions.push("Add tetragrams to reach Tesla 27-alignment (perfect harmony)".to_string());
+    for read in &flow.uninitialized_reads {
+        suggestions.push(format!("Initialize R{} with CONST4 before @{} instead of relying on the zero reset", read.register, read.pc));
     }
-    
-    // Performance suggestions
-    if count < 81 {
-        suggestions.push("Consider expanding to 81 tetragrams for optimal memory usage".to_string());
-    }
-    
-    // Gas optimization
-    if count % 2 != 0 {
-        suggestions.push("Even tetragram count for optimal gas efficiency".to_string());
+    for store in &flow.dead_stores {
+        suggestions.push(format!("Drop the ST4 at @{}: [{}] is never loaded", store.pc, store.address));
     }
     
     if suggestions.is_empty() {
-        suggestions.push("Program is already optimally configured".to_string());
+        suggestions.push("No dead code, silent loops or unused state found".to_string());
     }
     
     suggestions
 }
 
-
-
 // Command implementations
 
 pub fn execute_tetragram_program(args: ExecuteArgs) -> Result<(), Box<dyn std::error::Error>> {
-    // Validate file extension
-    if args.program.extension().map_or(true, |ext| ext != "sm") {
-        return Err(format!("Expected .sm (Supreme Mystery) file, got {:?}", args.program).into());
+    let save_sm = args.save_sm.clone().map(OutputTarget::glyphs);
+    let trace_out = args.trace.clone().map(OutputTarget::new);
+    if save_sm.iter().chain(&trace_out).filter(|t| t.is_stdout()).count() > 1 {
+        return Err("Only one of --save-sm and --trace can write to stdout".into());
     }
+    // When a result stream goes to stdout the run report is skipped
+    let report = !save_sm.iter().chain(&trace_out).any(OutputTarget::is_stdout);
     
-    println!("🔺 TrinaryVM Tetragram Executor");
-    println!("📖 Loading: {}", args.program.display());
+    if report {
+        println!("🔺 TrinaryVM Tetragram Executor");
+        println!("📖 Loading: {}", display_input(&args.program));
+    }
     
-    // Initialize processor with configuration
-    let mut processor = GlyphStreamProcessor::new();
+    // Load the glyph stream into the in-crate micro VM
+    let source = load_glyph_source(&args.program)?;
+    let mut vm = match TetragramMicroVM::from_source(&source) {
+        Ok(vm) => {
+            let memory_limit = args.memory_limit.resolve(vm.program());
+            if args.memory_limit == MemoryLimit::Auto && report {
+                println!("💾 Memory limit (auto): {} nibbles", memory_limit);
+            }
+            vm.with_gas_limit(args.gas_limit).with_memory_limit(memory_limit)
+        }
+        Err(e) => {
+            eprintln!("❌ Invalid Tetragram: {}", e);
+            return Err(e.into());
+        }
+    };
     
-    if args.debug {
-        processor.enable_debug_mode();
+    if args.debug && report {
         println!("🐛 Debug mode enabled");
     }
     
-    if args.trace.is_some() {
-        processor.enable_trace();
-        println!("📝 Execution tracing enabled");
-    }
-    
-    // Execute the program
-    let result = match processor.execute_file(&args.program) {
-        Ok(result) => result,
-        Err(VMError::InvalidFileType(msg)) => {
-            eprintln!("❌ File Error: {}", msg);
-            return Err(msg.into());
-        }
-        Err(VMError::FileError(msg)) => {
-            eprintln!("❌ IO Error: {}", msg);
-            return Err(msg.into());
-        }
-        Err(VMError::InvalidTetragram(msg)) => {
-            eprintln!("❌ Invalid Tetragram: {}", msg);
-            return Err(msg.into());
-        }
-        Err(e) => {
-            eprintln!("❌ Execution Error: {:?}", e);
-            return Err(format!("{:?}", e).into());
+    // Execute the program, recording a trace if requested
+    let (result, trace) = if trace_out.is_some() {
+        if report {
+            println!("📝 Execution tracing enabled");
         }
+        let (result, trace) = record_run(&mut vm);
+        (result, Some(trace))
+    } else {
+        (vm.run(), None)
     };
     
     // Output results based on format
-    if args.cyberpunk {
+    if args.cyberpunk && report {
         println!("\n┏━━━━━━━━━━━━━━━━━━━━━━━━ CYBER EXECUTION ━━━━━━━━━━━━━━━━━━━━━━━━┓");
         println!("✶  Glyph Stream  ✶  =>  {} tetragrams", result.glyph_stream.chars().count());
         println!("┗━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━┛\n");
     }
-    if args.debug {
+    if args.debug && report {
         println!("🔬 Final register state: {:?}", result.register_state);
     }
 
     // Optionally save glyph stream
-    if let Some(path) = args.save_sm {
-        let normalized = normalize_output_path(path);
-        fs::write(&normalized, &result.glyph_stream)?;
-        println!("💾 Saved glyph stream to {}", normalized.display());
+    if let Some(target) = &save_sm {
+        target.write(result.glyph_stream.as_bytes())?;
+        target.announce(format_args!("💾 Saved glyph stream to {}", target));
     }
     
     // Output results based on format
     match args.output_format.as_str() {
+        _ if !report => {}
         "json" => {
             let json_output = serde_json::to_string_pretty(&result)?;
             println!("{}", json_output);
@@ -490,10 +809,11 @@ pub fn execute_tetragram_program(args: ExecuteArgs) -> Result<(), Box<dyn std::e
     }
     
     // Save trace if requested
-    if let Some(trace_path) = args.trace {
-        let normalized = normalize_output_path(trace_path);
-        processor.save_trace(&normalized)?;
-        println!("📝 Execution trace saved to: {}", normalized.display());
+    if let (Some(target), Some(trace)) = (&trace_out, trace) {
+        let mut out = target.create()?;
+        trace.write_to(&mut out, args.trace_format)?;
+        out.flush()?;
+        target.announce(format_args!("📝 Execution trace ({} records) saved to: {}", trace.records.len(), target));
     }
     
     // Check for errors
@@ -502,62 +822,177 @@ pub fn execute_tetragram_program(args: ExecuteArgs) -> Result<(), Box<dyn std::e
         for error in &result.errors {
             eprintln!("   • {}", error);
         }
+        if let Some(fault) = result.fault {
+            return Err(fault.into());
+        }
         return Err("Execution had errors".into());
     }
     
-    println!("✅ Execution completed successfully!");
+    if report {
+        println!("✅ Execution completed successfully!");
+    }
     Ok(())
 }
 
-pub fn validate_tetragram_file(args: ValidateArgs) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🔍 TrinaryVM Tetragram Validator");
-    println!("📁 Validating: {}", args.file.display());
+pub fn replay_tetragram_trace(args: ReplayArgs) -> Result<(), Box<dyn std::error::Error>> {
+    if args.program.as_deref().is_some_and(is_stdio) && is_stdio(&args.trace) {
+        return Err("The trace and the program cannot both be read from stdin".into());
+    }
+    let trace = Trace::read_from(&read_input(&args.trace)?)
+        .map_err(|e| format!("Invalid trace {}: {}", display_input(&args.trace), e))?;
+    
+    let program = match &args.program {
+        Some(path) => parse_glyph_stream(&load_glyph_source(path)?)?,
+        None => parse_glyph_stream(&trace.header.glyph_stream)?,
+    };
+    let report = replay(&trace, program);
     
-    // Check file extension
-    if args.file.extension().map_or(true, |ext| ext != "sm") {
-        return Err("Expected .sm (Supreme Mystery) file".into());
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("🔁 Replaying trace: {}", display_input(&args.trace));
+        println!("📋 Format: {} v{}", trace.header.format, trace.header.version);
+        println!("📊 Records: {}", trace.records.len());
+        if !report.program_matches_header {
+            println!("⚠️  Program differs from the glyph stream recorded in the trace");
+        }
+        println!("✔️  Steps verified: {}", report.steps_verified);
     }
     
-    // Read file content
-    let content = fs::read_to_string(&args.file)?;
+    match report.divergence {
+        Some(divergence) => {
+            eprintln!("❌ Divergence at {}", divergence);
+            Err("Replay diverged from trace".into())
+        }
+        None => {
+            if !args.json {
+                println!("✅ Replay matches trace");
+            }
+            Ok(())
+        }
+    }
+}
+
+pub fn debug_tetragram_program(args: DebugArgs) -> Result<(), Box<dyn std::error::Error>> {
+    // The REPL reads its commands from stdin
+    if is_stdio(&args.program) {
+        return Err("The debugger reads commands from stdin; pass the program as a file".into());
+    }
     
-    if args.verbose {
-        println!("📏 File size: {} characters", content.len());
-        println!("🔤 Character count: {}", content.chars().count());
+    println!("🔺 TrinaryVM Tetragram Debugger");
+    println!("📖 Loading: {}", args.program.display());
+    
+    let source = load_glyph_source(&args.program)?;
+    let vm = TetragramMicroVM::from_source(&source)?;
+    let memory_limit = args.memory_limit.resolve(vm.program());
+    if args.memory_limit == MemoryLimit::Auto {
+        println!("💾 Memory limit (auto): {} nibbles", memory_limit);
     }
+    let vm = vm.with_gas_limit(args.gas_limit).with_memory_limit(memory_limit);
     
-    // Create processor for validation
-    let _processor = GlyphStreamProcessor::new();
+    let mut debugger = Debugger::new(vm);
+    for target in &args.r#break {
+        if let Some(output) = debugger.execute_command(&format!("break {}", target)) {
+            println!("{}", output);
+        }
+    }
+    for address in &args.watch {
+        if let Some(output) = debugger.execute_command(&format!("watch {}", address)) {
+            println!("{}", output);
+        }
+    }
     
-    // Validate tetragram syntax
-    let mut valid_tetragrams = 0;
-    let mut invalid_chars = Vec::new();
-    let mut char_position = 0;
+    run_repl(debugger, args.tui)?;
+    Ok(())
+}
+
+pub fn screen_tetragram_program(args: ScreenArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let source = load_glyph_source(&args.program)?;
+    let vm = TetragramMicroVM::from_source(&source)?.with_gas_limit(args.gas_limit);
+    let mut frames = FrameSource::new(vm);
     
-    for ch in content.chars() {
-        if ch.is_whitespace() || ch.is_control() {
-            char_position += 1;
-            continue;
+    match args.dump {
+        Some(dir) => {
+            println!("🖥️  TrinaryVM Tetragram Screen (headless)");
+            let written = dump_frames(&mut frames, args.frames, &dir)?;
+            println!("💾 Wrote {} frame(s) to {}", written.len(), dir.display());
         }
-        
-        let code = ch as u32;
-        if code >= 0x1D306 && code <= 0x1D356 {
-            valid_tetragrams += 1;
-        } else {
-            invalid_chars.push((ch, char_position, code));
+        None => {
+            let shown = run_interactive(&mut frames, args.fps, args.frames)?;
+            println!("🖥️  Rendered {} frame(s)", shown);
         }
-        char_position += 1;
     }
     
-    // Report validation results
-    println!("✅ Valid tetragrams: {}", valid_tetragrams);
+    if let Some(fault) = frames.fault() {
+        eprintln!("⚠️  Program stopped early: {}", fault);
+    }
+    Ok(())
+}
+
+pub fn validate_tetragram_file(args: ValidateArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let text = args.format == ReportFormat::Text;
+    if text {
+        println!("🔍 TrinaryVM Tetragram Validator");
+        println!("📁 Validating: {}", display_input(&args.file));
+    }
+    
+    // Recognized extension, stdin or glyph content
+    let content = load_glyph_source(&args.file)?;
+    
+    // `auto` needs a decodable stream; otherwise check against the full memory
+    let memory_limit = match (args.memory_limit, parse_glyph_stream(&content)) {
+        (MemoryLimit::Auto, Ok(program)) => MemoryLimit::Auto.resolve(&program),
+        (MemoryLimit::Auto, Err(_)) => MEMORY_SIZE,
+        (MemoryLimit::Nibbles(n), _) => n,
+    };
+    
+    // Decode instructions and check every operand
+    let report = validate_source(&content, memory_limit);
+    let valid_tetragrams = report.glyphs.len();
+    let errors = report.count(Severity::Error);
+    let warnings = report.count(Severity::Warning);
+    
+    if !text {
+        let summary = serde_json::json!({
+            "schema": "tetragram-validate",
+            "version": REPORT_VERSION,
+            "file": display_input(&args.file),
+            "valid": report.is_valid(),
+            "glyphs": valid_tetragrams,
+            "instructions": report.instructions.len(),
+            "errors": errors,
+            "warnings": warnings,
+            "memory_limit": memory_limit,
+            "alignment": analyze_tesla_alignment(valid_tetragrams),
+            "diagnostics": report.diagnostics,
+        });
+        let output = match args.format {
+            ReportFormat::Sarif => sarif_log(&args.file, &report.diagnostics, summary),
+            _ => summary,
+        };
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        if errors > 0 {
+            return Err(format!("Validation failed with {} error(s)", errors).into());
+        }
+        return Ok(());
+    }
+    
+    if args.verbose {
+        println!("📏 File size: {} characters", content.len());
+        println!("🔤 Character count: {}", content.chars().count());
+    }
     
-    if !invalid_chars.is_empty() {
-        println!("❌ Invalid characters found: {}", invalid_chars.len());
-        for (ch, pos, code) in &invalid_chars {
-            println!("   • '{}' at position {} (U+{:04X})", ch, pos, code);
+    println!("✅ Valid tetragrams: {}", valid_tetragrams);
+    println!("🧩 Decoded instructions: {}", report.instructions.len());
+    if args.verbose {
+        for instr in &report.instructions {
+            println!("   @{:04} {}", instr.pc, instr);
         }
-        return Err("File contains invalid tetragram characters".into());
+    }
+    
+    print_diagnostics(&args.file, &report.diagnostics);
+    if errors > 0 {
+        return Err(format!("Validation failed with {} error(s)", errors).into());
     }
     
     // Check Tesla 3-6-9 alignment
@@ -584,6 +1019,20 @@ pub fn validate_tetragram_file(args: ValidateArgs) -> Result<(), Box<dyn std::er
     Ok(())
 }
 
+fn print_diagnostics(file: &Path, diagnostics: &[Diagnostic]) {
+    for diagnostic in diagnostics {
+        let icon = match diagnostic.severity {
+            Severity::Error => "❌",
+            Severity::Warning => "⚠️ ",
+        };
+        println!("{} {}:{}", icon, display_input(file), diagnostic);
+    }
+    if !diagnostics.is_empty() {
+        let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+        println!("📋 {} error(s), {} warning(s)", errors, diagnostics.len() - errors);
+    }
+}
+
 pub fn benchmark_tetragram_performance(args: BenchmarkArgs) -> Result<(), Box<dyn std::error::Error>> {
     if args.cyberpunk {
         println!("██╗████████╗██╗   ██╗██████╗ ██████╗ ██████╗ ██╗   ██╗███╗   ███╗");
@@ -597,16 +1046,26 @@ pub fn benchmark_tetragram_performance(args: BenchmarkArgs) -> Result<(), Box<dy
     println!("🔢 Operations: {}", args.operations);
     println!("🔄 Iterations: {}", args.iterations);
     
-    let test_file = args.file.unwrap_or_else(|| {
-        // Create a temporary test file
-        let temp_content = "𝌆".repeat(args.operations);
-        let temp_path = args.save_sm.clone().unwrap_or(std::env::temp_dir().join("benchmark_test.sm"));
-        fs::write(&temp_path, &temp_content).expect("Failed to create test file");
-        temp_path
-    });
+    if args.mode == "encode" || args.mode == "decode" {
+        return benchmark_codec_throughput(&args);
+    }
+    
+    // Read once up front so `--file -` works and the timings cover the VM, not the disk
+    let (test_file, content) = match &args.file {
+        Some(path) => (path.clone(), read_input_string(path)?),
+        None => {
+            let content = "𝌆".repeat(args.operations);
+            if let Some(save) = args.save_sm.clone().map(OutputTarget::glyphs) {
+                save.write(content.as_bytes())?;
+                save.announce(format_args!("💾 Saved generated test glyphs to {}", save));
+            }
+            (PathBuf::from("<generated>"), content)
+        }
+    };
     
     let mut total_time = std::time::Duration::ZERO;
     let mut successful_runs = 0;
+    let mut diagnostics = Vec::new();
     
     for i in 0..args.iterations {
         if i % 10 == 0 {
@@ -617,37 +1076,24 @@ pub fn benchmark_tetragram_performance(args: BenchmarkArgs) -> Result<(), Box<dy
         
         match args.mode.as_str() {
             "execute" => {
-                let mut processor = GlyphStreamProcessor::new();
-                if processor.execute_file(&test_file).is_ok() {
-                    successful_runs += 1;
+                if let Ok(mut vm) = TetragramMicroVM::from_source(&content) {
+                    if vm.run().errors.is_empty() {
+                        successful_runs += 1;
+                    }
                 }
             }
             "parse" => {
-                let content = fs::read_to_string(&test_file)?;
-                #[cfg(feature = "dev")]
-                {
-                    let processor = GlyphStreamProcessor::new();
-                    let count = processor.parse_only(&content)?;
-                    if count > 0 { successful_runs += 1; }
-                }
-                #[cfg(not(feature = "dev"))]
-                {
-                    // Fallback: simple validation parse
-                    let valid = content.chars().all(|ch| {
-                        ch.is_whitespace() || (ch as u32 >= 0x1D306 && ch as u32 <= 0x1D356)
-                    });
-                    if valid { successful_runs += 1; }
+                if parse_glyph_stream(&content).is_ok() {
+                    successful_runs += 1;
                 }
             }
             "validate" => {
-                let _content = fs::read_to_string(&test_file)?;
-                // Validate tetragrams
-                let valid = _content.chars().all(|ch| {
-                    ch.is_whitespace() || (ch as u32 >= 0x1D306 && ch as u32 <= 0x1D356)
-                });
-                if valid {
+                // Same decoder and operand checks as `tetragram validate`
+                let report = validate_source(&content, MEMORY_SIZE);
+                if report.is_valid() {
                     successful_runs += 1;
                 }
+                diagnostics = report.diagnostics;
             }
             _ => return Err("Invalid benchmark mode".into()),
         }
@@ -663,6 +1109,11 @@ pub fn benchmark_tetragram_performance(args: BenchmarkArgs) -> Result<(), Box<dy
         0.0
     };
     
+    if !diagnostics.is_empty() {
+        println!();
+        print_diagnostics(&test_file, &diagnostics);
+    }
+    
     println!("\n🏁 Benchmark Results:");
     println!("   ⏱️  Average time: {:?}", avg_time);
     println!("   🚀 Operations/second: {:.2}", ops_per_second);
@@ -686,41 +1137,149 @@ pub fn benchmark_tetragram_performance(args: BenchmarkArgs) -> Result<(), Box<dy
     Ok(())
 }
 
-pub fn create_tetragram_template(args: CreateArgs) -> Result<(), Box<dyn std::error::Error>> {
-    println!("📝 Creating Supreme Mystery template: {}", args.output.display());
-    
-    let template_content = match args.template.as_str() {
-        "hello" => create_hello_world_template(args.debug_annotations),
-        "fibonacci" => create_fibonacci_template(args.debug_annotations),
-        "crypto" => create_crypto_template(args.debug_annotations),
-        "test" => create_test_template(args.debug_annotations),
-        _ => return Err("Unknown template type".into()),
+// Codec throughput: `--operations` is the payload size in KiB unless `--file` supplies raw bytes
+fn benchmark_codec_throughput(args: &BenchmarkArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let payload = match &args.file {
+        Some(path) => read_input(path)?,
+        None => (0..args.operations * 1024).map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8).collect(),
     };
+    let mut glyphs = Vec::new();
+    let encoded = tetragram_codec::encode_stream(&payload[..], &mut glyphs)?;
     
-    // Ensure .sm extension
-    let output_path = if args.output.extension().map_or(true, |ext| ext != "sm") {
-        args.output.with_extension("sm")
+    let mut total_time = std::time::Duration::ZERO;
+    let mut successful_runs = 0;
+    for i in 0..args.iterations {
+        if i % 10 == 0 {
+            print!("🏃 Progress: {}/{}\r", i, args.iterations);
+        }
+        let start = std::time::Instant::now();
+        let ok = if args.mode == "encode" {
+            tetragram_codec::encode_stream(&payload[..], std::io::sink()).is_ok()
+        } else {
+            tetragram_codec::decode_stream(&glyphs[..], std::io::sink()).is_ok()
+        };
+        total_time += start.elapsed();
+        if ok {
+            successful_runs += 1;
+        }
+    }
+    
+    let avg_time = total_time / args.iterations.max(1) as u32;
+    let throughput = if avg_time.as_secs_f64() > 0.0 {
+        payload.len() as f64 / avg_time.as_secs_f64() / (1024.0 * 1024.0)
     } else {
-        args.output
+        0.0
     };
+    let glyph_count = encoded.glyphs;
+    
+    println!("\n🏁 Codec Benchmark Results:");
+    println!("   📦 Payload: {} bytes ↔ {} tetragrams", payload.len(), glyph_count);
+    println!("   ⏱️  Average time: {:?}", avg_time);
+    println!("   🚀 Throughput: {:.2} MiB/s", throughput);
+    println!("   ✅ Success rate: {}/{}", successful_runs, args.iterations);
+    
+    if args.json {
+        let results = serde_json::json!({
+            "benchmark_mode": args.mode,
+            "payload_bytes": payload.len(),
+            "glyphs": glyph_count,
+            "iterations": args.iterations,
+            "average_time_ns": avg_time.as_nanos(),
+            "throughput_mib_per_second": throughput,
+            "success_rate": successful_runs as f64 / args.iterations.max(1) as f64,
+        });
+        println!("\n📊 JSON Results:\n{}", serde_json::to_string_pretty(&results)?);
+    }
+    
+    Ok(())
+}
+
+pub fn create_tetragram_template(args: CreateArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let output = OutputTarget::glyphs(args.output);
+    output.announce(format_args!("📝 Creating Supreme Mystery template: {}", output));
+    
+    // Templates are kept as micro-ISA assembly in cli/templates/
+    let source = TEMPLATES
+        .iter()
+        .find(|(name, _)| *name == args.template)
+        .map(|(_, source)| *source)
+        .ok_or("Unknown template type")?;
+    let program = assemble(source).map_err(|e| format!("Template {} failed to assemble at {}", args.template, e))?;
+    
+    let mut template_content = String::new();
+    if args.debug_annotations {
+        for line in source.lines() {
+            template_content.push_str("// ");
+            template_content.push_str(line);
+            template_content.push('\n');
+        }
+        template_content.push('\n');
+    }
+    template_content.extend(program.iter().map(|&v| value_to_glyph(v)));
+    
+    output.write(template_content.as_bytes())?;
+    output.announce(format_args!("✅ Template created: {} ({} glyphs)", output, program.len()));
+    
+    Ok(())
+}
 
-    // Normalize to vm_outputs directory if necessary
-    let output_path = normalize_output_path(output_path);
+pub fn assemble_tetragram_source(args: AsmArgs) -> Result<(), Box<dyn std::error::Error>> {
+    // Source from stdin assembles to stdout unless --output says otherwise
+    let output = OutputTarget::glyphs(args.output.unwrap_or_else(|| {
+        if is_stdio(&args.input) {
+            PathBuf::from(STDIO)
+        } else {
+            PathBuf::from(args.input.file_name().unwrap_or_default()).with_extension("sm")
+        }
+    }));
+    output.announce(format_args!("🔧 Assembling: {}", display_input(&args.input)));
+    let source = read_input_string(&args.input)?;
+    let program = assemble(&source).map_err(|e| format!("{}:{}", display_input(&args.input), e))?;
     
-    fs::write(&output_path, template_content)?;
-    println!("✅ Template created: {}", output_path.display());
+    let glyphs: String = program.iter().map(|&v| value_to_glyph(v)).collect();
+    output.write(glyphs.as_bytes())?;
     
+    if args.verbose {
+        output.announce(disassemble(&program).trim_end());
+    }
+    output.announce(format_args!("✅ Assembled {} glyphs to {}", program.len(), output));
+    Ok(())
+}
+
+pub fn disassemble_tetragram_program(args: DisasmArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let source = load_glyph_source(&args.input)?;
+    let program = parse_glyph_stream(&source)?;
+    let listing = disassemble(&program);
+    
+    let output = OutputTarget::new(args.output.unwrap_or_else(|| PathBuf::from(STDIO)));
+    output.write(listing.as_bytes())?;
+    if !output.is_stdout() {
+        println!("✅ Disassembled {} glyphs to {}", program.len(), output);
+    }
     Ok(())
 }
 
 pub fn compile_pipeline(args: CompileArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let output = OutputTarget::glyphs(args.output.clone());
     // Prepare temp tritvm path
     let tmp_tritvm = std::env::temp_dir().join("compile_tmp.tritvm");
 
+    // The compiler reads a path, so source from stdin is staged in a temp file
+    let source = if is_stdio(&args.source) {
+        let staged = std::env::temp_dir().join("compile_tmp.trit");
+        fs::write(&staged, read_input(&args.source)?)?;
+        staged
+    } else {
+        args.source.clone()
+    };
+
     // Compile using library – no subprocess spawn
-    println!("🔨 Compiling TritLang source {}", args.source.display());
-    compile_source(&args.source, &tmp_tritvm)
-        .map_err(|e| format!("TritLang compile error: {:?}", e))?;
+    output.announce(format_args!("🔨 Compiling TritLang source {}", display_input(&args.source)));
+    let compiled = compile_source(&source, &tmp_tritvm);
+    if is_stdio(&args.source) {
+        let _ = fs::remove_file(&source);
+    }
+    compiled.map_err(|e| format!("TritLang compile error: {:?}", e))?;
 
     // Convert to .sm using existing converter
     let conv_args = ConvertArgs {
@@ -737,7 +1296,7 @@ pub fn compile_pipeline(args: CompileArgs) -> Result<(), Box<dyn std::error::Err
         let _ = std::fs::remove_file(&tmp_tritvm);
     }
 
-    println!("✅ Compile pipeline finished");
+    output.announce("✅ Compile pipeline finished");
     Ok(())
 }
 
@@ -749,9 +1308,16 @@ fn print_summary_result(result: &ExecutionResult) {
     println!("\n📊 Execution Summary:");
     println!("   ⏱️  Time: {:?}", result.execution_time);
     println!("   🔢 Operations: {}", result.operations_executed);
-    println!("   ⛽ Gas consumed: {}", result.gas_consumed);
+    println!("   ⛽ Gas consumed: {}/{}", result.gas_consumed, result.gas_limit);
+    println!("   💾 Memory limit: {} nibbles", result.memory_limit);
     println!("   📍 Final PC: {}", result.final_pc);
     
+    match &result.fault {
+        Some(fault @ VmError::OutOfGas { .. }) => println!("   ⛽ Out of gas: {}", fault),
+        Some(fault @ VmError::OutOfMemory { .. }) => println!("   💾 Out of memory: {}", fault),
+        _ => {}
+    }
+    
     if !result.errors.is_empty() {
         println!("   ⚠️  Errors: {}", result.errors.len());
     }
@@ -780,484 +1346,84 @@ fn print_detailed_result(result: &ExecutionResult, verbose: bool) {
     }
 }
 
-// Template creation functions
-
-fn create_hello_world_template(debug: bool) -> String {
-    let mut content = String::new();
-    
-    if debug {
-        content.push_str("// Supreme Mystery Hello World Template\n");
-        content.push_str("// Tesla 3-6-9 aligned tetragram program\n\n");
-    }
-    
-    // Simple hello world in tetragrams (NOP operations for demo)
-    content.push_str("𝌆𝌇𝌈𝌉𝌊𝌋𝌌𝌍𝌎"); // 9 tetragrams (Tesla aligned)
-    
-    if debug {
-        content.push_str("\n\n// This template demonstrates basic tetragram structure\n");
-        content.push_str("// Each symbol represents a 4-trit instruction nibble\n");
-    }
-    
-    content
-}
-
-fn create_fibonacci_template(debug: bool) -> String {
-    let mut content = String::new();
-    
-    if debug {
-        content.push_str("// Fibonacci sequence calculator\n");
-        content.push_str("// Demonstrates arithmetic operations\n\n");
-    }
-    
-    // More complex fibonacci pattern (27 tetragrams for sacred alignment)
-    content.push_str("𝌆𝌇𝌈𝌉𝌊𝌋𝌌𝌍𝌎𝌏𝌐𝌑𝌒𝌓𝌔𝌕𝌖𝌗𝌘𝌙𝌚𝌛𝌜𝌝𝌞𝌟𝌠");
-    
-    content
-}
-
-fn create_crypto_template(debug: bool) -> String {
-    let mut content = String::new();
-    
-    if debug {
-        content.push_str("// Cryptographic operations template\n");
-        content.push_str("// Tesla 3-6-9 aligned for sacred firewall\n\n");
-    }
-    
-    // 81 tetragrams for perfect tetragram alignment
-    let base_pattern = "𝌆𝌇𝌈𝌉𝌊𝌋𝌌𝌍𝌎";
-    content.push_str(&base_pattern.repeat(9)); // 9 × 9 = 81 tetragrams
-    
-    content
-}
-
-fn create_test_template(debug: bool) -> String {
-    let mut content = String::new();
-    
-    if debug {
-        content.push_str("// Test template for validation\n");
-        content.push_str("// Contains all instruction types\n\n");
-    }
-    
-    // Test pattern with various instruction types
-    content.push_str("𝌆𝌇𝌈𝌉𝌊𝌋𝌌𝌍𝌎𝌏𝌐𝌑"); // 12 tetragrams for testing
-    
-    content
-} 
-
-const DEFAULT_OUTPUT_DIR: &str = "vm_outputs";
-
-fn normalize_output_path(initial: PathBuf) -> PathBuf {
-    // If path is absolute or already contains directory separators, keep as is
-    if initial.is_absolute() || initial.components().count() > 1 {
-        return initial;
-    }
-    let mut out = PathBuf::from(DEFAULT_OUTPUT_DIR);
-    out.push(initial);
-    out
-} 
-
-// Universal tetragram encoding using proper lossless scheme
-fn encode_universal_to_tetragrams(input: &[u8], output: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🔢 Encoding {} bytes using lossless tetragram mapping", input.len());
-    
-    // Use a proper lossless encoding: convert bytes to base-81 digits
-    const GLYPH_BASE: u32 = 0x1D306;
-    let mut tetragrams: Vec<char> = Vec::new();
-    
-    // Convert input bytes to a single large number (little-endian)
-    let mut big_number = BigUint::from(0u32);
-    for (i, &byte) in input.iter().enumerate() {
-        let byte_value = BigUint::from(byte);
-        let shift = BigUint::from(256u32).pow(i as u32);
-        big_number += byte_value * shift;
-    }
-    
-    // Convert to base-81 digits
-    let base = BigUint::from(81u32);
-    let mut digits: Vec<u8> = Vec::new();
-    let mut value = big_number.clone();
-    
-    while !value.is_zero() {
-        let (quotient, remainder) = value.div_rem(&base);
-        digits.push(remainder.to_u8().unwrap());
-        value = quotient;
-    }
-    
-    // If input was all zeros, ensure we have at least one digit
-    if digits.is_empty() {
-        digits.push(0);
-    }
-    
-    // Convert digits to tetragrams
-    for &digit in digits.iter() {
-        if let Some(glyph) = std::char::from_u32(GLYPH_BASE + digit as u32) {
-            tetragrams.push(glyph);
-        }
-    }
-    
-    // Ensure Tesla 3-6-9 alignment (pad to multiple of 9)
-    while tetragrams.len() % 9 != 0 {
-        if let Some(glyph) = std::char::from_u32(GLYPH_BASE) {
-            tetragrams.push(glyph);
-        }
-    }
-    
-    let glyph_stream: String = tetragrams.iter().collect();
-    
-    // Ensure output has .sm extension
-    let mut out_path = output.clone();
-    if out_path.extension().map_or(true, |ext| ext != "sm") {
-        out_path = out_path.with_extension("sm");
-    }
-    let normalized_out_path = normalize_output_path(out_path);
-    
-    std::fs::write(&normalized_out_path, glyph_stream)?;
-    println!("✅ Encoded {} tetragrams to {}", tetragrams.len(), normalized_out_path.display());
-    println!("🔢 Original bytes: {}", input.len());
-    println!("📊 Efficiency: {:.2} bytes per tetragram", input.len() as f64 / tetragrams.len() as f64);
+// Stream bytes from any reader into a .sm file (or stdout) in constant memory
+fn encode_reader_to_tetragrams(reader: impl std::io::Read, output: &OutputTarget) -> Result<(), Box<dyn std::error::Error>> {
+    let mut out = output.create()?;
+    let stats = tetragram_codec::encode_stream(reader, &mut out)?;
+    out.flush()?;
+    output.announce(format_args!("✅ Encoded {} tetragrams to {}", stats.glyphs, output));
+    output.announce(format_args!("🔢 Original bytes: {}", stats.bytes));
+    output.announce(format_args!("📊 Efficiency: {:.2} bytes per tetragram", stats.bytes as f64 / stats.glyphs as f64));
     
     Ok(())
 }
 
-// Lossless tetragram decoding using base-81 conversion
-fn decode_tetragrams_to_bytes(input: &str) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-    // Remove whitespace and validate glyphs
-    let glyphs: Vec<char> = input.chars().filter(|c| !c.is_whitespace()).collect();
-    if glyphs.is_empty() {
-        return Err("No tetragram glyphs found in input".into());
-    }
-    
-    // Convert tetragrams back to base-81 digits
-    const GLYPH_BASE: u32 = 0x1D306;
-    let mut digits: Vec<u8> = Vec::new();
-    
-    for ch in glyphs {
-        let code = ch as u32;
-        if code < GLYPH_BASE || code > GLYPH_BASE + 80 {
-            return Err(format!("Invalid tetragram: '{}' (U+{:X})", ch, code).into());
-        }
-        
-        let digit = (code - GLYPH_BASE) as u8;
-        digits.push(digit);
-    }
-    
-    // Convert base-81 digits back to a large number
-    let base = BigUint::from(81u32);
-    let mut big_number = BigUint::from(0u32);
-    
-    for (i, &digit) in digits.iter().enumerate() {
-        let digit_value = BigUint::from(digit);
-        let power = base.pow(i as u32);
-        big_number += digit_value * power;
-    }
-    
-    // Convert large number back to bytes (little-endian)
-    let mut bytes: Vec<u8> = Vec::new();
-    let mut value = big_number.clone();
-    let byte_base = BigUint::from(256u32);
-    
-    while !value.is_zero() {
-        let (quotient, remainder) = value.div_rem(&byte_base);
-        bytes.push(remainder.to_u8().unwrap());
-        value = quotient;
-    }
-    
-    Ok(bytes)
-}
-
-// Encode number to tetragrams using universal byte mapping
-fn encode_number_to_tetragrams(input: &str, output: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    // Parse the input number
-    let number = input.parse::<BigUint>()
-        .map_err(|_| format!("Failed to parse number: {}", input))?;
-    
-    println!("🔢 Encoding number: {}", number);
-    
-    // Convert BigUint to bytes (little-endian, matching bytecode_to_glyphs)
-    let bytes = number.to_bytes_le();
-    
-    // Use universal encoding
-    encode_universal_to_tetragrams(&bytes, output)
-}
-
-// Encode text to tetragrams using universal byte mapping
-fn encode_text_to_tetragrams(input: &str, output: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    println!("📝 Encoding text: {}", input);
-    
-    // Convert text to bytes (UTF-8)
-    let bytes = input.as_bytes();
-    
-    // Use universal encoding
-    encode_universal_to_tetragrams(bytes, output)
-}
-
-// Decode tetragrams to number using universal byte mapping
-fn decode_tetragrams_to_number(input: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let bytes = decode_tetragrams_to_bytes(input)?;
-    
-    // Convert bytes back to BigUint (little-endian)
-    let number = BigUint::from_bytes_le(&bytes);
-    
-    Ok(number.to_str_radix(10))
-}
-
-// Decode tetragrams to text using universal byte mapping
-fn decode_tetragrams_to_text(input: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let bytes = decode_tetragrams_to_bytes(input)?;
-    
-    // Convert bytes back to text (UTF-8)
-    let text = String::from_utf8(bytes)
-        .map_err(|e| format!("Invalid UTF-8: {}", e))?;
-    
-    Ok(text)
-}
-
-// Trit-to-Tetragram direct mapping (Tesla 3-6-9 aligned)
-fn encode_trits_to_tetragrams_direct(trits: &[i8]) -> String {
-    const TETRAGRAM_BASE: u32 = 0x1D306;
-    let mut tetragrams = String::new();
-    
-    for &trit in trits {
-        // Map trit values (-1, 0, 1) to tetragram indices (0-80)
-        let tetragram_index = match trit {
-            -1 => 0,   // First tetragram for negative
-            0  => 27,  // Middle tetragram for zero (Tesla 3^3)
-            1  => 54,  // Last tetragram for positive
-            _  => panic!("Invalid trit value: {}", trit),
-        };
-        
-        // Convert to tetragram glyph
-        let glyph_code = TETRAGRAM_BASE + tetragram_index;
-        if let Some(glyph) = std::char::from_u32(glyph_code) {
-            tetragrams.push(glyph);
-        }
-    }
-    
-    tetragrams
-}
-
-// Decode tetragrams back to trits
-fn decode_tetragrams_to_trits_direct(tetragrams: &str) -> Vec<i8> {
-    const TETRAGRAM_BASE: u32 = 0x1D306;
-    let mut trits = Vec::new();
-    
-    for ch in tetragrams.chars() {
-        let code = ch as u32;
-        if code >= TETRAGRAM_BASE && code <= TETRAGRAM_BASE + 80 {
-            let index = code - TETRAGRAM_BASE;
-            let trit = match index {
-                0..=26  => -1,  // First 27 tetragrams = negative
-                27..=53 => 0,   // Middle 27 tetragrams = zero
-                54..=80 => 1,   // Last 27 tetragrams = positive
-                _ => panic!("Invalid tetragram index: {}", index),
+// Convert handler: every pair goes through the format-neutral IR, except the
+// streaming binary <-> sm pair which runs in constant memory
+pub fn convert_tetragram_file(args: ConvertArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let from_fmt: Format = args.from.parse()?;
+    let to_fmt: Format = args.to.parse()?;
+
+    let output = if to_fmt == Format::Sm { OutputTarget::glyphs(args.output.clone()) } else { OutputTarget::new(args.output.clone()) };
+    let input_path = Path::new(&args.input);
+    output.announce(format_args!("🔄 Converting from {} to {}", from_fmt, to_fmt));
+
+    match (from_fmt, to_fmt) {
+        (Format::Binary, Format::Sm) => {
+            output.announce(format_args!("🔢 Streaming {} through the chunked tetragram codec", display_input(input_path)));
+            return if is_stdio(input_path) {
+                encode_reader_to_tetragrams(std::io::stdin().lock(), &output)
+            } else {
+                encode_reader_to_tetragrams(fs::File::open(input_path)?, &output)
             };
-            trits.push(trit);
         }
-    }
-    
-    trits
-}
-
-// Convert text to trits using Tesla-aligned mapping
-fn text_to_trits(text: &str) -> Vec<i8> {
-    let mut trits = Vec::new();
-    
-    for byte in text.as_bytes() {
-        // Convert each byte to trits using Tesla 3-6-9 alignment
-        let mut value = *byte as i32;
-        
-        // Convert to base-3 representation
-        while value > 0 {
-            let remainder = value % 3;
-            let trit = match remainder {
-                0 => 0,
-                1 => 1,
-                2 => -1,  // Tesla negative encoding
-                _ => unreachable!(),
+        (Format::Sm, Format::Binary) => {
+            let mut out = output.create()?;
+            let stats = if is_stdio(input_path) {
+                tetragram_codec::decode_stream(std::io::stdin().lock(), &mut out)?
+            } else {
+                tetragram_codec::decode_stream(fs::File::open(input_path)?, &mut out)?
             };
-            trits.push(trit);
-            value = value / 3;
-        }
-        
-        // Pad to 4 trits per byte for Tesla alignment
-        while trits.len() % 4 != 0 {
-            trits.push(0);
-        }
-    }
-    
-    trits
-}
-
-// Convert trits back to text
-fn trits_to_text(trits: &[i8]) -> String {
-    let mut bytes = Vec::new();
-    let mut current_byte = 0;
-    let mut trit_count = 0;
-    
-    for &trit in trits {
-        let value = match trit {
-            -1 => 2,
-            0  => 0,
-            1  => 1,
-            _  => panic!("Invalid trit: {}", trit),
-        };
-        
-        current_byte = current_byte * 3 + value;
-        trit_count += 1;
-        
-        if trit_count == 4 {
-            bytes.push(current_byte as u8);
-            current_byte = 0;
-            trit_count = 0;
+            out.flush()?;
+            output.announce(format_args!("✅ Decoded {} tetragrams into {} bytes at {}", stats.glyphs, stats.bytes, output));
+            return Ok(());
         }
+        _ => {}
     }
-    
-    String::from_utf8(bytes).unwrap_or_else(|_| "Invalid UTF-8".to_string())
-}
-
-// Enhanced text-to-tetragram conversion using direct trit mapping
-fn encode_text_to_tetragrams_direct(input: &str, output: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
-    println!("📝 Encoding text using direct trit-to-tetragram mapping: {}", input);
-    
-    // Convert text to trits
-    let trits = text_to_trits(input);
-    println!("🔢 Generated {} trits", trits.len());
-    
-    // Convert trits to tetragrams
-    let tetragrams = encode_trits_to_tetragrams_direct(&trits);
-    println!("🔺 Generated {} tetragrams", tetragrams.chars().count());
-    
-    // Save to file
-    let normalized = normalize_output_path(output.clone());
-    let tetragram_count = tetragrams.chars().count();
-    fs::write(&normalized, &tetragrams)?;
-    println!("✅ Encoded {} tetragrams to {}", tetragram_count, normalized.display());
-    
-    // Tesla alignment analysis
-    let tesla_alignment = analyze_tesla_alignment(tetragram_count);
-    println!("🔺 Tesla alignment score: {:.2}", tesla_alignment.score);
-    println!("🔺 Tesla constants: {}", tesla_alignment.constants.join(", "));
-    
-    Ok(())
-}
-
-// Enhanced tetragram-to-text conversion using direct trit mapping
-fn decode_tetragrams_to_text_direct(input: &str) -> Result<String, Box<dyn std::error::Error>> {
-    // Convert tetragrams to trits
-    let trits = decode_tetragrams_to_trits_direct(input);
-    println!("🔢 Decoded {} trits", trits.len());
-    
-    // Convert trits to text
-    let text = trits_to_text(&trits);
-    println!("📝 Decoded text: {}", text);
-    
-    Ok(text)
-}
-
-// Convert handler implementation
-pub fn convert_tetragram_file(args: ConvertArgs) -> Result<(), Box<dyn std::error::Error>> {
-    use std::io::Read;
-    let from_fmt = args.from.to_lowercase();
-    let to_fmt = args.to.to_lowercase();
-
-    println!("🔄 Converting from {} to {}", from_fmt, to_fmt);
-
-    if from_fmt == "number" && to_fmt == "sm" {
-        // Encode number directly to tetragrams
-        encode_number_to_tetragrams(&args.input, &args.output)
-    } else if from_fmt == "text" && to_fmt == "sm" {
-        // Encode text directly to tetragrams using direct trit mapping
-        encode_text_to_tetragrams_direct(&args.input, &args.output)
-    } else if from_fmt == "sm" && to_fmt == "number" {
-        // Decode tetragrams to number
-        // Read input as file or direct string
-        let glyphs = if std::path::Path::new(&args.input).exists() {
-            std::fs::read_to_string(&args.input)?
-        } else {
-            args.input.clone()
-        };
-        let number = decode_tetragrams_to_number(&glyphs)?;
-        println!("🔢 Decoded number: {}", number);
-        // Write to output file if specified
-        if !args.output.as_os_str().is_empty() {
-            let normalized = normalize_output_path(args.output.clone());
-            std::fs::write(&normalized, number.as_bytes())?;
-            println!("✅ Wrote decoded number to {}", normalized.display());
-        }
-        Ok(())
-    } else if from_fmt == "sm" && to_fmt == "text" {
-        // Decode tetragrams to text using direct trit mapping
-        // Read input as file or direct string
-        let glyphs = if std::path::Path::new(&args.input).exists() {
-            std::fs::read_to_string(&args.input)?
-        } else {
-            args.input.clone()
-        };
-        let text = decode_tetragrams_to_text_direct(&glyphs)?;
-        println!("📝 Decoded text: {}", text);
-        // Write to output file if specified
-        if !args.output.as_os_str().is_empty() {
-            let normalized = normalize_output_path(args.output.clone());
-            std::fs::write(&normalized, text.as_bytes())?;
-            println!("✅ Wrote decoded text to {}", normalized.display());
-        }
-        Ok(())
-    } else if from_fmt == "tritvm" && to_fmt == "sm" {
-        // Read input bytecode
-        let mut data = Vec::new();
-        std::fs::File::open(&args.input)?.read_to_end(&mut data)?;
 
-        // Use translator first; if it yields empty string fallback to BigUint encoder
-        let glyph_stream_t = trinaryvm_runtime::translator::bytecode_to_glyphs(&data);
-        if !glyph_stream_t.is_empty() {
-            let mut out_path = args.output.clone();
-            if out_path.extension().map_or(true, |ext| ext != "sm") {
-                out_path = out_path.with_extension("sm");
+    // Read input as file (or stdin) or literal value
+    let input = if from_fmt.reads_file() || is_stdio(input_path) || (!from_fmt.reads_literal() && input_path.exists()) {
+        read_input(input_path)?
+    } else {
+        args.input.clone().into_bytes()
+    };
+    let ir = tetragram_ir::read(from_fmt, &input)?;
+    let bytes = tetragram_ir::write(&ir, to_fmt)?;
+    output.write(&bytes)?;
+
+    match to_fmt {
+        Format::Sm => {
+            let tetragram_count = String::from_utf8_lossy(&bytes).chars().count();
+            output.announce(format_args!("✅ Encoded {} tetragrams to {}", tetragram_count, output));
+            if let tetragram_ir::Ir::Text(text) = &ir {
+                let trits = text.len() * tetragram_codec::TRITS_PER_BYTE;
+                let capacity = tetragram_count * tetragram_codec::TRITS_PER_GLYPH;
+                output.announce(format_args!(
+                    "📐 Glyph efficiency: {:.2} bytes/glyph, {}/{} trits used ({:.1}%)",
+                    text.len() as f64 / tetragram_count.max(1) as f64,
+                    trits,
+                    capacity,
+                    100.0 * trits as f64 / capacity.max(1) as f64
+                ));
             }
-            let normalized = normalize_output_path(out_path);
-            std::fs::write(&normalized, glyph_stream_t)?;
-            println!("✅ Wrote glyph stream to {}", normalized.display());
-            return Ok(());
-        }
-        // Fallback BigUint encoder
-        let mut data = Vec::new();
-        std::fs::File::open(&args.input)?.read_to_end(&mut data)?;
-
-        // Convert entire byte stream into one BigUint (little-endian)
-        let big = BigUint::from_bytes_le(&data);
-
-        // Re-encode into base-81 digits
-        const BASE: u32 = 81;
-        let mut digits: Vec<u8> = Vec::new();
-        let mut value = big.clone();
-        let base = BigUint::from(BASE);
-        while !value.is_zero() {
-            let (q, r) = value.div_rem(&base);
-            digits.push(r.to_u8().unwrap());
-            value = q;
-        }
-        if digits.is_empty() {
-            digits.push(0);
+            let tesla_alignment = analyze_tesla_alignment(tetragram_count);
+            output.announce(format_args!("🔺 Tesla alignment score: {:.2}", tesla_alignment.score));
         }
-
-        // Map digits to glyphs
-        const GLYPH_BASE: u32 = 0x1D306;
-        let glyph_stream: String = digits
-            .iter()
-            .map(|d| std::char::from_u32(GLYPH_BASE + *d as u32).unwrap())
-            .collect();
-
-        let mut out_path = args.output.clone();
-        if out_path.extension().map_or(true, |ext| ext != "sm") {
-            out_path = out_path.with_extension("sm");
+        Format::Number | Format::Text if !output.is_stdout() => {
+            println!("📝 Decoded {}: {}", to_fmt, String::from_utf8_lossy(&bytes));
+            println!("✅ Wrote decoded {} to {}", to_fmt, output);
         }
-        let normalized = normalize_output_path(out_path);
-        std::fs::write(&normalized, glyph_stream)?;
-        println!("✅ Wrote {} glyphs to {}", digits.len(), normalized.display());
-        Ok(())
-    } else {
-        Err(format!("Unsupported conversion: {} -> {}", from_fmt, to_fmt).into())
+        _ => output.announce(format_args!("✅ Wrote {} bytes of {} to {}", bytes.len(), to_fmt, output)),
     }
-} 
-
- 
\ No newline at end of file
+    Ok(())
+}
@@ -0,0 +1,455 @@
+//! Interactive step debugger for Supreme Mystery programs
+//!
+//! Wraps a `TetragramMicroVM` with breakpoints, memory watchpoints and a
+//! snapshot history so execution can be stepped backwards as well as forwards.
+
+use crate::tetragram_vm::{decode_instruction, nibble_to_value, Opcode, TetragramMicroVM, VmError, VmSnapshot};
+use crossterm::{
+    cursor::MoveTo,
+    execute,
+    style::Stylize,
+    terminal::{Clear, ClearType},
+};
+use std::collections::{BTreeSet, VecDeque};
+use std::io::{self, BufRead, Write};
+
+/// Maximum number of snapshots kept for reverse stepping
+const HISTORY_LIMIT: usize = 10_000;
+
+/// Why a `continue` or `step` stopped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// Single step completed
+    Stepped,
+    /// Reached a glyph-index breakpoint
+    Breakpoint(usize),
+    /// Reached an instruction with a breakpointed opcode
+    OpcodeBreakpoint(Opcode),
+    /// A watched memory nibble changed value
+    Watchpoint { address: usize, old: u8, new: u8 },
+    /// Program counter ran past the end of the stream
+    Halted,
+    /// Execution faulted
+    Fault(VmError),
+}
+
+/// Debugger state around a micro VM
+pub struct Debugger {
+    vm: TetragramMicroVM,
+    /// Snapshots hold only mutable state; the program lives once, in `vm`
+    initial: VmSnapshot,
+    history: VecDeque<VmSnapshot>,
+    breakpoints: BTreeSet<usize>,
+    opcode_breakpoints: BTreeSet<Opcode>,
+    watchpoints: BTreeSet<usize>,
+}
+
+impl Debugger {
+    pub fn new(vm: TetragramMicroVM) -> Self {
+        Self {
+            initial: vm.snapshot(),
+            vm,
+            history: VecDeque::new(),
+            breakpoints: BTreeSet::new(),
+            opcode_breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+        }
+    }
+
+    pub fn vm(&self) -> &TetragramMicroVM {
+        &self.vm
+    }
+
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    pub fn add_breakpoint(&mut self, pc: usize) {
+        self.breakpoints.insert(pc);
+    }
+
+    pub fn add_opcode_breakpoint(&mut self, opcode: Opcode) {
+        self.opcode_breakpoints.insert(opcode);
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize) {
+        self.watchpoints.insert(address);
+    }
+
+    /// Execute one instruction, recording a snapshot for reverse stepping
+    pub fn step(&mut self) -> StopReason {
+        if self.vm.is_halted() {
+            return StopReason::Halted;
+        }
+        let before = self.vm.snapshot();
+        match self.vm.step() {
+            Ok(_) => {
+                if self.history.len() == HISTORY_LIMIT {
+                    self.history.pop_front();
+                }
+                for &address in &self.watchpoints {
+                    let (old, new) = match (before.memory().get(address), self.vm.memory().get(address)) {
+                        (Some(old), Some(new)) => (nibble_to_value(old), nibble_to_value(new)),
+                        _ => continue,
+                    };
+                    if old != new {
+                        self.history.push_back(before);
+                        return StopReason::Watchpoint { address, old, new };
+                    }
+                }
+                self.history.push_back(before);
+                if self.vm.is_halted() {
+                    StopReason::Halted
+                } else {
+                    StopReason::Stepped
+                }
+            }
+            Err(e) => StopReason::Fault(e),
+        }
+    }
+
+    /// Run until a breakpoint, watchpoint, halt or fault
+    pub fn continue_run(&mut self) -> StopReason {
+        let mut first = true;
+        loop {
+            if !first {
+                if let Some(reason) = self.breakpoint_at_pc() {
+                    return reason;
+                }
+            }
+            first = false;
+            match self.step() {
+                StopReason::Stepped => continue,
+                other => return other,
+            }
+        }
+    }
+
+    /// Undo the last executed instruction; returns false when history is empty
+    pub fn reverse_step(&mut self) -> bool {
+        match self.history.pop_back() {
+            Some(previous) => {
+                self.vm.restore(&previous);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Restore the initial machine state and clear history
+    pub fn reset(&mut self) {
+        self.vm.restore(&self.initial);
+        self.history.clear();
+    }
+
+    fn breakpoint_at_pc(&self) -> Option<StopReason> {
+        let pc = self.vm.pc();
+        if self.breakpoints.contains(&pc) {
+            return Some(StopReason::Breakpoint(pc));
+        }
+        let opcode = self.vm.program().get(pc).and_then(|&v| Opcode::from_value(v))?;
+        if self.opcode_breakpoints.contains(&opcode) {
+            return Some(StopReason::OpcodeBreakpoint(opcode));
+        }
+        None
+    }
+
+    /// Describe the instruction at the current program counter
+    pub fn current_instruction(&self) -> String {
+        if self.vm.is_halted() {
+            return format!("{:04}: <halted>", self.vm.pc());
+        }
+        match decode_instruction(self.vm.program(), self.vm.pc()) {
+            Ok(instr) => format!("{:04}: {}", instr.pc, instr),
+            Err(e) => format!("{:04}: <{}>", self.vm.pc(), e),
+        }
+    }
+
+    /// Execute one REPL command; returns `None` when the session should end
+    pub fn execute_command(&mut self, line: &str) -> Option<String> {
+        let mut parts = line.split_whitespace();
+        let command = parts.next().unwrap_or("");
+        let arg = parts.next();
+
+        let output = match command {
+            "" => String::new(),
+            "q" | "quit" | "exit" => return None,
+            "h" | "help" => HELP.to_string(),
+            "s" | "step" => {
+                let count = arg.and_then(|a| a.parse::<usize>().ok()).unwrap_or(1);
+                let mut reason = StopReason::Stepped;
+                for _ in 0..count {
+                    reason = self.step();
+                    if reason != StopReason::Stepped {
+                        break;
+                    }
+                }
+                self.describe_stop(&reason)
+            }
+            "c" | "continue" => {
+                let reason = self.continue_run();
+                self.describe_stop(&reason)
+            }
+            "rs" | "back" => {
+                let count = arg.and_then(|a| a.parse::<usize>().ok()).unwrap_or(1);
+                let undone = (0..count).take_while(|_| self.reverse_step()).count();
+                if undone == 0 {
+                    "⏮️  No history to reverse".to_string()
+                } else {
+                    format!("⏮️  Reversed {} step(s)\n{}", undone, self.current_instruction())
+                }
+            }
+            "b" | "break" => match arg {
+                Some(a) => match a.parse::<usize>() {
+                    Ok(pc) => {
+                        self.add_breakpoint(pc);
+                        format!("🔴 Breakpoint at glyph {}", pc)
+                    }
+                    Err(_) => match Opcode::from_mnemonic(a) {
+                        Some(op) => {
+                            self.add_opcode_breakpoint(op);
+                            format!("🔴 Breakpoint on opcode {}", op)
+                        }
+                        None => format!("❌ Unknown breakpoint target: {}", a),
+                    },
+                },
+                None => "❌ Usage: break <glyph-index|OPCODE>".to_string(),
+            },
+            "d" | "delete" => match arg {
+                Some(a) => {
+                    let removed = match a.parse::<usize>() {
+                        Ok(pc) => self.breakpoints.remove(&pc),
+                        Err(_) => Opcode::from_mnemonic(a).is_some_and(|op| self.opcode_breakpoints.remove(&op)),
+                    };
+                    if removed { format!("Deleted breakpoint {}", a) } else { format!("❌ No breakpoint {}", a) }
+                }
+                None => "❌ Usage: delete <glyph-index|OPCODE>".to_string(),
+            },
+            "w" | "watch" => match arg.and_then(|a| a.parse::<usize>().ok()) {
+                Some(address) if address < self.vm.memory_limit() => {
+                    self.add_watchpoint(address);
+                    format!("👁️  Watching M{:02}", address)
+                }
+                Some(address) => format!("❌ Address {} beyond memory limit {}", address, self.vm.memory_limit()),
+                None => format!("❌ Usage: watch <0-{}>", self.vm.memory_limit().saturating_sub(1)),
+            },
+            "unwatch" => match arg.and_then(|a| a.parse::<usize>().ok()) {
+                Some(address) if self.watchpoints.remove(&address) => format!("Removed watchpoint M{:02}", address),
+                _ => "❌ No such watchpoint".to_string(),
+            },
+            "i" | "info" => self.format_info(),
+            "r" | "regs" => self.format_registers(),
+            "st" | "status" => format!("Status: {:?} (sign {})", self.vm.status(), self.vm.status_sign()),
+            "m" | "mem" => match arg.and_then(|a| a.parse::<usize>().ok()) {
+                Some(address) => match self.vm.memory().get(address) {
+                    Some(nibble) => format!("M{:02}: {:?}", address, nibble),
+                    None => format!("❌ Address {} beyond memory limit {}", address, self.vm.memory_limit()),
+                },
+                None => self.format_memory(),
+            },
+            "l" | "list" => self.current_instruction(),
+            "reset" => {
+                self.reset();
+                format!("🔄 Reset\n{}", self.current_instruction())
+            }
+            other => format!("❌ Unknown command '{}' (type 'help')", other),
+        };
+        Some(output)
+    }
+
+    fn describe_stop(&self, reason: &StopReason) -> String {
+        let headline = match reason {
+            StopReason::Stepped => String::new(),
+            StopReason::Breakpoint(pc) => format!("🔴 Breakpoint at glyph {}\n", pc),
+            StopReason::OpcodeBreakpoint(op) => format!("🔴 Breakpoint on {}\n", op),
+            StopReason::Watchpoint { address, old, new } => {
+                format!("👁️  M{:02} changed {} -> {}\n", address, old, new)
+            }
+            StopReason::Halted => "🏁 Program halted\n".to_string(),
+            StopReason::Fault(e) => format!("❌ Fault: {}\n", e),
+        };
+        format!(
+            "{}{}   (gas {}/{})",
+            headline,
+            self.current_instruction(),
+            self.vm.gas_consumed(),
+            self.vm.gas_limit()
+        )
+    }
+
+    fn format_info(&self) -> String {
+        let opcodes: Vec<&str> = self.opcode_breakpoints.iter().map(|op| op.mnemonic()).collect();
+        format!(
+            "Breakpoints: {:?}\nOpcode breakpoints: {:?}\nWatchpoints: {:?}\nHistory: {} step(s)",
+            self.breakpoints, opcodes, self.watchpoints, self.history.len()
+        )
+    }
+
+    fn format_registers(&self) -> String {
+        let mut out = String::new();
+        for (i, reg) in self.vm.registers().iter().enumerate() {
+            out.push_str(&format!("R{}: [{:2}, {:2}, {:2}, {:2}]\n", i, reg[0], reg[1], reg[2], reg[3]));
+        }
+        out.push_str(&format!("SR: {:?}  PC: {}", self.vm.status(), self.vm.pc()));
+        out
+    }
+
+    fn format_memory(&self) -> String {
+        self.vm
+            .memory()
+            .chunks(9)
+            .enumerate()
+            .map(|(row, cells)| {
+                let values: Vec<String> = cells.iter().map(|n| format!("{:2}", nibble_to_value(n))).collect();
+                format!("M{:02}: {}", row * 9, values.join(" "))
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Redraw a full-screen view of registers and memory, with the last
+    /// command's output below it
+    pub fn render_view(&self, out: &mut impl Write, message: &str) -> io::Result<()> {
+        execute!(out, Clear(ClearType::All), MoveTo(0, 0))?;
+        writeln!(out, "{}", "🔺 TetragramMicroVM Debugger".bold())?;
+        writeln!(out, "{}\n", self.current_instruction())?;
+        for (i, reg) in self.vm.registers().iter().enumerate() {
+            writeln!(out, "  R{}: [{:2}, {:2}, {:2}, {:2}]", i, reg[0], reg[1], reg[2], reg[3])?;
+        }
+        writeln!(out, "  SR: {:?}\n", self.vm.status())?;
+        for (row, cells) in self.vm.memory().chunks(9).enumerate() {
+            write!(out, "  M{:02} ", row * 9)?;
+            for (col, nibble) in cells.iter().enumerate() {
+                let cell = format!("{:3}", nibble_to_value(nibble));
+                if self.watchpoints.contains(&(row * 9 + col)) {
+                    write!(out, "{}", cell.reverse())?;
+                } else {
+                    write!(out, "{}", cell)?;
+                }
+            }
+            writeln!(out)?;
+        }
+        writeln!(out, "\n  gas {}/{}\n", self.vm.gas_consumed(), self.vm.gas_limit())?;
+        if !message.is_empty() {
+            writeln!(out, "{}", message)?;
+        }
+        out.flush()
+    }
+}
+
+const HELP: &str = "\
+Commands:
+  s, step [n]        Execute n instructions (default 1)
+  c, continue        Run to the next breakpoint, watchpoint, halt or fault
+  rs, back [n]       Reverse n instructions
+  b, break <i|OP>    Break at glyph index or on an opcode mnemonic
+  d, delete <i|OP>   Remove a breakpoint
+  w, watch <addr>    Stop when memory nibble <addr> changes
+  unwatch <addr>     Remove a watchpoint
+  r, regs            Print registers and status register
+  st, status         Print the status register
+  m, mem [addr]      Print memory (all cells or one nibble)
+  l, list            Show the current instruction
+  i, info            List breakpoints, watchpoints and history depth
+  reset              Restart from the initial state
+  q, quit            Leave the debugger";
+
+/// Run the interactive REPL on stdin/stdout
+pub fn run_repl(mut debugger: Debugger, tui: bool) -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    println!("🐛 TetragramMicroVM debugger - type 'help' for commands");
+    println!("{}", debugger.current_instruction());
+
+    // In TUI mode the view is redrawn before each prompt, so command output
+    // is kept and drawn below it rather than printed and then cleared
+    let mut message = String::from("🐛 Type 'help' for commands");
+    loop {
+        if tui {
+            debugger.render_view(&mut stdout, &message)?;
+        }
+        print!("(smdb) ");
+        stdout.flush()?;
+
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        match debugger.execute_command(line.trim()) {
+            Some(output) if tui => message = output,
+            Some(output) => {
+                if !output.is_empty() {
+                    println!("{}", output);
+                }
+            }
+            None => break,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // CONST4 R0, 80; ST4 [5], R0; BR4 AL @10; NOP; PUSHOUT R0
+    fn sample() -> Debugger {
+        Debugger::new(TetragramMicroVM::new(vec![12, 0, 80, 2, 5, 0, 10, 0, 10, 0, 11, 0]))
+    }
+
+    #[test]
+    fn test_step_and_reverse() {
+        let mut dbg = sample();
+        assert_eq!(dbg.step(), StopReason::Stepped);
+        assert_eq!(dbg.step(), StopReason::Stepped);
+        assert_eq!(dbg.vm().pc(), 6);
+        assert!(dbg.reverse_step());
+        assert_eq!(dbg.vm().pc(), 3);
+        assert_eq!(dbg.vm().memory()[5], [0, 0, 0, 0]);
+        assert!(dbg.reverse_step());
+        assert!(!dbg.reverse_step());
+        assert_eq!(dbg.vm().gas_consumed(), 0);
+
+        // Output pushed after a snapshot goes away with it
+        assert_eq!(dbg.continue_run(), StopReason::Halted);
+        assert_eq!(dbg.vm().output(), &[80]);
+        assert!(dbg.reverse_step());
+        assert!(dbg.vm().output().is_empty());
+        dbg.reset();
+        assert_eq!((dbg.vm().pc(), dbg.history_len()), (0, 0));
+    }
+
+    #[test]
+    fn test_breakpoints_and_watchpoints() {
+        let mut dbg = sample();
+        dbg.add_watchpoint(5);
+        assert_eq!(dbg.continue_run(), StopReason::Watchpoint { address: 5, old: 40, new: 80 });
+
+        let mut dbg = sample();
+        dbg.add_opcode_breakpoint(Opcode::Br4);
+        assert_eq!(dbg.continue_run(), StopReason::OpcodeBreakpoint(Opcode::Br4));
+
+        dbg.add_breakpoint(10);
+        assert_eq!(dbg.continue_run(), StopReason::Breakpoint(10));
+        assert_eq!(dbg.continue_run(), StopReason::Halted);
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut dbg = sample();
+        assert!(dbg.execute_command("break PUSHOUT").unwrap().contains("PUSHOUT"));
+        assert!(dbg.execute_command("c").unwrap().contains("Breakpoint on PUSHOUT"));
+        assert!(dbg.execute_command("back 2").unwrap().contains("Reversed 2"));
+        assert!(dbg.execute_command("bogus").unwrap().contains("Unknown command"));
+        assert!(dbg.execute_command("watch 80").unwrap().contains("Watching M80"));
+
+        let mut view = Vec::new();
+        dbg.render_view(&mut view, "R0: [ 1,  0,  0, -1]").unwrap();
+        assert!(String::from_utf8_lossy(&view).ends_with("R0: [ 1,  0,  0, -1]\n"));
+
+        let mut dbg = Debugger::new(TetragramMicroVM::new(vec![0]).with_memory_limit(27));
+        assert!(dbg.execute_command("watch 27").unwrap().contains("beyond memory limit 27"));
+        assert!(dbg.execute_command("watch").unwrap().contains("watch <0-26>"));
+        assert!(dbg.execute_command("quit").is_none());
+    }
+}
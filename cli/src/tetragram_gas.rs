@@ -0,0 +1,306 @@
+//! Static gas model for micro-ISA programs
+//!
+//! Costs come from [`Opcode::gas_cost`], the same table the executor meters
+//! (NOP 1, BR4 9, everything else 3). Over the control-flow graph:
+//!
+//! * **min** is the cheapest path from the entry point to a halt
+//! * **max** is the most expensive terminating path, counting each bounded
+//!   loop body `bound` times; it is unbounded if any reachable cycle has no
+//!   inferred bound
+//!
+//! Loop bounds are inferred for the countdown idiom only: a single-block loop
+//! that shifts a register with SHL4/SHR4 (and writes it no other way),
+//! compares it with a register that is still zero on entry, and branches back
+//! on NE, LT or GT. Four shifts clear a nibble, so such a loop runs at most
+//! 4 times (5 if the compare precedes the shift). Other loops are annotated
+//! with the reason no bound was inferred.
+
+use crate::tetragram_cfg::{register_effects, BasicBlock, Cfg, EdgeKind, Successor};
+use crate::tetragram_vm::{Condition, Opcode};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
+use std::cmp::Reverse;
+
+/// Static count and gas of one opcode over reachable code
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct OpcodeGas {
+    pub opcode: &'static str,
+    pub count: usize,
+    pub unit_gas: u64,
+    pub total_gas: u64,
+}
+
+/// Iteration bound of a loop, or why none was inferred
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LoopBound {
+    pub header: usize,
+    pub bound: Option<u32>,
+    pub note: String,
+}
+
+/// Gas of the reachable code and over CFG paths
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GasBounds {
+    pub per_opcode: Vec<OpcodeGas>,
+    /// Every reachable instruction executed once
+    pub static_gas: u64,
+    /// Cheapest path to a halt (`None` if no path halts)
+    pub min_gas: Option<u64>,
+    /// Most expensive terminating path (`None` if unbounded)
+    pub max_gas: Option<u64>,
+    pub loops: Vec<LoopBound>,
+}
+
+fn block_gas(block: &BasicBlock) -> u64 {
+    block.instructions.iter().map(|i| i.opcode.gas_cost()).sum()
+}
+
+/// Per-opcode counts and gas over the reachable instructions
+pub fn opcode_gas(cfg: &Cfg) -> Vec<OpcodeGas> {
+    let mut counts: BTreeMap<Opcode, usize> = BTreeMap::new();
+    for instr in cfg.blocks.values().flat_map(|b| &b.instructions) {
+        *counts.entry(instr.opcode).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .map(|(opcode, count)| OpcodeGas {
+            opcode: opcode.mnemonic(),
+            count,
+            unit_gas: opcode.gas_cost(),
+            total_gas: count as u64 * opcode.gas_cost(),
+        })
+        .collect()
+}
+
+/// Registers some path may have written before each block starts
+fn may_written(cfg: &Cfg) -> BTreeMap<usize, u16> {
+    let mut entry: BTreeMap<usize, u16> = cfg.blocks.keys().map(|&pc| (pc, 0)).collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for block in cfg.blocks.values() {
+            let written = block.instructions.iter().fold(entry[&block.start], |acc, i| acc | register_effects(i).1);
+            for (succ, _) in &block.successors {
+                if let Successor::Block(next) = succ {
+                    let merged = entry[next] | written;
+                    if merged != entry[next] {
+                        entry.insert(*next, merged);
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+    entry
+}
+
+/// Infer the bound of a single-block countdown loop
+fn countdown_bound(block: &BasicBlock, zero_on_entry: u16) -> Result<u32, String> {
+    let cond = match block.successors.first() {
+        Some((Successor::Block(target), EdgeKind::Taken(cond))) if *target == block.start => *cond,
+        _ => return Err("back edge is not a conditional BR4".to_string()),
+    };
+    if !matches!(cond, Condition::Ne | Condition::Lt | Condition::Gt) {
+        return Err(format!("BR4 {} can keep looping once the counter is zero", cond.mnemonic()));
+    }
+    let Some(cmp_index) = block.instructions.iter().rposition(|i| i.opcode == Opcode::Cmp4) else {
+        return Err("no CMP4 sets the loop condition".to_string());
+    };
+
+    let written: u16 = block.instructions.iter().fold(0, |acc, i| acc | register_effects(i).1);
+    let cmp = &block.instructions[cmp_index];
+    for (counter, other) in [(cmp.operands[0], cmp.operands[1]), (cmp.operands[1], cmp.operands[0])] {
+        let shifts: Vec<usize> = block
+            .instructions
+            .iter()
+            .enumerate()
+            .filter(|(_, i)| matches!(i.opcode, Opcode::Shl4 | Opcode::Shr4) && i.operands[0] == counter)
+            .map(|(idx, _)| idx)
+            .collect();
+        let counter_writes = block
+            .instructions
+            .iter()
+            .filter(|i| !matches!(i.opcode, Opcode::Shl4 | Opcode::Shr4) && register_effects(i).1 & (1 << counter) != 0)
+            .count();
+        let other_is_zero = written & (1 << other) == 0 && zero_on_entry & (1 << other) != 0;
+        if !shifts.is_empty() && counter_writes == 0 && other_is_zero && counter != other {
+            // Four shifts clear any nibble; one more trip if the compare runs first
+            return Ok(if shifts[0] < cmp_index { 4 } else { 5 });
+        }
+    }
+    Err("loop condition is not a shifted counter compared with zero".to_string())
+}
+
+/// Iteration bounds for every loop in the graph
+pub fn loop_bounds(cfg: &Cfg) -> Vec<LoopBound> {
+    let written = may_written(cfg);
+    cfg.loops()
+        .into_iter()
+        .map(|l| {
+            let result = if l.closed {
+                Err("loop has no exit".to_string())
+            } else if l.blocks.len() > 1 {
+                Err(format!("loop spans {} blocks", l.blocks.len()))
+            } else {
+                let block = &cfg.blocks[&l.header];
+                // Registers no path has written before entering the loop are still zero
+                let entering = cfg
+                    .blocks
+                    .values()
+                    .filter(|b| b.start != block.start && b.successors.iter().any(|(s, _)| *s == Successor::Block(block.start)));
+                let written_before = entering.fold(0u16, |acc, b| {
+                    acc | b.instructions.iter().fold(written[&b.start], |w, i| w | register_effects(i).1)
+                });
+                countdown_bound(block, !written_before)
+            };
+            match result {
+                Ok(bound) => LoopBound { header: l.header, bound: Some(bound), note: format!("countdown, at most {} iterations", bound) },
+                Err(reason) => LoopBound { header: l.header, bound: None, note: format!("bound unknown: {}", reason) },
+            }
+        })
+        .collect()
+}
+
+/// Cheapest path from the entry to a halt (Dijkstra over block costs)
+fn min_gas(cfg: &Cfg) -> Option<u64> {
+    let start = match cfg.entry {
+        Successor::Halt => return Some(0),
+        Successor::Fault => return None,
+        Successor::Block(pc) => pc,
+    };
+    let mut best: BTreeMap<usize, u64> = BTreeMap::new();
+    let mut queue = BinaryHeap::from([Reverse((block_gas(&cfg.blocks[&start]), start))]);
+    while let Some(Reverse((gas, pc))) = queue.pop() {
+        if best.contains_key(&pc) {
+            continue;
+        }
+        best.insert(pc, gas);
+        for (succ, _) in &cfg.blocks[&pc].successors {
+            match succ {
+                Successor::Halt => return Some(gas),
+                Successor::Block(next) if !best.contains_key(next) => {
+                    queue.push(Reverse((gas + block_gas(&cfg.blocks[next]), *next)));
+                }
+                _ => {}
+            }
+        }
+    }
+    None
+}
+
+/// Most expensive terminating path, with bounded self-loops expanded
+fn max_gas(cfg: &Cfg, bounds: &[LoopBound]) -> Option<u64> {
+    fn visit(
+        cfg: &Cfg,
+        pc: usize,
+        bounded: &BTreeMap<usize, u32>,
+        memo: &mut BTreeMap<usize, Option<u64>>,
+        active: &mut BTreeSet<usize>,
+    ) -> Option<Option<u64>> {
+        if let Some(&known) = memo.get(&pc) {
+            return Some(known);
+        }
+        if !active.insert(pc) {
+            // Unbounded cycle
+            return None;
+        }
+        let block = &cfg.blocks[&pc];
+        let trips = bounded.get(&pc).copied().unwrap_or(1) as u64;
+        let mut longest: Option<u64> = None;
+        for (succ, _) in &block.successors {
+            let rest = match succ {
+                Successor::Halt | Successor::Fault => Some(0),
+                Successor::Block(next) if *next == pc && bounded.contains_key(&pc) => continue,
+                Successor::Block(next) => visit(cfg, *next, bounded, memo, active)?,
+            };
+            if let Some(rest) = rest {
+                longest = Some(longest.map_or(rest, |l| l.max(rest)));
+            }
+        }
+        active.remove(&pc);
+        // `None` inside means "never terminates from here", which adds no path
+        let total = longest.map(|l| l + trips * block_gas(block));
+        memo.insert(pc, total);
+        Some(total)
+    }
+
+    let bounded: BTreeMap<usize, u32> = bounds.iter().filter_map(|b| b.bound.map(|n| (b.header, n))).collect();
+    match cfg.entry {
+        Successor::Halt | Successor::Fault => Some(0),
+        Successor::Block(pc) => visit(cfg, pc, &bounded, &mut BTreeMap::new(), &mut BTreeSet::new()).flatten(),
+    }
+}
+
+/// Full static gas analysis
+pub fn gas_bounds(cfg: &Cfg) -> GasBounds {
+    let per_opcode = opcode_gas(cfg);
+    let loops = loop_bounds(cfg);
+    GasBounds {
+        static_gas: per_opcode.iter().map(|o| o.total_gas).sum(),
+        min_gas: min_gas(cfg),
+        max_gas: max_gas(cfg, &loops),
+        per_opcode,
+        loops,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetragram_asm::{assemble, TEMPLATES};
+    use crate::tetragram_vm::TetragramMicroVM;
+
+    fn bounds(source: &str) -> GasBounds {
+        gas_bounds(&Cfg::build(&assemble(source).expect("test program assembles")))
+    }
+
+    #[test]
+    fn test_branch_paths_bound_min_and_max() {
+        let gas = bounds("
+            CONST4 R0, 41
+            CMP4 R0, R1
+            BR4 EQ, done     ; 3 + 3 + 9
+            PUSHOUT R0
+            NOP              ; + 3 + 1
+        done:
+        ");
+        assert_eq!((gas.static_gas, gas.min_gas, gas.max_gas), (19, Some(15), Some(19)));
+        let br4 = gas.per_opcode.iter().find(|o| o.opcode == "BR4").unwrap();
+        assert_eq!((br4.count, br4.unit_gas, br4.total_gas), (1, 9, 9));
+    }
+
+    #[test]
+    fn test_countdown_loop_bound_matches_execution() {
+        // The `test` template counts R1 down from 1111 with SHR4 against R2 = 0
+        let source = TEMPLATES.iter().find(|(name, _)| *name == "test").unwrap().1;
+        let program = assemble(source).unwrap();
+        let gas = gas_bounds(&Cfg::build(&program));
+        assert_eq!(gas.loops.len(), 1);
+        assert_eq!(gas.loops[0].bound, Some(4));
+
+        let mut vm = TetragramMicroVM::new(program);
+        let result = vm.run();
+        assert!(result.fault.is_none());
+        assert!(gas.min_gas.unwrap() <= result.gas_consumed);
+        assert_eq!(gas.max_gas, Some(result.gas_consumed));
+    }
+
+    #[test]
+    fn test_unbounded_loops_are_annotated() {
+        let gas = bounds("
+            CONST4 R2, 41    ; comparison register is not zero
+            CONST4 R1, 80
+        loop:
+            SHR4 R1
+            CMP4 R1, R2
+            BR4 NE, loop
+        ");
+        assert_eq!(gas.max_gas, None);
+        assert_eq!(gas.loops[0].bound, None);
+        assert!(gas.loops[0].note.contains("not a shifted counter"));
+
+        let spin = bounds("spin: BR4 AL, spin");
+        assert_eq!((spin.min_gas, spin.max_gas), (None, None));
+        assert_eq!(spin.loops[0].note, "bound unknown: loop has no exit");
+    }
+}
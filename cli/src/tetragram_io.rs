@@ -0,0 +1,168 @@
+//! Input and output paths shared by the tetragram commands
+//!
+//! Every input path accepts `-` for stdin and every output path accepts `-`
+//! for stdout. Glyph programs are recognized by extension ([`GLYPH_EXTENSIONS`])
+//! or, failing that, by content: a file whose non-comment characters are
+//! mostly tetragram glyphs is accepted whatever it is called, as is anything
+//! on stdin. Output paths are used exactly as given.
+
+use crate::tetragram_vm::glyph_to_value;
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Path that stands for stdin (inputs) or stdout (outputs)
+pub const STDIO: &str = "-";
+/// Extensions recognized as Supreme Mystery glyph programs
+pub const GLYPH_EXTENSIONS: &[&str] = &["sm", "supmys"];
+
+pub fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == STDIO
+}
+
+/// Whether `path` carries one of the [`GLYPH_EXTENSIONS`]
+pub fn has_glyph_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| GLYPH_EXTENSIONS.iter().any(|known| ext.eq_ignore_ascii_case(known)))
+}
+
+/// Content sniffing: at least half of the non-whitespace, non-comment
+/// characters are tetragram glyphs (so validate can still report the rest)
+pub fn looks_like_glyph_source(source: &str) -> bool {
+    let (mut glyphs, mut total) = (0usize, 0usize);
+    for line in source.lines() {
+        let code = line.find("//").map_or(line, |idx| &line[..idx]);
+        for ch in code.chars().filter(|ch| !ch.is_whitespace() && !ch.is_control()) {
+            total += 1;
+            if glyph_to_value(ch).is_some() {
+                glyphs += 1;
+            }
+        }
+    }
+    glyphs > 0 && glyphs * 2 >= total
+}
+
+/// Read an input path, or stdin for `-`
+pub fn read_input(path: &Path) -> io::Result<Vec<u8>> {
+    if is_stdio(path) {
+        let mut bytes = Vec::new();
+        io::stdin().lock().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    } else {
+        fs::read(path)
+    }
+}
+
+/// Read an input path as UTF-8 text, or stdin for `-`
+pub fn read_input_string(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    String::from_utf8(read_input(path)?).map_err(|e| format!("{}: not UTF-8 text: {}", display_input(path), e).into())
+}
+
+/// Load a glyph program, accepting recognized extensions, stdin (even when
+/// empty) and any file whose content looks like a glyph stream
+pub fn load_glyph_source(path: &Path) -> Result<String, Box<dyn std::error::Error>> {
+    let source = read_input_string(path)?;
+    if is_stdio(path) || has_glyph_extension(path) || looks_like_glyph_source(&source) {
+        return Ok(source);
+    }
+    let extensions: Vec<String> = GLYPH_EXTENSIONS.iter().map(|ext| format!(".{}", ext)).collect();
+    Err(format!(
+        "{} is not a Supreme Mystery program (expected a {} file or tetragram glyph content)",
+        display_input(path),
+        extensions.join("/")
+    )
+    .into())
+}
+
+/// Human-readable name of an input path
+pub fn display_input(path: &Path) -> String {
+    if is_stdio(path) { "<stdin>".to_string() } else { path.display().to_string() }
+}
+
+/// Where a command writes its result: stdout for `-`, otherwise a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputTarget {
+    Stdout,
+    File(PathBuf),
+}
+
+impl OutputTarget {
+    /// Resolve an output path (`-` or a file path, used as given)
+    pub fn new(path: PathBuf) -> Self {
+        if is_stdio(&path) { OutputTarget::Stdout } else { OutputTarget::File(path) }
+    }
+
+    /// Like [`OutputTarget::new`], forcing a glyph extension onto file outputs
+    pub fn glyphs(path: PathBuf) -> Self {
+        if is_stdio(&path) || has_glyph_extension(&path) { Self::new(path) } else { Self::new(path.with_extension("sm")) }
+    }
+
+    pub fn is_stdout(&self) -> bool {
+        matches!(self, OutputTarget::Stdout)
+    }
+
+    /// Open the target, creating parent directories for files
+    pub fn create(&self) -> io::Result<Box<dyn Write>> {
+        match self {
+            OutputTarget::Stdout => Ok(Box::new(io::stdout().lock())),
+            OutputTarget::File(path) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                Ok(Box::new(io::BufWriter::new(fs::File::create(path)?)))
+            }
+        }
+    }
+
+    pub fn write(&self, bytes: &[u8]) -> io::Result<()> {
+        let mut out = self.create()?;
+        out.write_all(bytes)?;
+        out.flush()
+    }
+
+    /// Print a status line: to stderr when the result itself goes to stdout
+    pub fn announce(&self, message: impl fmt::Display) {
+        if self.is_stdout() {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+}
+
+impl fmt::Display for OutputTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputTarget::Stdout => f.write_str("<stdout>"),
+            OutputTarget::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension_and_content_detection() {
+        assert!(has_glyph_extension(Path::new("samples/test-tetragrams.supmys")));
+        assert!(has_glyph_extension(Path::new("HELLO.SM")));
+        assert!(!has_glyph_extension(Path::new("program")));
+
+        assert!(looks_like_glyph_source("// header comment\n𝌆𝌇 𝌈\n"));
+        assert!(looks_like_glyph_source("𝌆𝌇x"));
+        assert!(!looks_like_glyph_source("fn main() {} // 𝌆𝌇𝌈"));
+        assert!(!looks_like_glyph_source(""));
+    }
+
+    #[test]
+    fn test_output_targets() {
+        assert_eq!(OutputTarget::new(PathBuf::from("-")), OutputTarget::Stdout);
+        assert_eq!(OutputTarget::glyphs(PathBuf::from("-")), OutputTarget::Stdout);
+        assert_eq!(OutputTarget::glyphs(PathBuf::from("out.txt")), OutputTarget::File(PathBuf::from("out.sm")));
+        assert_eq!(OutputTarget::new(PathBuf::from("loop.dot")), OutputTarget::File(PathBuf::from("loop.dot")));
+        assert_eq!(OutputTarget::glyphs(PathBuf::from("a/b.supmys")), OutputTarget::File(PathBuf::from("a/b.supmys")));
+    }
+}
@@ -0,0 +1,259 @@
+//! Intermediate representation behind `tetragram convert`
+//!
+//! Every input format is read into an [`Ir`] value and every output format is
+//! written from one, so each pair of formats shares the same two code paths:
+//!
+//! | Format   | Reads as   | Written from                                        |
+//! |----------|------------|-----------------------------------------------------|
+//! | `sm`     | glyphs     | glyphs; bytes and bytecode via the chunked codec     |
+//! | `hex`    | bytes      | bytes / bytecode; glyphs decoded with the codec      |
+//! | `binary` | bytes      | bytes / bytecode; glyphs decoded with the codec      |
+//! | `number` | bytes (LE) | bytes / bytecode; glyphs decoded with the codec      |
+//! | `text`   | text       | text; glyphs via the balanced-ternary codec; UTF-8 bytes |
+//! | `tritvm` | bytecode   | bytecode / bytes; glyphs decoded with the codec      |
+//! | `asm`    | glyphs     | glyphs only (annotated listing)                      |
+//!
+//! The codec records the payload length, so bytes and bytecode keep trailing
+//! zero bytes through `sm`. A `number` has no length: payloads ending in a
+//! zero byte are rejected rather than written as a shorter number.
+
+use crate::tetragram_asm::{assemble, disassemble};
+use crate::tetragram_codec::{decode_balanced, decode_stream, encode_balanced, encode_stream};
+use crate::tetragram_vm::{parse_glyph_stream, value_to_glyph};
+use num_bigint::BigUint;
+use std::fmt;
+use std::str::FromStr;
+
+/// A `tetragram convert` format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Sm,
+    Hex,
+    Binary,
+    Asm,
+    Text,
+    Number,
+    Tritvm,
+}
+
+impl Format {
+    pub const ALL: [Format; 7] = [
+        Format::Sm,
+        Format::Hex,
+        Format::Binary,
+        Format::Asm,
+        Format::Text,
+        Format::Number,
+        Format::Tritvm,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Sm => "sm",
+            Format::Hex => "hex",
+            Format::Binary => "binary",
+            Format::Asm => "asm",
+            Format::Text => "text",
+            Format::Number => "number",
+            Format::Tritvm => "tritvm",
+        }
+    }
+
+    /// Whether `--input` names a file (as opposed to a literal value)
+    pub fn reads_file(&self) -> bool {
+        matches!(self, Format::Binary | Format::Tritvm)
+    }
+
+    /// Whether `--input` is a literal value rather than a path
+    pub fn reads_literal(&self) -> bool {
+        matches!(self, Format::Text | Format::Number)
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_lowercase();
+        Self::ALL.iter().copied().find(|f| f.name() == lower).ok_or_else(|| {
+            let names: Vec<&str> = Self::ALL.iter().map(|f| f.name()).collect();
+            format!("Unknown format '{}' (expected one of: {})", s, names.join(", "))
+        })
+    }
+}
+
+/// Format-neutral content of a conversion
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ir {
+    /// Glyph values 0-80 (programs or encoded payloads)
+    Glyphs(Vec<u8>),
+    /// An opaque byte payload
+    Bytes(Vec<u8>),
+    /// TrinaryVM bytecode, carried like [`Ir::Bytes`] but never read as text
+    Bytecode(Vec<u8>),
+    /// Plain text
+    Text(String),
+}
+
+impl Ir {
+    fn kind(&self) -> &'static str {
+        match self {
+            Ir::Glyphs(_) => "a glyph stream",
+            Ir::Bytes(_) => "a byte payload",
+            Ir::Bytecode(_) => "TrinaryVM bytecode",
+            Ir::Text(_) => "text",
+        }
+    }
+
+    /// Number of glyphs or bytes held
+    pub fn len(&self) -> usize {
+        match self {
+            Ir::Glyphs(v) | Ir::Bytes(v) | Ir::Bytecode(v) => v.len(),
+            Ir::Text(t) => t.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Read `input` in the given format
+pub fn read(format: Format, input: &[u8]) -> Result<Ir, String> {
+    let text = || std::str::from_utf8(input).map_err(|e| format!("{} input is not UTF-8: {}", format, e));
+    match format {
+        Format::Sm => parse_glyph_stream(text()?).map(Ir::Glyphs).map_err(|e| e.to_string()),
+        Format::Hex => {
+            let digits: String = text()?.split_whitespace().collect();
+            let digits = digits.strip_prefix("0x").unwrap_or(&digits);
+            hex::decode(digits).map(Ir::Bytes).map_err(|e| format!("Invalid hex input: {}", e))
+        }
+        Format::Binary => Ok(Ir::Bytes(input.to_vec())),
+        Format::Number => {
+            let number = text()?
+                .trim()
+                .parse::<BigUint>()
+                .map_err(|_| format!("Failed to parse number: {}", String::from_utf8_lossy(input)))?;
+            Ok(Ir::Bytes(number.to_bytes_le()))
+        }
+        Format::Text => Ok(Ir::Text(text()?.to_string())),
+        Format::Tritvm => Ok(Ir::Bytecode(input.to_vec())),
+        Format::Asm => assemble(text()?).map(Ir::Glyphs).map_err(|e| format!("Assembly error at {}", e)),
+    }
+}
+
+/// Write `ir` in the given format
+pub fn write(ir: &Ir, format: Format) -> Result<Vec<u8>, String> {
+    let impossible = || format!("Cannot convert {} to {}", ir.kind(), format);
+    match format {
+        Format::Sm => {
+            let glyphs: String = match ir {
+                Ir::Glyphs(values) => values.iter().map(|&v| value_to_glyph(v)).collect(),
+                Ir::Bytes(bytes) | Ir::Bytecode(bytes) => return encode_bytes(bytes),
+                Ir::Text(text) => encode_balanced(text.as_bytes()).into_iter().map(value_to_glyph).collect(),
+            };
+            Ok(glyphs.into_bytes())
+        }
+        Format::Hex => Ok(hex::encode(payload(ir)?).into_bytes()),
+        Format::Binary => payload(ir),
+        Format::Number => {
+            let bytes = payload(ir)?;
+            if bytes.len() > 1 && bytes.last() == Some(&0) {
+                return Err(format!("{}: the payload ends in zero bytes, which a number cannot keep (use hex)", impossible()));
+            }
+            Ok(BigUint::from_bytes_le(&bytes).to_str_radix(10).into_bytes())
+        }
+        Format::Text => match ir {
+            Ir::Text(text) => Ok(text.clone().into_bytes()),
+            Ir::Glyphs(values) => {
+                let bytes = decode_balanced(values).map_err(|e| format!("Glyph stream is not balanced-ternary text: {}", e))?;
+                String::from_utf8(bytes).map(String::into_bytes).map_err(|e| format!("Decoded text is not UTF-8: {}", e))
+            }
+            Ir::Bytes(bytes) => String::from_utf8(bytes.clone())
+                .map(String::into_bytes)
+                .map_err(|e| format!("Payload is not valid UTF-8 text: {}", e)),
+            Ir::Bytecode(_) => Err(impossible()),
+        },
+        Format::Tritvm => match ir {
+            Ir::Text(_) => Err(impossible()),
+            _ => payload(ir),
+        },
+        Format::Asm => match ir {
+            Ir::Glyphs(values) => Ok(disassemble(values).into_bytes()),
+            _ => Err(format!("{}; only glyph programs can be disassembled", impossible())),
+        },
+    }
+}
+
+fn encode_bytes(bytes: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    encode_stream(bytes, &mut out).map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+/// Raw bytes carried by `ir`; glyph streams must be codec frames
+fn payload(ir: &Ir) -> Result<Vec<u8>, String> {
+    match ir {
+        Ir::Bytes(bytes) | Ir::Bytecode(bytes) => Ok(bytes.clone()),
+        Ir::Text(text) => Ok(text.clone().into_bytes()),
+        Ir::Glyphs(values) => {
+            let glyphs: String = values.iter().map(|&v| value_to_glyph(v)).collect();
+            let mut bytes = Vec::new();
+            decode_stream(glyphs.as_bytes(), &mut bytes)
+                .map_err(|e| format!("Glyph stream is not an encoded byte payload: {}", e))?;
+            Ok(bytes)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(from: Format, to: Format, input: &[u8]) -> Result<Vec<u8>, String> {
+        write(&read(from, input)?, to)
+    }
+
+    #[test]
+    fn test_byte_formats_roundtrip_through_sm() {
+        let payload = b"\x00tetragram\x00\xff\x00";
+        for format in [Format::Hex, Format::Binary] {
+            let input = if format == Format::Hex { hex::encode(payload).into_bytes() } else { payload.to_vec() };
+            let sm = convert(format, Format::Sm, &input).unwrap();
+            assert_eq!(convert(Format::Sm, format, &sm).unwrap(), input, "{}", format);
+        }
+        let sm = convert(Format::Number, Format::Sm, b"729").unwrap();
+        assert_eq!(convert(Format::Sm, Format::Number, &sm).unwrap(), b"729");
+        assert_eq!(convert(Format::Hex, Format::Binary, b"0x00ff").unwrap(), vec![0, 255]);
+    }
+
+    #[test]
+    fn test_tritvm_roundtrip_keeps_trailing_zeros() {
+        let bytecode = b"TVM\x01\x0c\x00\x00";
+        let sm = convert(Format::Tritvm, Format::Sm, bytecode).unwrap();
+        assert_eq!(convert(Format::Sm, Format::Tritvm, &sm).unwrap(), bytecode);
+
+        assert!(convert(Format::Hex, Format::Number, b"0100").unwrap_err().contains("zero bytes"));
+        assert_eq!(convert(Format::Hex, Format::Number, b"0001").unwrap(), b"256");
+        assert_eq!(convert(Format::Number, Format::Hex, b"0").unwrap(), b"00");
+    }
+
+    #[test]
+    fn test_sm_to_asm_and_impossible_pairs() {
+        let sm: String = [12u8, 0, 80].iter().map(|&v| value_to_glyph(v)).collect();
+        let asm = String::from_utf8(convert(Format::Sm, Format::Asm, sm.as_bytes()).unwrap()).unwrap();
+        assert!(asm.contains("CONST4 R0, 80"));
+
+        assert!(convert(Format::Binary, Format::Asm, b"\x01").unwrap_err().contains("Cannot convert"));
+        assert!(convert(Format::Tritvm, Format::Text, b"\x01").is_err());
+        assert_eq!(convert(Format::Sm, Format::Sm, &convert(Format::Asm, Format::Sm, asm.as_bytes()).unwrap()).unwrap(), sm.as_bytes());
+        assert!(convert(Format::Asm, Format::Sm, b"NOP R1").unwrap_err().contains("1:1"));
+        assert!("wav".parse::<Format>().is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Measured execution performance for `tetragram analyze --measure N`
+//!
+//! The program runs N times from a fresh VM under the default gas limit. Wall
+//! time is the executor's own timing of `run` (VM construction excluded);
+//! operations and gas are the same every run, so only their rates vary.
+//! Each figure is reported with mean, variance, standard deviation and range
+//! over the runs.
+
+use crate::tetragram_vm::{TetragramMicroVM, DEFAULT_GAS_LIMIT};
+use serde::Serialize;
+
+/// Summary of one quantity over the measured runs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stat {
+    pub mean: f64,
+    /// Sample variance (zero for a single run)
+    pub variance: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Stat {
+    pub fn from_samples(samples: &[f64]) -> Self {
+        if samples.is_empty() {
+            return Stat { mean: 0.0, variance: 0.0, std_dev: 0.0, min: 0.0, max: 0.0 };
+        }
+        let n = samples.len() as f64;
+        let mean = samples.iter().sum::<f64>() / n;
+        let variance = if samples.len() > 1 {
+            samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)
+        } else {
+            0.0
+        };
+        Stat {
+            mean,
+            variance,
+            std_dev: variance.sqrt(),
+            min: samples.iter().copied().fold(f64::INFINITY, f64::min),
+            max: samples.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+}
+
+/// Executor timings over repeated runs
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Measurement {
+    pub runs: usize,
+    pub gas_limit: u64,
+    /// Instructions executed per run
+    pub operations: u64,
+    /// Gas consumed per run
+    pub gas_consumed: u64,
+    /// Fault that ended every run, if any
+    pub fault: Option<String>,
+    pub wall_time_us: Stat,
+    pub ops_per_second: Stat,
+    pub gas_per_us: Stat,
+}
+
+/// Per-run rate, zero when the timer could not resolve the run
+fn rate(amount: f64, elapsed: f64) -> f64 {
+    if elapsed > 0.0 { amount / elapsed } else { 0.0 }
+}
+
+/// Run a decoded program `runs` times and summarize the timings
+pub fn measure(program: &[u8], runs: usize) -> Measurement {
+    let mut wall = Vec::with_capacity(runs);
+    let mut ops = Vec::with_capacity(runs);
+    let mut gas = Vec::with_capacity(runs);
+    let mut last = None;
+    for _ in 0..runs {
+        let result = TetragramMicroVM::new(program.to_vec()).with_gas_limit(DEFAULT_GAS_LIMIT).run();
+        let micros = result.execution_time.as_secs_f64() * 1_000_000.0;
+        wall.push(micros);
+        ops.push(rate(result.operations_executed as f64 * 1_000_000.0, micros));
+        gas.push(rate(result.gas_consumed as f64, micros));
+        last = Some(result);
+    }
+
+    Measurement {
+        runs,
+        gas_limit: DEFAULT_GAS_LIMIT,
+        operations: last.as_ref().map_or(0, |r| r.operations_executed),
+        gas_consumed: last.as_ref().map_or(0, |r| r.gas_consumed),
+        fault: last.and_then(|r| r.fault).map(|f| f.to_string()),
+        wall_time_us: Stat::from_samples(&wall),
+        ops_per_second: Stat::from_samples(&ops),
+        gas_per_us: Stat::from_samples(&gas),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetragram_asm::assemble;
+
+    #[test]
+    fn test_stat_summary() {
+        let stat = Stat::from_samples(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert_eq!((stat.mean, stat.min, stat.max), (5.0, 2.0, 9.0));
+        assert!((stat.variance - 32.0 / 7.0).abs() < 1e-12);
+        assert_eq!(Stat::from_samples(&[3.0]).variance, 0.0);
+        assert_eq!(Stat::from_samples(&[]).mean, 0.0);
+    }
+
+    #[test]
+    fn test_measure_counts_are_exact() {
+        let program = assemble("CONST4 R0, 41\nPUSHOUT R0\nNOP").unwrap();
+        let measured = measure(&program, 3);
+        assert_eq!((measured.runs, measured.operations, measured.gas_consumed), (3, 3, 7));
+        assert!(measured.fault.is_none());
+        assert!(measured.wall_time_us.mean.is_finite() && measured.ops_per_second.mean.is_finite());
+
+        let empty = measure(&[], 2);
+        assert_eq!(empty.operations, 0);
+        assert_eq!(empty.ops_per_second.mean, 0.0);
+    }
+}
@@ -0,0 +1,274 @@
+//! Memory-access analysis of micro-ISA programs
+//!
+//! Only LD4 and ST4 touch memory, and both take the address as an immediate
+//! glyph operand, so the set of nibbles a program can access is known
+//! statically. Over the reachable instructions of the control-flow graph this
+//! reports which of the 81 nibbles are read and written, LD4s that can run
+//! before any ST4 to their address (memory starts at zero, reported as
+//! `A005`), cells that are stored but never loaded, and a 9x9 heat map of
+//! static access counts.
+//!
+//! The smallest `--memory-limit` that runs the program without an
+//! out-of-memory fault is one past the highest address accessed;
+//! `tetragram execute --memory-limit auto` applies it directly.
+
+use crate::tetragram_cfg::{BasicBlock, Cfg, Successor};
+use crate::tetragram_validate::{Diagnostic, ValidationReport};
+use crate::tetragram_vm::{parse_memory_limit, Instruction, Opcode, MEMORY_SIZE};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Side of the square heat map (9 x 9 = 81 nibbles)
+pub const HEAT_MAP_WIDTH: usize = 9;
+
+/// Static LD4/ST4 counts for one nibble
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CellAccess {
+    pub address: u8,
+    pub reads: usize,
+    pub writes: usize,
+}
+
+/// An LD4 that some path reaches before any ST4 to its address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MemoryRead {
+    pub pc: usize,
+    pub address: u8,
+}
+
+/// Which nibbles the reachable code reads and writes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MemoryMap {
+    /// Every accessed nibble, by address
+    pub cells: Vec<CellAccess>,
+    pub uninitialized_reads: Vec<MemoryRead>,
+    /// Stored but never loaded
+    pub write_only: Vec<u8>,
+    /// Loaded but never stored (always reads zero)
+    pub read_only: Vec<u8>,
+    /// Accesses per nibble, `heat_map[address / 9][address % 9]`
+    pub heat_map: Vec<[usize; HEAT_MAP_WIDTH]>,
+    /// Smallest `--memory-limit` that never faults on an access
+    pub recommended_memory_limit: usize,
+}
+
+/// Address accessed by a memory instruction, if any
+fn accessed_address(instr: &Instruction) -> Option<(u8, bool)> {
+    match instr.opcode {
+        Opcode::Ld4 => Some((instr.operands[1], false)),
+        Opcode::St4 => Some((instr.operands[0], true)),
+        _ => None,
+    }
+}
+
+/// LD4 reads that some path reaches without a prior ST4 to the same nibble
+fn uninitialized_reads(cfg: &Cfg) -> Vec<MemoryRead> {
+    const ALL: u128 = (1 << MEMORY_SIZE) - 1;
+    let stores = |block: &BasicBlock, mut written: u128| {
+        for (address, write) in block.instructions.iter().filter_map(accessed_address) {
+            if write {
+                written |= 1 << address;
+            }
+        }
+        written
+    };
+    let preds = cfg.predecessors();
+    // Must-stored nibbles at block exit, starting from "everything" (top)
+    let mut out: BTreeMap<usize, u128> = cfg.blocks.keys().map(|&pc| (pc, ALL)).collect();
+    let block_in = |start: usize, out: &BTreeMap<usize, u128>| {
+        let from_preds = preds[&start].iter().fold(ALL, |acc, p| acc & out[p]);
+        if cfg.entry == Successor::Block(start) { 0 } else { from_preds }
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (&start, block) in &cfg.blocks {
+            let written = stores(block, block_in(start, &out));
+            if out[&start] != written {
+                out.insert(start, written);
+                changed = true;
+            }
+        }
+    }
+
+    let mut reads = Vec::new();
+    for (&start, block) in &cfg.blocks {
+        let mut written = block_in(start, &out);
+        for instr in &block.instructions {
+            match accessed_address(instr) {
+                Some((address, true)) => written |= 1 << address,
+                Some((address, false)) if written & (1 << address) == 0 => {
+                    reads.push(MemoryRead { pc: instr.pc, address });
+                }
+                _ => {}
+            }
+        }
+    }
+    reads
+}
+
+impl MemoryMap {
+    /// Analyze the reachable LD4/ST4 instructions of a graph
+    pub fn build(cfg: &Cfg) -> Self {
+        let mut counts: BTreeMap<u8, (usize, usize)> = BTreeMap::new();
+        for instr in cfg.blocks.values().flat_map(|b| &b.instructions) {
+            if let Some((address, write)) = accessed_address(instr) {
+                let entry = counts.entry(address).or_default();
+                if write { entry.1 += 1 } else { entry.0 += 1 }
+            }
+        }
+        let cells: Vec<CellAccess> = counts
+            .into_iter()
+            .map(|(address, (reads, writes))| CellAccess { address, reads, writes })
+            .collect();
+
+        let mut heat_map = vec![[0; HEAT_MAP_WIDTH]; MEMORY_SIZE / HEAT_MAP_WIDTH];
+        for cell in &cells {
+            let address = cell.address as usize;
+            heat_map[address / HEAT_MAP_WIDTH][address % HEAT_MAP_WIDTH] = cell.reads + cell.writes;
+        }
+
+        MemoryMap {
+            write_only: cells.iter().filter(|c| c.reads == 0).map(|c| c.address).collect(),
+            read_only: cells.iter().filter(|c| c.writes == 0).map(|c| c.address).collect(),
+            recommended_memory_limit: cells.last().map_or(0, |c| c.address as usize + 1),
+            uninitialized_reads: uninitialized_reads(cfg),
+            heat_map,
+            cells,
+        }
+    }
+
+    /// Nibbles that are both stored and loaded
+    pub fn live_cells(&self) -> usize {
+        self.cells.iter().filter(|c| c.reads > 0 && c.writes > 0).count()
+    }
+
+    /// Heat map rows, one character per nibble: `·` untouched, the access
+    /// count up to 9, `+` beyond
+    pub fn heat_map_rows(&self) -> Vec<String> {
+        self.heat_map
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&n| match n {
+                        0 => '·',
+                        1..=9 => char::from_digit(n as u32, 10).expect("single digit"),
+                        _ => '+',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Uninitialized reads as `A005` diagnostics positioned in the validated source
+    pub fn diagnostics(&self, source: &ValidationReport) -> Vec<Diagnostic> {
+        self.uninitialized_reads
+            .iter()
+            .map(|read| {
+                let message = format!("LD4 reads [{}] before it is stored on some path (memory starts at zero)", read.address);
+                source.diagnostic("A005", read.pc, message)
+            })
+            .collect()
+    }
+}
+
+/// `--memory-limit` of `tetragram execute`, `debug` and `validate`: a nibble count or `auto`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemoryLimit {
+    Nibbles(usize),
+    /// The program's [`MemoryMap::recommended_memory_limit`]
+    Auto,
+}
+
+impl MemoryLimit {
+    /// Concrete limit for a decoded program
+    pub fn resolve(self, program: &[u8]) -> usize {
+        match self {
+            MemoryLimit::Nibbles(n) => n,
+            MemoryLimit::Auto => MemoryMap::build(&Cfg::build(program)).recommended_memory_limit,
+        }
+    }
+}
+
+impl FromStr for MemoryLimit {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(MemoryLimit::Auto);
+        }
+        parse_memory_limit(s).map(MemoryLimit::Nibbles)
+    }
+}
+
+impl fmt::Display for MemoryLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryLimit::Nibbles(n) => write!(f, "{}", n),
+            MemoryLimit::Auto => f.write_str("auto"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetragram_asm::assemble;
+    use crate::tetragram_vm::TetragramMicroVM;
+
+    fn memory_map(source: &str) -> MemoryMap {
+        MemoryMap::build(&Cfg::build(&assemble(source).expect("test program assembles")))
+    }
+
+    #[test]
+    fn test_cells_and_findings() {
+        let map = memory_map("
+            CONST4 R0, 41
+            ST4 [10], R0
+            ST4 [30], R0     ; never loaded
+            LD4 R1, [10]
+            LD4 R2, [12]     ; never stored
+            LD4 R3, [10]
+        ");
+        let cells: Vec<(u8, usize, usize)> = map.cells.iter().map(|c| (c.address, c.reads, c.writes)).collect();
+        assert_eq!(cells, vec![(10, 2, 1), (12, 1, 0), (30, 0, 1)]);
+        assert_eq!(map.write_only, vec![30]);
+        assert_eq!(map.read_only, vec![12]);
+        assert_eq!(map.live_cells(), 1);
+        assert_eq!(map.uninitialized_reads, vec![MemoryRead { pc: 12, address: 12 }]);
+        assert_eq!(map.recommended_memory_limit, 31);
+        assert_eq!(map.heat_map[1][1], 3);
+        assert_eq!(map.heat_map_rows()[1], "·3·1·····");
+        assert_eq!(map.heat_map_rows()[3], "···1·····");
+    }
+
+    #[test]
+    fn test_store_on_one_branch_only() {
+        let map = memory_map("
+            CMP4 R0, R1
+            BR4 EQ, load
+            ST4 [5], R0
+        load:
+            LD4 R1, [5]
+        ");
+        assert_eq!(map.uninitialized_reads, vec![MemoryRead { pc: 9, address: 5 }]);
+    }
+
+    #[test]
+    fn test_auto_limit_runs_without_fault() {
+        let program = assemble("CONST4 R0, 80\nST4 [26], R0\nLD4 R1, [26]").unwrap();
+        assert_eq!("auto".parse(), Ok(MemoryLimit::Auto));
+        assert_eq!("27".parse(), Ok(MemoryLimit::Nibbles(27)));
+        assert!("lots".parse::<MemoryLimit>().is_err());
+
+        let limit = MemoryLimit::Auto.resolve(&program);
+        assert_eq!(limit, 27);
+        let result = TetragramMicroVM::new(program.clone()).with_memory_limit(limit).run();
+        assert!(result.fault.is_none());
+        let result = TetragramMicroVM::new(program).with_memory_limit(limit - 1).run();
+        assert!(result.fault.is_some());
+    }
+}
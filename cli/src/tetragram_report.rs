@@ -0,0 +1,112 @@
+//! Machine-readable output for `tetragram validate` and `tetragram analyze`
+//!
+//! Both commands accept `--format text|json|sarif`:
+//!
+//! * **json** - one object tagged with `schema` (`tetragram-validate` or
+//!   `tetragram-analysis`) and `version`. Fields are only ever added within a
+//!   version; renames or removals bump [`REPORT_VERSION`].
+//! * **sarif** - a SARIF 2.1.0 log with one run. Every diagnostic becomes a
+//!   result whose `ruleId` is the diagnostic code (`E001`, `W002`, ...) with a
+//!   region at the source line:column; the glyph index is kept in the result's
+//!   `properties`. The command's JSON report is attached as the run's
+//!   `properties` so nothing is lost relative to `--format json`.
+
+use crate::tetragram_validate::{Diagnostic, Severity, RULES};
+use serde_json::{json, Value};
+use std::path::Path;
+
+/// Version of the JSON report schema
+pub const REPORT_VERSION: u32 = 1;
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Output format of validate and analyze
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Text,
+    Json,
+    Sarif,
+}
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+    }
+}
+
+/// Wrap diagnostics for `file` in a SARIF 2.1.0 log
+pub fn sarif_log(file: &Path, diagnostics: &[Diagnostic], properties: Value) -> Value {
+    let uri = file.to_string_lossy().replace('\\', "/");
+    let rules: Vec<Value> = RULES
+        .iter()
+        .map(|(code, severity, description)| {
+            json!({
+                "id": code,
+                "shortDescription": { "text": description },
+                "defaultConfiguration": { "level": sarif_level(*severity) },
+            })
+        })
+        .collect();
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|d| {
+            json!({
+                "ruleId": d.code,
+                "level": sarif_level(d.severity),
+                "message": { "text": d.message },
+                "locations": [{
+                    "physicalLocation": {
+                        "artifactLocation": { "uri": uri },
+                        "region": { "startLine": d.line, "startColumn": d.column },
+                    }
+                }],
+                "properties": { "glyphIndex": d.glyph_index },
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "trinaryvm-cli",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "artifacts": [{ "location": { "uri": uri } }],
+            "results": results,
+            "properties": properties,
+        }]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetragram_validate::validate_source;
+    use crate::tetragram_vm::{value_to_glyph, MEMORY_SIZE};
+
+    #[test]
+    fn test_sarif_results_point_at_source() {
+        // LD4 R9, [3] on the second line
+        let source: String = [1u8, 9, 3].iter().map(|&v| value_to_glyph(v)).collect();
+        let report = validate_source(&format!("\n {}", source), MEMORY_SIZE);
+        let log = sarif_log(Path::new("dir\\bad.sm"), &report.diagnostics, json!({ "valid": false }));
+
+        let run = &log["runs"][0];
+        assert_eq!(log["version"], "2.1.0");
+        assert_eq!(run["tool"]["driver"]["rules"].as_array().unwrap().len(), RULES.len());
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "E004");
+        assert_eq!(result["level"], "error");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "dir/bad.sm");
+        assert_eq!(location["region"]["startLine"], 2);
+        assert_eq!(location["region"]["startColumn"], 3);
+        assert_eq!(result["properties"]["glyphIndex"], 1);
+        assert_eq!(run["properties"]["valid"], false);
+    }
+}
@@ -0,0 +1,246 @@
+//! Terminal screen renderer for TetragramMicroVM output
+//!
+//! PUSHOUT values are laid out row-major on an 81x27 grid; every 2187 values
+//! form one frame. Frames can be animated in the terminal or dumped to text
+//! files for headless use.
+
+use crate::tetragram_vm::{TetragramMicroVM, VmError, GLYPH_COUNT};
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+    execute, queue,
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Grid width in cells
+pub const SCREEN_WIDTH: usize = 81;
+/// Grid height in cells
+pub const SCREEN_HEIGHT: usize = 27;
+/// PUSHOUT values per frame
+pub const FRAME_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
+
+/// Brightness ramp from glyph value 0 (dark) to 80 (bright)
+const SHADES: &[u8] = b" .:-=+*#%@";
+
+/// One 81x27 frame of output values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub cells: Vec<u8>,
+}
+
+impl Frame {
+    /// Render the frame as 27 lines of 81 shade characters
+    pub fn render_text(&self) -> String {
+        let mut out = String::with_capacity(FRAME_SIZE + SCREEN_HEIGHT);
+        for row in 0..SCREEN_HEIGHT {
+            for col in 0..SCREEN_WIDTH {
+                let value = self.cells.get(row * SCREEN_WIDTH + col).copied().unwrap_or(0);
+                out.push(shade(value));
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+fn shade(value: u8) -> char {
+    let idx = value.min(GLYPH_COUNT - 1) as usize * SHADES.len() / GLYPH_COUNT as usize;
+    SHADES[idx] as char
+}
+
+/// Produces frames by stepping a VM until enough output has accumulated
+pub struct FrameSource {
+    vm: TetragramMicroVM,
+    consumed: usize,
+    fault: Option<VmError>,
+}
+
+impl FrameSource {
+    pub fn new(vm: TetragramMicroVM) -> Self {
+        Self { vm, consumed: 0, fault: None }
+    }
+
+    pub fn vm(&self) -> &TetragramMicroVM {
+        &self.vm
+    }
+
+    /// Fault that ended execution, if any
+    pub fn fault(&self) -> Option<&VmError> {
+        self.fault.as_ref()
+    }
+
+    /// Next complete frame; a trailing partial frame is returned once the
+    /// program stops, padded with dark cells
+    pub fn next_frame(&mut self) -> Option<Frame> {
+        while self.fault.is_none() && self.vm.output().len() < self.consumed + FRAME_SIZE {
+            match self.vm.step() {
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(e) => self.fault = Some(e),
+            }
+        }
+        let output = self.vm.output();
+        if output.len() <= self.consumed {
+            return None;
+        }
+        let end = output.len().min(self.consumed + FRAME_SIZE);
+        let cells = output[self.consumed..end].to_vec();
+        self.consumed = end;
+        Some(Frame { cells })
+    }
+}
+
+/// Write up to `max_frames` frames as `frame_NNNN.txt` into `dir`
+pub fn dump_frames(source: &mut FrameSource, max_frames: Option<usize>, dir: &Path) -> io::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+    let mut written = Vec::new();
+    while max_frames.is_none_or(|max| written.len() < max) {
+        let frame = match source.next_frame() {
+            Some(frame) => frame,
+            None => break,
+        };
+        let path = dir.join(format!("frame_{:04}.txt", written.len()));
+        std::fs::write(&path, frame.render_text())?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Animate frames in the terminal: space/p pauses, n/→ steps, q/Esc quits
+pub fn run_interactive(source: &mut FrameSource, fps: u32, max_frames: Option<usize>) -> io::Result<usize> {
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(stdout, EnterAlternateScreen, Hide)?;
+
+    let result = animate(source, fps, max_frames, &mut stdout);
+
+    execute!(stdout, Show, LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
+
+fn animate(source: &mut FrameSource, fps: u32, max_frames: Option<usize>, out: &mut impl Write) -> io::Result<usize> {
+    let interval = Duration::from_millis(1000 / fps.max(1) as u64);
+    let mut shown = 0usize;
+    let mut paused = false;
+    let mut finished = false;
+    let mut advance = true;
+
+    loop {
+        if advance && !finished {
+            match source.next_frame() {
+                Some(frame) if max_frames.is_none_or(|max| shown < max) => {
+                    shown += 1;
+                    draw(out, &frame, shown, paused, false, source)?;
+                }
+                _ => {
+                    finished = true;
+                    draw_status(out, shown, paused, true, source)?;
+                }
+            }
+        }
+
+        let deadline = Instant::now() + interval;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if !event::poll(timeout)? {
+                advance = !paused;
+                break;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(shown),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(shown),
+                    KeyCode::Char(' ') | KeyCode::Char('p') => {
+                        paused = !paused;
+                        draw_status(out, shown, paused, finished, source)?;
+                    }
+                    KeyCode::Char('n') | KeyCode::Right => {
+                        paused = true;
+                        advance = true;
+                        break;
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn draw(out: &mut impl Write, frame: &Frame, index: usize, paused: bool, finished: bool, source: &FrameSource) -> io::Result<()> {
+    queue!(out, MoveTo(0, 0), Clear(ClearType::All))?;
+    for (row, line) in frame.render_text().lines().enumerate() {
+        queue!(out, MoveTo(0, row as u16))?;
+        write!(out, "{}", line)?;
+    }
+    draw_status(out, index, paused, finished, source)
+}
+
+fn draw_status(out: &mut impl Write, index: usize, paused: bool, finished: bool, source: &FrameSource) -> io::Result<()> {
+    let state = if finished {
+        "end of output"
+    } else if paused {
+        "paused"
+    } else {
+        "playing"
+    };
+    queue!(out, MoveTo(0, SCREEN_HEIGHT as u16 + 1), Clear(ClearType::CurrentLine))?;
+    write!(
+        out,
+        "frame {} | {} | gas {} | [space] pause  [n] step  [q] quit",
+        index,
+        state,
+        source.vm().gas_consumed()
+    )?;
+    if let Some(fault) = source.fault() {
+        queue!(out, MoveTo(0, SCREEN_HEIGHT as u16 + 2), Clear(ClearType::CurrentLine))?;
+        write!(out, "fault: {}", fault)?;
+    }
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // CONST4 R0, v; then PUSHOUT R0 `count` times
+    fn pushout_program(value: u8, count: usize) -> TetragramMicroVM {
+        let mut program = vec![12, 0, value];
+        for _ in 0..count {
+            program.extend([11, 0]);
+        }
+        TetragramMicroVM::new(program).with_gas_limit(u64::MAX)
+    }
+
+    #[test]
+    fn test_frames_split_on_frame_size() {
+        let mut source = FrameSource::new(pushout_program(80, FRAME_SIZE + 5));
+        let first = source.next_frame().unwrap();
+        assert_eq!(first.cells.len(), FRAME_SIZE);
+        let text = first.render_text();
+        assert_eq!(text.lines().count(), SCREEN_HEIGHT);
+        assert!(text.lines().all(|l| l == "@".repeat(SCREEN_WIDTH)));
+
+        let partial = source.next_frame().unwrap();
+        assert_eq!(partial.cells.len(), 5);
+        assert!(partial.render_text().starts_with("@@@@@ "));
+        assert!(source.next_frame().is_none());
+    }
+
+    #[test]
+    fn test_dump_frames() {
+        let dir = std::env::temp_dir().join(format!("tetragram_screen_{}", std::process::id()));
+        let mut source = FrameSource::new(pushout_program(0, FRAME_SIZE * 3));
+        let written = dump_frames(&mut source, Some(2), &dir).unwrap();
+        assert_eq!(written.len(), 2);
+        let contents = std::fs::read_to_string(&written[1]).unwrap();
+        assert_eq!(contents.len(), FRAME_SIZE + SCREEN_HEIGHT);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
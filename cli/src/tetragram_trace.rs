@@ -0,0 +1,599 @@
+//! Versioned execution trace format for the TetragramMicroVM
+//!
+//! A trace is a header, one record per executed instruction and an end record.
+//! Two encodings are supported:
+//!
+//! * **JSON Lines** - one object per line, tagged by `"type"`:
+//!   `header` (format, version, glyph stream, limits), `step` (pc, glyph,
+//!   opcode, operands, register/memory/status deltas, output, gas before and
+//!   after) and `end` (final pc, gas consumed, fault message).
+//! * **Binary** - magic `TGTR`, a version byte, then the same records with
+//!   LEB128 varints for pc and gas and one byte per nibble value.
+//!
+//! `replay` re-executes a program against a trace and reports the first
+//! record where they disagree.
+
+use crate::tetragram_vm::{
+    glyph_to_value, nibble_to_value, value_to_glyph, value_to_nibble, ExecutionResult, Nibble, Opcode, TetragramMicroVM, VmError, MEMORY_SIZE,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::time::Instant;
+
+/// Format identifier stored in every header
+pub const TRACE_FORMAT: &str = "tetragram-trace";
+/// Current trace format version
+pub const TRACE_VERSION: u32 = 1;
+/// Magic prefix of the binary encoding
+const BINARY_MAGIC: &[u8; 4] = b"TGTR";
+const TAG_STEP: u8 = 0x01;
+const TAG_END: u8 = 0xFF;
+
+/// Trace encoding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TraceFormat {
+    Jsonl,
+    Binary,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceHeader {
+    pub format: String,
+    pub version: u32,
+    pub glyph_stream: String,
+    pub gas_limit: u64,
+    pub memory_limit: usize,
+}
+
+/// A register or memory cell that changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellDelta {
+    pub index: usize,
+    pub before: Nibble,
+    pub after: Nibble,
+}
+
+/// A status register change
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NibbleChange {
+    pub before: Nibble,
+    pub after: Nibble,
+}
+
+/// One executed instruction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceRecord {
+    pub pc: usize,
+    pub glyph: char,
+    pub opcode: Opcode,
+    pub operands: Vec<u8>,
+    pub registers: Vec<CellDelta>,
+    pub memory: Vec<CellDelta>,
+    pub status: Option<NibbleChange>,
+    pub output: Option<u8>,
+    pub gas_before: u64,
+    pub gas_after: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceEnd {
+    pub final_pc: usize,
+    pub gas_consumed: u64,
+    pub fault: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TraceLine {
+    Header(TraceHeader),
+    Step(TraceRecord),
+    End(TraceEnd),
+}
+
+/// A complete execution trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Trace {
+    pub header: TraceHeader,
+    pub records: Vec<TraceRecord>,
+    pub end: Option<TraceEnd>,
+}
+
+/// Execute one instruction and describe its effects; `None` once halted
+pub fn record_step(vm: &mut TetragramMicroVM) -> Result<Option<TraceRecord>, VmError> {
+    let registers = *vm.registers();
+    let memory = vm.memory().to_vec();
+    let status = vm.status();
+    let gas_before = vm.gas_consumed();
+    let output_len = vm.output().len();
+
+    let instr = match vm.step()? {
+        Some(instr) => instr,
+        None => return Ok(None),
+    };
+
+    let diff = |before: &[Nibble], after: &[Nibble]| -> Vec<CellDelta> {
+        before
+            .iter()
+            .zip(after)
+            .enumerate()
+            .filter(|(_, (b, a))| b != a)
+            .map(|(index, (b, a))| CellDelta { index, before: *b, after: *a })
+            .collect()
+    };
+
+    Ok(Some(TraceRecord {
+        pc: instr.pc,
+        glyph: value_to_glyph(instr.opcode.value()),
+        opcode: instr.opcode,
+        operands: instr.operands,
+        registers: diff(&registers, vm.registers()),
+        memory: diff(&memory, vm.memory()),
+        status: (status != vm.status()).then(|| NibbleChange { before: status, after: vm.status() }),
+        output: vm.output().get(output_len).copied(),
+        gas_before,
+        gas_after: vm.gas_consumed(),
+    }))
+}
+
+/// Run a VM to completion while recording a trace
+pub fn record_run(vm: &mut TetragramMicroVM) -> (ExecutionResult, Trace) {
+    let start = Instant::now();
+    let header = header_for(vm);
+    let mut records = Vec::new();
+    let fault = loop {
+        match record_step(vm) {
+            Ok(Some(record)) => records.push(record),
+            Ok(None) => break None,
+            Err(e) => break Some(e),
+        }
+    };
+    let end = TraceEnd {
+        final_pc: vm.pc(),
+        gas_consumed: vm.gas_consumed(),
+        fault: fault.as_ref().map(|e| e.to_string()),
+    };
+    let result = vm.result(start.elapsed(), fault);
+    (result, Trace { header, records, end: Some(end) })
+}
+
+fn header_for(vm: &TetragramMicroVM) -> TraceHeader {
+    TraceHeader {
+        format: TRACE_FORMAT.to_string(),
+        version: TRACE_VERSION,
+        glyph_stream: vm.program().iter().map(|&v| value_to_glyph(v)).collect(),
+        gas_limit: vm.gas_limit(),
+        memory_limit: vm.memory_limit(),
+    }
+}
+
+impl Trace {
+    /// Serialize in the requested encoding
+    pub fn write_to(&self, out: &mut impl Write, format: TraceFormat) -> io::Result<()> {
+        match format {
+            TraceFormat::Jsonl => self.write_jsonl(out),
+            TraceFormat::Binary => self.write_binary(out),
+        }
+    }
+
+    fn write_jsonl(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut line = |value: &TraceLine| -> io::Result<()> {
+            serde_json::to_writer(&mut *out, value)?;
+            out.write_all(b"\n")
+        };
+        line(&TraceLine::Header(self.header.clone()))?;
+        for record in &self.records {
+            line(&TraceLine::Step(record.clone()))?;
+        }
+        if let Some(end) = &self.end {
+            line(&TraceLine::End(end.clone()))?;
+        }
+        Ok(())
+    }
+
+    fn write_binary(&self, out: &mut impl Write) -> io::Result<()> {
+        out.write_all(BINARY_MAGIC)?;
+        out.write_all(&[TRACE_VERSION as u8])?;
+        write_varint(out, self.header.gas_limit)?;
+        write_varint(out, self.header.memory_limit as u64)?;
+        let program: Vec<u8> = self
+            .header
+            .glyph_stream
+            .chars()
+            .map(|ch| glyph_to_value(ch).unwrap_or(0))
+            .collect();
+        write_varint(out, program.len() as u64)?;
+        out.write_all(&program)?;
+
+        for record in &self.records {
+            out.write_all(&[TAG_STEP])?;
+            write_varint(out, record.pc as u64)?;
+            out.write_all(&[record.opcode.value()])?;
+            out.write_all(&record.operands)?;
+            for cells in [&record.registers, &record.memory] {
+                out.write_all(&[cells.len() as u8])?;
+                for cell in cells {
+                    out.write_all(&[cell.index as u8, nibble_to_value(&cell.before), nibble_to_value(&cell.after)])?;
+                }
+            }
+            match &record.status {
+                Some(change) => out.write_all(&[1, nibble_to_value(&change.before), nibble_to_value(&change.after)])?,
+                None => out.write_all(&[0])?,
+            }
+            match record.output {
+                Some(value) => out.write_all(&[1, value])?,
+                None => out.write_all(&[0])?,
+            }
+            write_varint(out, record.gas_before)?;
+            write_varint(out, record.gas_after)?;
+        }
+
+        if let Some(end) = &self.end {
+            out.write_all(&[TAG_END])?;
+            write_varint(out, end.final_pc as u64)?;
+            write_varint(out, end.gas_consumed)?;
+            let fault = end.fault.as_deref().unwrap_or("");
+            write_varint(out, fault.len() as u64)?;
+            out.write_all(fault.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Parse either encoding, detected from the leading bytes
+    pub fn read_from(data: &[u8]) -> io::Result<Self> {
+        if data.starts_with(BINARY_MAGIC) {
+            Self::read_binary(&data[BINARY_MAGIC.len()..])
+        } else {
+            Self::read_jsonl(data)
+        }
+    }
+
+    fn read_jsonl(data: &[u8]) -> io::Result<Self> {
+        let mut header = None;
+        let mut records = Vec::new();
+        let mut end = None;
+        for (n, line) in data.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let parsed: TraceLine = serde_json::from_str(&line)
+                .map_err(|e| invalid(format!("line {}: {}", n + 1, e)))?;
+            match parsed {
+                TraceLine::Header(h) if header.is_none() => header = Some(h),
+                TraceLine::Header(_) => return Err(invalid(format!("line {}: duplicate header", n + 1))),
+                TraceLine::Step(r) => records.push(r),
+                TraceLine::End(e) => end = Some(e),
+            }
+        }
+        let header = header.ok_or_else(|| invalid("missing trace header".to_string()))?;
+        check_header(&header)?;
+        Ok(Trace { header, records, end })
+    }
+
+    fn read_binary(mut data: &[u8]) -> io::Result<Self> {
+        let r = &mut data;
+        let version = read_u8(r)? as u32;
+        let gas_limit = read_varint(r)?;
+        let memory_limit = read_varint(r)? as usize;
+        let program_len = read_varint(r)? as usize;
+        let program = read_bytes(r, program_len)?;
+        let header = TraceHeader {
+            format: TRACE_FORMAT.to_string(),
+            version,
+            glyph_stream: program.iter().map(|&v| value_to_glyph(v)).collect(),
+            gas_limit,
+            memory_limit,
+        };
+        check_header(&header)?;
+
+        let mut records = Vec::new();
+        let mut end = None;
+        while !r.is_empty() {
+            match read_u8(r)? {
+                TAG_STEP => {
+                    let pc = read_varint(r)? as usize;
+                    let value = read_u8(r)?;
+                    let opcode = Opcode::from_value(value)
+                        .ok_or_else(|| invalid(format!("invalid opcode {} in record {}", value, records.len())))?;
+                    let operands = read_bytes(r, opcode.operand_count())?;
+                    let mut cells = [Vec::new(), Vec::new()];
+                    for list in cells.iter_mut() {
+                        for _ in 0..read_u8(r)? {
+                            let b = read_bytes(r, 3)?;
+                            list.push(CellDelta {
+                                index: b[0] as usize,
+                                before: value_to_nibble(b[1]),
+                                after: value_to_nibble(b[2]),
+                            });
+                        }
+                    }
+                    let [registers, memory] = cells;
+                    let status = match read_u8(r)? {
+                        0 => None,
+                        _ => {
+                            let b = read_bytes(r, 2)?;
+                            Some(NibbleChange { before: value_to_nibble(b[0]), after: value_to_nibble(b[1]) })
+                        }
+                    };
+                    let output = match read_u8(r)? {
+                        0 => None,
+                        _ => Some(read_u8(r)?),
+                    };
+                    records.push(TraceRecord {
+                        pc,
+                        glyph: value_to_glyph(value),
+                        opcode,
+                        operands,
+                        registers,
+                        memory,
+                        status,
+                        output,
+                        gas_before: read_varint(r)?,
+                        gas_after: read_varint(r)?,
+                    });
+                }
+                TAG_END => {
+                    let final_pc = read_varint(r)? as usize;
+                    let gas_consumed = read_varint(r)?;
+                    let fault_len = read_varint(r)? as usize;
+                    let fault = read_bytes(r, fault_len)?;
+                    let fault = String::from_utf8(fault).map_err(|e| invalid(e.to_string()))?;
+                    end = Some(TraceEnd {
+                        final_pc,
+                        gas_consumed,
+                        fault: if fault.is_empty() { None } else { Some(fault) },
+                    });
+                }
+                tag => return Err(invalid(format!("unknown record tag 0x{:02X}", tag))),
+            }
+        }
+        Ok(Trace { header, records, end })
+    }
+}
+
+fn check_header(header: &TraceHeader) -> io::Result<()> {
+    if header.format != TRACE_FORMAT {
+        return Err(invalid(format!("unknown trace format '{}'", header.format)));
+    }
+    if header.version != TRACE_VERSION {
+        return Err(invalid(format!(
+            "unsupported trace version {} (expected {})",
+            header.version, TRACE_VERSION
+        )));
+    }
+    if header.memory_limit > MEMORY_SIZE {
+        return Err(invalid(format!("memory limit {} exceeds {} nibbles", header.memory_limit, MEMORY_SIZE)));
+    }
+    Ok(())
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn write_varint(out: &mut impl Write, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            return out.write_all(&[byte]);
+        }
+        out.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(r: &mut &[u8]) -> io::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_u8(r)?;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("varint overflow".to_string()))
+}
+
+fn read_u8(r: &mut &[u8]) -> io::Result<u8> {
+    let mut buf = [0u8; 1];
+    r.read_exact(&mut buf)?;
+    Ok(buf[0])
+}
+
+fn read_bytes(r: &mut &[u8], len: usize) -> io::Result<Vec<u8>> {
+    // Lengths come from the file; check them before allocating
+    if len > r.len() {
+        return Err(invalid(format!("declared length {} exceeds the {} bytes left", len, r.len())));
+    }
+    let mut buf = vec![0u8; len];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// First point where a replay disagrees with the trace
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Divergence {
+    /// Zero-based index of the diverging step record
+    pub step: usize,
+    pub pc: usize,
+    pub field: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "step {} (pc {}): {} expected {} but got {}",
+            self.step, self.pc, self.field, self.expected, self.actual
+        )
+    }
+}
+
+/// Outcome of replaying a trace
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReplayReport {
+    pub steps_verified: usize,
+    pub program_matches_header: bool,
+    pub divergence: Option<Divergence>,
+}
+
+/// Re-execute `program` under the trace's limits and compare record by record
+pub fn replay(trace: &Trace, program: Vec<u8>) -> ReplayReport {
+    let program_matches_header = program.iter().map(|&v| value_to_glyph(v)).eq(trace.header.glyph_stream.chars());
+    let mut vm = TetragramMicroVM::new(program)
+        .with_gas_limit(trace.header.gas_limit)
+        .with_memory_limit(trace.header.memory_limit);
+
+    let report = |steps_verified, divergence| ReplayReport { steps_verified, program_matches_header, divergence };
+
+    for (step, expected) in trace.records.iter().enumerate() {
+        let diverge = |field: &str, expected: String, actual: String| Divergence {
+            step,
+            pc: expected_pc(trace, step),
+            field: field.to_string(),
+            expected,
+            actual,
+        };
+        let actual = match record_step(&mut vm) {
+            Ok(Some(actual)) => actual,
+            Ok(None) => return report(step, Some(diverge("execution", "step".into(), "halted".into()))),
+            Err(e) => return report(step, Some(diverge("execution", "step".into(), format!("fault: {}", e)))),
+        };
+        if let Some((field, e, a)) = first_difference(expected, &actual) {
+            return report(step, Some(diverge(field, e, a)));
+        }
+    }
+
+    let steps = trace.records.len();
+    let fault = record_step(&mut vm);
+    let end_divergence = |field: &str, expected: String, actual: String| Divergence {
+        step: steps,
+        pc: vm.pc(),
+        field: field.to_string(),
+        expected,
+        actual,
+    };
+    let fault = match fault {
+        Ok(None) => None,
+        Ok(Some(extra)) => {
+            return report(steps, Some(end_divergence("execution", "end of trace".into(), format!("{} executed", extra.opcode))));
+        }
+        Err(e) => Some(e.to_string()),
+    };
+    if let Some(end) = &trace.end {
+        if end.final_pc != vm.pc() {
+            return report(steps, Some(end_divergence("final_pc", end.final_pc.to_string(), vm.pc().to_string())));
+        }
+        if end.gas_consumed != vm.gas_consumed() {
+            return report(steps, Some(end_divergence("gas_consumed", end.gas_consumed.to_string(), vm.gas_consumed().to_string())));
+        }
+        if end.fault != fault {
+            return report(steps, Some(end_divergence("fault", format!("{:?}", end.fault), format!("{:?}", fault))));
+        }
+    }
+    report(steps, None)
+}
+
+fn expected_pc(trace: &Trace, step: usize) -> usize {
+    trace.records.get(step).map(|r| r.pc).unwrap_or_default()
+}
+
+fn first_difference(expected: &TraceRecord, actual: &TraceRecord) -> Option<(&'static str, String, String)> {
+    macro_rules! check {
+        ($field:ident) => {
+            if expected.$field != actual.$field {
+                return Some((stringify!($field), format!("{:?}", expected.$field), format!("{:?}", actual.$field)));
+            }
+        };
+    }
+    check!(pc);
+    check!(glyph);
+    check!(opcode);
+    check!(operands);
+    check!(gas_before);
+    check!(registers);
+    check!(memory);
+    check!(status);
+    check!(output);
+    check!(gas_after);
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // CONST4 R0, 80; ST4 [3], R0; CMP4 R0, R1; PUSHOUT R0; BR4 AL @99 (faults)
+    fn sample_vm() -> TetragramMicroVM {
+        TetragramMicroVM::new(vec![12, 0, 80, 2, 3, 0, 9, 0, 1, 11, 0, 10, 0, 80])
+    }
+
+    #[test]
+    fn test_record_deltas() {
+        let (result, trace) = record_run(&mut sample_vm());
+        assert_eq!(trace.records.len(), 4);
+        assert!(result.fault.is_some());
+
+        let st4 = &trace.records[1];
+        assert_eq!(st4.opcode, Opcode::St4);
+        assert_eq!(st4.memory, vec![CellDelta { index: 3, before: [0; 4], after: [1; 4] }]);
+        assert!(st4.registers.is_empty());
+        assert_eq!((st4.gas_before, st4.gas_after), (3, 6));
+        assert_eq!(trace.records[2].status.as_ref().unwrap().after, [1; 4]);
+        assert_eq!(trace.records[3].output, Some(80));
+        assert!(trace.end.as_ref().unwrap().fault.is_some());
+    }
+
+    #[test]
+    fn test_roundtrip_both_formats() {
+        let (_, trace) = record_run(&mut sample_vm());
+        for format in [TraceFormat::Jsonl, TraceFormat::Binary] {
+            let mut buf = Vec::new();
+            trace.write_to(&mut buf, format).unwrap();
+            assert_eq!(Trace::read_from(&buf).unwrap(), trace, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_replay_reports_first_divergence() {
+        let (_, trace) = record_run(&mut sample_vm());
+        let program = sample_vm().program().to_vec();
+        let report = replay(&trace, program.clone());
+        assert!(report.divergence.is_none());
+        assert_eq!(report.steps_verified, 4);
+
+        // Change the stored constant: CONST4 R0, 79
+        let mut tampered = program;
+        tampered[2] = 79;
+        let report = replay(&trace, tampered);
+        assert!(!report.program_matches_header);
+        let divergence = report.divergence.unwrap();
+        assert_eq!((divergence.step, divergence.field.as_str()), (0, "operands"));
+    }
+
+    #[test]
+    fn test_rejects_unknown_version() {
+        let line = r#"{"type":"header","format":"tetragram-trace","version":9,"glyph_stream":"","gas_limit":1,"memory_limit":81}"#;
+        assert!(Trace::read_from(line.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_rejects_huge_declared_lengths() {
+        // Version 1, gas 0, memory 0, then a program length of 2^62
+        let mut program = b"TGTR\x01\x00\x00".to_vec();
+        program.extend([0x80; 8]);
+        program.push(0x40);
+        let err = Trace::read_from(&program).unwrap_err();
+        assert!(err.to_string().contains("exceeds"), "{}", err);
+
+        // Same for the fault message of the end record
+        let (_, trace) = record_run(&mut sample_vm());
+        let mut fault = Vec::new();
+        trace.write_to(&mut fault, TraceFormat::Binary).unwrap();
+        fault.extend([TAG_END, 0, 0, 0x80, 0x80, 0x80, 0x80, 0x08]);
+        assert!(Trace::read_from(&fault).unwrap_err().to_string().contains("exceeds"));
+    }
+}
@@ -0,0 +1,291 @@
+//! Opcode-aware validation of Supreme Mystery (.sm) glyph programs
+//!
+//! The validator decodes the glyph stream linearly, the same way the VM
+//! fetches instructions, and checks every operand against its kind. Unlike
+//! the VM decoder it keeps going after a problem, so one run reports every
+//! diagnostic in the file. Each diagnostic carries the source line:column,
+//! the glyph index, a severity and a stable code:
+//!
+//! | Code   | Severity | Meaning                                            |
+//! |--------|----------|----------------------------------------------------|
+//! | `E001` | error    | character is not a tetragram glyph                 |
+//! | `E002` | error    | glyph value is not an opcode                       |
+//! | `E003` | error    | instruction is missing operands at end of stream   |
+//! | `E004` | error    | register operand outside R0-R8                     |
+//! | `E005` | error    | branch condition outside AL..GE                    |
+//! | `E006` | error    | memory address outside the memory limit            |
+//! | `E007` | error    | branch target past the end of the program          |
+//! | `W001` | warning  | branch target lands inside another instruction     |
+//! | `W002` | warning  | rotation amount of 4 or more wraps modulo 4        |
+//! | `W003` | warning  | program contains no glyphs                         |
+
+use crate::tetragram_vm::{glyph_to_value, Condition, Instruction, Opcode, OperandKind, MEMORY_SIZE, REGISTER_COUNT};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// Diagnostic severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// Diagnostic codes with their default severity and a one-line description
+pub const RULES: &[(&str, Severity, &str)] = &[
+    ("E001", Severity::Error, "Character is not a tetragram glyph"),
+    ("E002", Severity::Error, "Glyph value is not an opcode"),
+    ("E003", Severity::Error, "Instruction is missing operands at end of stream"),
+    ("E004", Severity::Error, "Register operand outside R0-R8"),
+    ("E005", Severity::Error, "Branch condition outside AL..GE"),
+    ("E006", Severity::Error, "Memory address outside the memory limit"),
+    ("E007", Severity::Error, "Branch target past the end of the program"),
+    ("W001", Severity::Warning, "Branch target lands inside another instruction"),
+    ("W002", Severity::Warning, "Rotation amount of 4 or more wraps modulo 4"),
+    ("W003", Severity::Warning, "Program contains no glyphs"),
+    // Static analysis findings reported by `tetragram analyze` (see tetragram_cfg)
+    ("A001", Severity::Warning, "Glyphs are unreachable from the entry point"),
+    ("A002", Severity::Warning, "Loop never exits and never executes PUSHOUT"),
+    ("A003", Severity::Warning, "Register may be read before it is written"),
+    ("A004", Severity::Warning, "Stored value is never loaded"),
+    ("A005", Severity::Warning, "Memory may be loaded before it is stored"),
+];
+
+/// A validation finding at a source position
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    /// 1-based source line and column (in characters)
+    pub line: usize,
+    pub column: usize,
+    /// Index in the glyph stream; for `E001` the index the character would have had
+    pub glyph_index: usize,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}[{}] glyph {}: {}",
+            self.line, self.column, self.severity, self.code, self.glyph_index, self.message
+        )
+    }
+}
+
+/// Result of validating a source file
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    /// Glyph values, excluding characters rejected with `E001`
+    pub glyphs: Vec<u8>,
+    /// Instructions that decoded with valid operands, in stream order
+    pub instructions: Vec<Instruction>,
+    pub diagnostics: Vec<Diagnostic>,
+    /// 1-based (line, column) of every glyph
+    pub positions: Vec<(usize, usize)>,
+}
+
+impl ValidationReport {
+    pub fn count(&self, severity: Severity) -> usize {
+        self.diagnostics.iter().filter(|d| d.severity == severity).count()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.count(Severity::Error) == 0
+    }
+
+    /// Build a diagnostic for a registered rule at a glyph index
+    pub fn diagnostic(&self, code: &'static str, glyph_index: usize, message: String) -> Diagnostic {
+        let severity = RULES.iter().find(|(c, _, _)| *c == code).map_or(Severity::Warning, |rule| rule.1);
+        let (line, column) = self.positions.get(glyph_index).or(self.positions.last()).copied().unwrap_or((1, 1));
+        Diagnostic { code, severity, message, line, column, glyph_index }
+    }
+}
+
+/// Validate glyph source against a memory limit (at most 81 nibbles)
+pub fn validate_source(source: &str, memory_limit: usize) -> ValidationReport {
+    let memory_limit = memory_limit.min(MEMORY_SIZE);
+    let mut report = ValidationReport::default();
+    let mut positions = Vec::new();
+    let mut last_position = (1, 1);
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let code = match line.find("//") {
+            Some(idx) => &line[..idx],
+            None => line,
+        };
+        for (col_idx, ch) in code.chars().enumerate() {
+            let position = (line_idx + 1, col_idx + 1);
+            last_position = position;
+            if ch.is_whitespace() || ch.is_control() {
+                continue;
+            }
+            match glyph_to_value(ch) {
+                Some(value) => {
+                    report.glyphs.push(value);
+                    positions.push(position);
+                }
+                None => report.diagnostics.push(Diagnostic {
+                    code: "E001",
+                    severity: Severity::Error,
+                    message: format!("'{}' (U+{:04X}) is not a tetragram glyph", ch, ch as u32),
+                    line: position.0,
+                    column: position.1,
+                    glyph_index: report.glyphs.len(),
+                }),
+            }
+        }
+    }
+
+    let glyphs = &report.glyphs;
+    let mut diagnostics = Vec::new();
+    let mut push = |code, severity, index: usize, message: String| {
+        let (line, column) = positions.get(index).copied().unwrap_or(last_position);
+        diagnostics.push(Diagnostic { code, severity, message, line, column, glyph_index: index });
+    };
+
+    if glyphs.is_empty() {
+        push("W003", Severity::Warning, 0, "program contains no glyphs".to_string());
+    }
+
+    let mut starts = BTreeSet::new();
+    let mut branches = Vec::new();
+    let mut pc = 0;
+    while pc < glyphs.len() {
+        let Some(opcode) = Opcode::from_value(glyphs[pc]) else {
+            push("E002", Severity::Error, pc, format!("glyph value {} is not an opcode", glyphs[pc]));
+            pc += 1;
+            continue;
+        };
+        starts.insert(pc);
+        let available = glyphs.len() - pc - 1;
+        if available < opcode.operand_count() {
+            push(
+                "E003",
+                Severity::Error,
+                pc,
+                format!("{} expects {} operand(s), found {}", opcode.mnemonic(), opcode.operand_count(), available),
+            );
+            break;
+        }
+
+        let operands = glyphs[pc + 1..pc + 1 + opcode.operand_count()].to_vec();
+        let mut valid = true;
+        for (offset, (kind, &value)) in opcode.operand_kinds().iter().zip(&operands).enumerate() {
+            let at = pc + 1 + offset;
+            let mnemonic = opcode.mnemonic();
+            match kind {
+                OperandKind::Register if value as usize >= REGISTER_COUNT => {
+                    push("E004", Severity::Error, at, format!("{} register R{} is outside R0-R8", mnemonic, value));
+                    valid = false;
+                }
+                OperandKind::Condition if Condition::from_value(value).is_none() => {
+                    push("E005", Severity::Error, at, format!("{} condition {} is outside AL..GE (0-6)", mnemonic, value));
+                    valid = false;
+                }
+                OperandKind::Address if value as usize >= memory_limit => {
+                    push(
+                        "E006",
+                        Severity::Error,
+                        at,
+                        format!("{} address [{}] is outside memory (limit {})", mnemonic, value, memory_limit),
+                    );
+                    valid = false;
+                }
+                OperandKind::Rotation if value >= 4 => {
+                    push("W002", Severity::Warning, at, format!("ROT4 by {} wraps to {}", value, value % 4));
+                }
+                OperandKind::Target => branches.push((pc, at, value as usize)),
+                _ => {}
+            }
+        }
+
+        let instruction = Instruction { pc, opcode, operands };
+        pc = instruction.next_pc();
+        if valid {
+            report.instructions.push(instruction);
+        }
+    }
+
+    // Branch targets are checked once every instruction boundary is known
+    for (pc, at, target) in branches {
+        if target > glyphs.len() {
+            push(
+                "E007",
+                Severity::Error,
+                at,
+                format!("BR4 at {} targets {}, past the end of the program ({} glyphs)", pc, target, glyphs.len()),
+            );
+        } else if target < glyphs.len() && !starts.contains(&target) {
+            push("W001", Severity::Warning, at, format!("BR4 at {} targets {}, inside another instruction", pc, target));
+        }
+    }
+
+    report.diagnostics.extend(diagnostics);
+    report.positions = positions;
+    report.diagnostics.sort_by_key(|d| (d.line, d.column, d.glyph_index));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tetragram_vm::value_to_glyph;
+
+    fn source(values: &[u8]) -> String {
+        values.iter().map(|&v| value_to_glyph(v)).collect()
+    }
+
+    fn codes(report: &ValidationReport) -> Vec<&'static str> {
+        report.diagnostics.iter().map(|d| d.code).collect()
+    }
+
+    #[test]
+    fn test_valid_program_has_no_diagnostics() {
+        // CONST4 R0, 5; PUSHOUT R0; BR4 AL, 8 (end of program)
+        let report = validate_source(&source(&[12, 0, 5, 11, 0, 10, 0, 8]), MEMORY_SIZE);
+        assert!(report.is_valid());
+        assert!(report.diagnostics.is_empty());
+        assert_eq!(report.instructions.len(), 3);
+    }
+
+    #[test]
+    fn test_operand_errors_are_all_reported() {
+        // LD4 R9, [3]; ROT4 R0, 5; BR4 9, 40; BR4 AL
+        let report = validate_source(&source(&[1, 9, 3, 4, 0, 5, 10, 9, 40, 10, 0]), MEMORY_SIZE);
+        assert_eq!(codes(&report), vec!["E004", "W002", "E005", "E007", "E003"]);
+        assert_eq!(report.diagnostics[0].glyph_index, 1);
+        assert_eq!(report.instructions.len(), 1);
+    }
+
+    #[test]
+    fn test_positions_addresses_and_branch_boundaries() {
+        let text = format!("// header\n{}\n  {} x", source(&[2, 50, 0]), source(&[10, 0, 1]));
+        let report = validate_source(&text, 27);
+        assert_eq!(codes(&report), vec!["E006", "W001", "E001"]);
+        assert_eq!((report.diagnostics[0].line, report.diagnostics[0].column), (2, 2));
+        assert_eq!((report.diagnostics[1].line, report.diagnostics[1].column), (3, 5));
+        assert_eq!((report.diagnostics[2].line, report.diagnostics[2].column, report.diagnostics[2].glyph_index), (3, 7, 6));
+        assert_eq!(validate_source("", MEMORY_SIZE).diagnostics[0].code, "W003");
+    }
+
+    #[test]
+    fn test_rules_cover_emitted_codes() {
+        let report = validate_source(&source(&[1, 9, 3, 4, 0, 5, 10, 9, 40, 10, 0]), MEMORY_SIZE);
+        for diagnostic in &report.diagnostics {
+            let rule = RULES.iter().find(|(code, _, _)| *code == diagnostic.code).expect("rule registered");
+            assert_eq!(rule.1, diagnostic.severity);
+        }
+    }
+}
@@ -0,0 +1,782 @@
+//! TetragramMicroVM interpreter
+//!
+//! Self-contained interpreter for the Supreme Mystery micro-ISA so that `.sm`
+//! programs can be executed without the private runtime.
+//!
+//! Each glyph (U+1D306..=U+1D356) carries a value 0-80, i.e. one 4-trit nibble.
+//! An instruction is an opcode glyph followed by its operand glyphs, and the
+//! program counter indexes glyphs in the stream.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// First tetragram glyph (value 0)
+pub const GLYPH_BASE: u32 = 0x1D306;
+/// Number of distinct tetragram glyphs (3^4)
+pub const GLYPH_COUNT: u8 = 81;
+/// General-purpose registers R0-R8
+pub const REGISTER_COUNT: usize = 9;
+/// Memory size in nibbles
+pub const MEMORY_SIZE: usize = 81;
+/// Default gas limit for `tetragram execute`
+pub const DEFAULT_GAS_LIMIT: u64 = 10_000;
+
+/// A 4-trit word, most significant trit first
+pub type Nibble = [i8; 4];
+
+/// Parse a `--memory-limit` nibble count, rejecting limits the VM cannot honour
+pub fn parse_memory_limit(s: &str) -> Result<usize, String> {
+    let limit: usize = s.parse().map_err(|_| format!("expected a nibble count, got `{}`", s))?;
+    if limit > MEMORY_SIZE {
+        return Err(format!("memory limit {} exceeds the {}-nibble address space", limit, MEMORY_SIZE));
+    }
+    Ok(limit)
+}
+
+/// Map a glyph to its value 0-80
+pub fn glyph_to_value(ch: char) -> Option<u8> {
+    let code = ch as u32;
+    if (GLYPH_BASE..GLYPH_BASE + GLYPH_COUNT as u32).contains(&code) {
+        Some((code - GLYPH_BASE) as u8)
+    } else {
+        None
+    }
+}
+
+/// Map a value 0-80 to its glyph
+pub fn value_to_glyph(value: u8) -> char {
+    std::char::from_u32(GLYPH_BASE + (value % GLYPH_COUNT) as u32).unwrap()
+}
+
+/// Unpack a glyph value into balanced trits (digit -1→0, 0→1, 1→2)
+pub fn value_to_nibble(value: u8) -> Nibble {
+    let mut nibble = [0i8; 4];
+    let mut v = value % GLYPH_COUNT;
+    for trit in nibble.iter_mut().rev() {
+        *trit = (v % 3) as i8 - 1;
+        v /= 3;
+    }
+    nibble
+}
+
+/// Pack balanced trits back into a glyph value
+pub fn nibble_to_value(nibble: &Nibble) -> u8 {
+    nibble.iter().fold(0u8, |acc, &t| acc * 3 + (t + 1) as u8)
+}
+
+/// Signed integer value of a nibble (-40..=40)
+pub fn nibble_to_int(nibble: &Nibble) -> i8 {
+    nibble.iter().fold(0i8, |acc, &t| acc * 3 + t)
+}
+
+/// Parse a glyph stream, skipping whitespace and `//` line comments
+pub fn parse_glyph_stream(source: &str) -> Result<Vec<u8>, VmError> {
+    let mut program = Vec::new();
+    for line in source.lines() {
+        let code = match line.find("//") {
+            Some(idx) => &line[..idx],
+            None => line,
+        };
+        for ch in code.chars() {
+            if ch.is_whitespace() || ch.is_control() {
+                continue;
+            }
+            match glyph_to_value(ch) {
+                Some(value) => program.push(value),
+                None => return Err(VmError::InvalidGlyph { index: program.len(), character: ch }),
+            }
+        }
+    }
+    Ok(program)
+}
+
+/// Micro-opcodes of the TetragramMicroVM
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Opcode {
+    Nop,
+    Ld4,
+    St4,
+    Mux4,
+    Rot4,
+    Xor4,
+    Inv4,
+    Shl4,
+    Shr4,
+    Cmp4,
+    Br4,
+    PushOut,
+    Const4,
+}
+
+/// Kind of value an operand glyph carries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum OperandKind {
+    /// Register index 0-8
+    Register,
+    /// Memory address 0-80
+    Address,
+    /// Rotation amount (taken modulo 4)
+    Rotation,
+    /// Branch condition 0-6
+    Condition,
+    /// Absolute glyph index
+    Target,
+    /// Immediate nibble
+    Immediate,
+}
+
+impl Opcode {
+    /// All opcodes in encoding order
+    pub const ALL: [Opcode; 13] = [
+        Opcode::Nop,
+        Opcode::Ld4,
+        Opcode::St4,
+        Opcode::Mux4,
+        Opcode::Rot4,
+        Opcode::Xor4,
+        Opcode::Inv4,
+        Opcode::Shl4,
+        Opcode::Shr4,
+        Opcode::Cmp4,
+        Opcode::Br4,
+        Opcode::PushOut,
+        Opcode::Const4,
+    ];
+
+    /// Decode an opcode glyph value
+    pub fn from_value(value: u8) -> Option<Self> {
+        Self::ALL.get(value as usize).copied()
+    }
+
+    /// Glyph value of this opcode
+    pub fn value(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Assembly mnemonic
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Opcode::Nop => "NOP",
+            Opcode::Ld4 => "LD4",
+            Opcode::St4 => "ST4",
+            Opcode::Mux4 => "MUX4",
+            Opcode::Rot4 => "ROT4",
+            Opcode::Xor4 => "XOR4",
+            Opcode::Inv4 => "INV4",
+            Opcode::Shl4 => "SHL4",
+            Opcode::Shr4 => "SHR4",
+            Opcode::Cmp4 => "CMP4",
+            Opcode::Br4 => "BR4",
+            Opcode::PushOut => "PUSHOUT",
+            Opcode::Const4 => "CONST4",
+        }
+    }
+
+    /// Look up an opcode by mnemonic (case-insensitive)
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|op| op.mnemonic().eq_ignore_ascii_case(mnemonic))
+    }
+
+    /// Operand layout following the opcode glyph
+    pub fn operand_kinds(&self) -> &'static [OperandKind] {
+        use OperandKind::*;
+        match self {
+            Opcode::Nop => &[],
+            Opcode::Ld4 => &[Register, Address],
+            Opcode::St4 => &[Address, Register],
+            Opcode::Mux4 | Opcode::Xor4 | Opcode::Cmp4 => &[Register, Register],
+            Opcode::Rot4 => &[Register, Rotation],
+            Opcode::Inv4 | Opcode::Shl4 | Opcode::Shr4 | Opcode::PushOut => &[Register],
+            Opcode::Br4 => &[Condition, Target],
+            Opcode::Const4 => &[Register, Immediate],
+        }
+    }
+
+    /// Number of operand glyphs
+    pub fn operand_count(&self) -> usize {
+        self.operand_kinds().len()
+    }
+
+    /// Tesla 3-6-9 gas cost (NOP 1, BR4 9, everything else 3)
+    pub fn gas_cost(&self) -> u64 {
+        match self {
+            Opcode::Nop => 1,
+            Opcode::Br4 => 9,
+            _ => 3,
+        }
+    }
+}
+
+impl fmt::Display for Opcode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.mnemonic())
+    }
+}
+
+/// BR4 branch conditions, evaluated against the sign of the status register
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Condition {
+    Always,
+    Lt,
+    Eq,
+    Gt,
+    Ne,
+    Le,
+    Ge,
+}
+
+impl Condition {
+    pub const ALL: [Condition; 7] = [
+        Condition::Always,
+        Condition::Lt,
+        Condition::Eq,
+        Condition::Gt,
+        Condition::Ne,
+        Condition::Le,
+        Condition::Ge,
+    ];
+
+    pub fn from_value(value: u8) -> Option<Self> {
+        Self::ALL.get(value as usize).copied()
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Condition::Always => "AL",
+            Condition::Lt => "LT",
+            Condition::Eq => "EQ",
+            Condition::Gt => "GT",
+            Condition::Ne => "NE",
+            Condition::Le => "LE",
+            Condition::Ge => "GE",
+        }
+    }
+
+    /// Whether the branch is taken for a status sign of -1, 0 or 1
+    pub fn holds(&self, sign: i8) -> bool {
+        match self {
+            Condition::Always => true,
+            Condition::Lt => sign < 0,
+            Condition::Eq => sign == 0,
+            Condition::Gt => sign > 0,
+            Condition::Ne => sign != 0,
+            Condition::Le => sign <= 0,
+            Condition::Ge => sign >= 0,
+        }
+    }
+}
+
+/// A decoded instruction at a glyph index
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Instruction {
+    pub pc: usize,
+    pub opcode: Opcode,
+    pub operands: Vec<u8>,
+}
+
+impl Instruction {
+    /// Number of glyphs occupied by the instruction
+    pub fn size(&self) -> usize {
+        1 + self.operands.len()
+    }
+
+    /// Glyph index of the following instruction
+    pub fn next_pc(&self) -> usize {
+        self.pc + self.size()
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.opcode)?;
+        for (i, (kind, value)) in self.opcode.operand_kinds().iter().zip(&self.operands).enumerate() {
+            let sep = if i == 0 { " " } else { ", " };
+            match kind {
+                OperandKind::Register => write!(f, "{}R{}", sep, value)?,
+                OperandKind::Address => write!(f, "{}[{}]", sep, value)?,
+                OperandKind::Condition => match Condition::from_value(*value) {
+                    Some(cond) => write!(f, "{}{}", sep, cond.mnemonic())?,
+                    None => write!(f, "{}?{}", sep, value)?,
+                },
+                OperandKind::Target => write!(f, "{}@{}", sep, value)?,
+                OperandKind::Rotation | OperandKind::Immediate => write!(f, "{}{}", sep, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decode the instruction starting at `pc`
+pub fn decode_instruction(program: &[u8], pc: usize) -> Result<Instruction, VmError> {
+    let value = *program.get(pc).ok_or(VmError::PcOutOfRange { pc })?;
+    let opcode = Opcode::from_value(value).ok_or(VmError::InvalidOpcode { pc, value })?;
+    let end = pc + 1 + opcode.operand_count();
+    if end > program.len() {
+        return Err(VmError::MissingOperand { pc, opcode });
+    }
+    let operands = program[pc + 1..end].to_vec();
+    for (kind, &operand) in opcode.operand_kinds().iter().zip(&operands) {
+        match kind {
+            OperandKind::Register if operand as usize >= REGISTER_COUNT => {
+                return Err(VmError::InvalidRegister { pc, value: operand });
+            }
+            OperandKind::Condition if Condition::from_value(operand).is_none() => {
+                return Err(VmError::InvalidCondition { pc, value: operand });
+            }
+            _ => {}
+        }
+    }
+    Ok(Instruction { pc, opcode, operands })
+}
+
+/// Errors raised while loading or executing a glyph program
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum VmError {
+    InvalidGlyph { index: usize, character: char },
+    InvalidOpcode { pc: usize, value: u8 },
+    MissingOperand { pc: usize, opcode: Opcode },
+    InvalidRegister { pc: usize, value: u8 },
+    InvalidCondition { pc: usize, value: u8 },
+    InvalidBranchTarget { pc: usize, target: usize },
+    PcOutOfRange { pc: usize },
+    OutOfGas { pc: usize, required: u64, remaining: u64 },
+    OutOfMemory { pc: usize, address: usize, limit: usize },
+}
+
+impl VmError {
+    /// Program counter (or glyph index) where the error occurred
+    pub fn pc(&self) -> usize {
+        match self {
+            VmError::InvalidGlyph { index, .. } => *index,
+            VmError::InvalidOpcode { pc, .. }
+            | VmError::MissingOperand { pc, .. }
+            | VmError::InvalidRegister { pc, .. }
+            | VmError::InvalidCondition { pc, .. }
+            | VmError::InvalidBranchTarget { pc, .. }
+            | VmError::PcOutOfRange { pc }
+            | VmError::OutOfGas { pc, .. }
+            | VmError::OutOfMemory { pc, .. } => *pc,
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VmError::InvalidGlyph { index, character } => {
+                write!(f, "invalid glyph '{}' (U+{:04X}) at index {}", character, *character as u32, index)
+            }
+            VmError::InvalidOpcode { pc, value } => write!(f, "invalid opcode {} at pc {}", value, pc),
+            VmError::MissingOperand { pc, opcode } => write!(f, "{} at pc {} is missing operands", opcode, pc),
+            VmError::InvalidRegister { pc, value } => write!(f, "invalid register R{} at pc {}", value, pc),
+            VmError::InvalidCondition { pc, value } => write!(f, "invalid branch condition {} at pc {}", value, pc),
+            VmError::InvalidBranchTarget { pc, target } => {
+                write!(f, "branch target {} out of range at pc {}", target, pc)
+            }
+            VmError::PcOutOfRange { pc } => write!(f, "program counter {} out of range", pc),
+            VmError::OutOfGas { pc, required, remaining } => {
+                write!(f, "out of gas at pc {}: needs {} gas, {} remaining", pc, required, remaining)
+            }
+            VmError::OutOfMemory { pc, address, limit } => {
+                write!(f, "out of memory at pc {}: nibble {} exceeds limit of {}", pc, address, limit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VmError {}
+
+/// Final machine state after a run
+#[derive(Debug, Clone, Serialize)]
+pub struct ExecutionResult {
+    pub glyph_stream: String,
+    pub register_state: Vec<Nibble>,
+    pub memory_state: Vec<Nibble>,
+    pub status_register: Nibble,
+    pub output_buffer: Vec<u8>,
+    pub gas_consumed: u64,
+    pub gas_limit: u64,
+    pub memory_limit: usize,
+    pub operations_executed: u64,
+    pub final_pc: usize,
+    pub execution_time: Duration,
+    /// Error that stopped execution, if any
+    pub fault: Option<VmError>,
+    pub errors: Vec<String>,
+}
+
+/// State a step can change, for rewinding without copying the program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmSnapshot {
+    registers: [Nibble; REGISTER_COUNT],
+    memory: Vec<Nibble>,
+    status: Nibble,
+    pc: usize,
+    gas_consumed: u64,
+    operations_executed: u64,
+    /// Output is append-only, so its length is enough
+    output_len: usize,
+}
+
+impl VmSnapshot {
+    pub fn memory(&self) -> &[Nibble] {
+        &self.memory
+    }
+}
+
+/// The TetragramMicroVM
+#[derive(Debug, Clone)]
+pub struct TetragramMicroVM {
+    program: Vec<u8>,
+    registers: [Nibble; REGISTER_COUNT],
+    memory: Vec<Nibble>,
+    status: Nibble,
+    pc: usize,
+    gas_consumed: u64,
+    gas_limit: u64,
+    operations_executed: u64,
+    output: Vec<u8>,
+}
+
+impl TetragramMicroVM {
+    /// Create a VM for a decoded glyph program
+    pub fn new(program: Vec<u8>) -> Self {
+        Self {
+            program,
+            registers: [[0; 4]; REGISTER_COUNT],
+            memory: vec![[0; 4]; MEMORY_SIZE],
+            status: [0; 4],
+            pc: 0,
+            gas_consumed: 0,
+            gas_limit: DEFAULT_GAS_LIMIT,
+            operations_executed: 0,
+            output: Vec::new(),
+        }
+    }
+
+    /// Create a VM from glyph source text
+    pub fn from_source(source: &str) -> Result<Self, VmError> {
+        Ok(Self::new(parse_glyph_stream(source)?))
+    }
+
+    /// Set the gas budget; an instruction that would exceed it faults with `OutOfGas`
+    pub fn with_gas_limit(mut self, gas_limit: u64) -> Self {
+        self.gas_limit = gas_limit;
+        self
+    }
+
+    /// Bound addressable memory to the first `memory_limit` nibbles
+    ///
+    /// Panics above [`MEMORY_SIZE`]; check user input with [`parse_memory_limit`].
+    pub fn with_memory_limit(mut self, memory_limit: usize) -> Self {
+        assert!(memory_limit <= MEMORY_SIZE, "memory limit {} exceeds {} nibbles", memory_limit, MEMORY_SIZE);
+        self.memory.truncate(memory_limit);
+        self
+    }
+
+    pub fn program(&self) -> &[u8] {
+        &self.program
+    }
+
+    pub fn registers(&self) -> &[Nibble; REGISTER_COUNT] {
+        &self.registers
+    }
+
+    pub fn memory(&self) -> &[Nibble] {
+        &self.memory
+    }
+
+    pub fn status(&self) -> Nibble {
+        self.status
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn gas_consumed(&self) -> u64 {
+        self.gas_consumed
+    }
+
+    pub fn gas_limit(&self) -> u64 {
+        self.gas_limit
+    }
+
+    pub fn memory_limit(&self) -> usize {
+        self.memory.len()
+    }
+
+    pub fn operations_executed(&self) -> u64 {
+        self.operations_executed
+    }
+
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+
+    /// Capture the state that [`step`](Self::step) can change
+    pub fn snapshot(&self) -> VmSnapshot {
+        VmSnapshot {
+            registers: self.registers,
+            memory: self.memory.clone(),
+            status: self.status,
+            pc: self.pc,
+            gas_consumed: self.gas_consumed,
+            operations_executed: self.operations_executed,
+            output_len: self.output.len(),
+        }
+    }
+
+    /// Return to a snapshot taken from this VM, dropping output pushed since
+    pub fn restore(&mut self, snapshot: &VmSnapshot) {
+        self.registers = snapshot.registers;
+        self.memory.clone_from(&snapshot.memory);
+        self.status = snapshot.status;
+        self.pc = snapshot.pc;
+        self.gas_consumed = snapshot.gas_consumed;
+        self.operations_executed = snapshot.operations_executed;
+        self.output.truncate(snapshot.output_len);
+    }
+
+    /// True once the program counter has run off the end of the stream
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+
+    /// Execute one instruction; returns `None` when already halted
+    pub fn step(&mut self) -> Result<Option<Instruction>, VmError> {
+        if self.is_halted() {
+            return Ok(None);
+        }
+        let instr = decode_instruction(&self.program, self.pc)?;
+        let ops = &instr.operands;
+        let mut next_pc = instr.next_pc();
+
+        // Charge gas up front so a failing instruction leaves state untouched
+        let cost = instr.opcode.gas_cost();
+        let remaining = self.gas_limit.saturating_sub(self.gas_consumed);
+        if cost > remaining {
+            return Err(VmError::OutOfGas { pc: instr.pc, required: cost, remaining });
+        }
+
+        match instr.opcode {
+            Opcode::Nop => {}
+            Opcode::Ld4 => {
+                let address = self.check_address(instr.pc, ops[1])?;
+                self.registers[ops[0] as usize] = self.memory[address];
+            }
+            Opcode::St4 => {
+                let address = self.check_address(instr.pc, ops[0])?;
+                self.memory[address] = self.registers[ops[1] as usize];
+            }
+            Opcode::Mux4 => {
+                // Status trit selects per position: -1 keeps, 0 clears, +1 takes src
+                let src = self.registers[ops[1] as usize];
+                let dst = &mut self.registers[ops[0] as usize];
+                for i in 0..4 {
+                    dst[i] = match self.status[i] {
+                        -1 => dst[i],
+                        0 => 0,
+                        _ => src[i],
+                    };
+                }
+            }
+            Opcode::Rot4 => self.registers[ops[0] as usize].rotate_left(ops[1] as usize % 4),
+            Opcode::Xor4 => {
+                // Balanced ternary sum modulo 3
+                let src = self.registers[ops[1] as usize];
+                let dst = &mut self.registers[ops[0] as usize];
+                for i in 0..4 {
+                    dst[i] = ((dst[i] + src[i] + 4) % 3) - 1;
+                }
+            }
+            Opcode::Inv4 => {
+                for trit in self.registers[ops[0] as usize].iter_mut() {
+                    *trit = -*trit;
+                }
+            }
+            Opcode::Shl4 => {
+                let reg = &mut self.registers[ops[0] as usize];
+                *reg = [reg[1], reg[2], reg[3], 0];
+            }
+            Opcode::Shr4 => {
+                let reg = &mut self.registers[ops[0] as usize];
+                *reg = [0, reg[0], reg[1], reg[2]];
+            }
+            Opcode::Cmp4 => {
+                let a = self.registers[ops[0] as usize];
+                let b = self.registers[ops[1] as usize];
+                for i in 0..4 {
+                    self.status[i] = (a[i] - b[i]).signum();
+                }
+            }
+            Opcode::Br4 => {
+                let cond = Condition::from_value(ops[0]).expect("validated by decoder");
+                let target = ops[1] as usize;
+                if cond.holds(self.status_sign()) {
+                    if target > self.program.len() {
+                        return Err(VmError::InvalidBranchTarget { pc: instr.pc, target });
+                    }
+                    next_pc = target;
+                }
+            }
+            Opcode::PushOut => self.output.push(nibble_to_value(&self.registers[ops[0] as usize])),
+            Opcode::Const4 => self.registers[ops[0] as usize] = value_to_nibble(ops[1]),
+        }
+
+        self.gas_consumed += cost;
+        self.operations_executed += 1;
+        self.pc = next_pc;
+        Ok(Some(instr))
+    }
+
+    fn check_address(&self, pc: usize, address: u8) -> Result<usize, VmError> {
+        let address = address as usize;
+        if address >= self.memory.len() {
+            return Err(VmError::OutOfMemory { pc, address, limit: self.memory.len() });
+        }
+        Ok(address)
+    }
+
+    /// Sign of the status register read as a balanced-ternary number
+    pub fn status_sign(&self) -> i8 {
+        nibble_to_int(&self.status).signum()
+    }
+
+    /// Run until the program halts or faults
+    pub fn run(&mut self) -> ExecutionResult {
+        let start = Instant::now();
+        let fault = loop {
+            match self.step() {
+                Ok(Some(_)) => continue,
+                Ok(None) => break None,
+                Err(e) => break Some(e),
+            }
+        };
+        self.result(start.elapsed(), fault)
+    }
+
+    /// Snapshot the current machine state as an `ExecutionResult`
+    pub fn result(&self, execution_time: Duration, fault: Option<VmError>) -> ExecutionResult {
+        ExecutionResult {
+            glyph_stream: self.program.iter().map(|&v| value_to_glyph(v)).collect(),
+            register_state: self.registers.to_vec(),
+            memory_state: self.memory.clone(),
+            status_register: self.status,
+            output_buffer: self.output.clone(),
+            gas_consumed: self.gas_consumed,
+            gas_limit: self.gas_limit,
+            memory_limit: self.memory.len(),
+            operations_executed: self.operations_executed,
+            final_pc: self.pc,
+            execution_time,
+            errors: fault.iter().map(|e| e.to_string()).collect(),
+            fault,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn glyphs(values: &[u8]) -> String {
+        values.iter().map(|&v| value_to_glyph(v)).collect()
+    }
+
+    #[test]
+    fn test_nibble_roundtrip() {
+        for v in 0..GLYPH_COUNT {
+            assert_eq!(nibble_to_value(&value_to_nibble(v)), v);
+        }
+        assert_eq!(value_to_nibble(0), [-1, -1, -1, -1]);
+        assert_eq!(value_to_nibble(40), [0, 0, 0, 0]);
+        assert_eq!(value_to_nibble(80), [1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn test_parse_skips_comments_and_whitespace() {
+        let source = format!("// header\n{} {}\n// tail", glyphs(&[0, 1]), glyphs(&[2]));
+        assert_eq!(parse_glyph_stream(&source).unwrap(), vec![0, 1, 2]);
+        assert!(matches!(parse_glyph_stream("x"), Err(VmError::InvalidGlyph { index: 0, .. })));
+    }
+
+    #[test]
+    fn test_const_store_load_pushout() {
+        // CONST4 R0, 80; ST4 [5], R0; LD4 R1, [5]; PUSHOUT R1
+        let mut vm = TetragramMicroVM::from_source(&glyphs(&[12, 0, 80, 2, 5, 0, 1, 1, 5, 11, 1])).unwrap();
+        let result = vm.run();
+        assert!(result.errors.is_empty());
+        assert_eq!(result.register_state[1], [1, 1, 1, 1]);
+        assert_eq!(result.memory_state[5], [1, 1, 1, 1]);
+        assert_eq!(result.output_buffer, vec![80]);
+        assert_eq!(result.gas_consumed, 12);
+        assert_eq!(result.final_pc, 11);
+    }
+
+    #[test]
+    fn test_logic_ops() {
+        let mut vm = TetragramMicroVM::new(vec![12, 0, 41, 6, 0, 7, 0, 4, 0, 1]);
+        vm.run();
+        // 41 = [0,0,0,1] -> INV4 [0,0,0,-1] -> SHL4 [0,0,-1,0] -> ROT4 1 [0,-1,0,0]
+        assert_eq!(vm.registers()[0], [0, -1, 0, 0]);
+
+        let mut vm = TetragramMicroVM::new(vec![12, 0, 80, 12, 1, 80, 5, 0, 1]);
+        vm.run();
+        // 1 + 1 = -1 (mod 3)
+        assert_eq!(vm.registers()[0], [-1, -1, -1, -1]);
+    }
+
+    #[test]
+    fn test_branch_on_compare() {
+        // R0=80, R1=0, CMP4 R0,R1 (gt); BR4 GT @14; PUSHOUT R0 (skipped); PUSHOUT R1
+        let mut vm = TetragramMicroVM::new(vec![12, 0, 80, 12, 1, 0, 9, 0, 1, 10, 3, 14, 11, 0, 11, 1]);
+        let result = vm.run();
+        assert!(result.errors.is_empty(), "{:?}", result.errors);
+        assert_eq!(vm.status_sign(), 1);
+        assert_eq!(result.output_buffer, vec![0]);
+        assert_eq!(result.gas_consumed, 3 * 4 + 9);
+    }
+
+    #[test]
+    fn test_gas_limit_stops_runaway_loop() {
+        // BR4 AL @0 loops forever
+        let mut vm = TetragramMicroVM::new(vec![10, 0, 0]).with_gas_limit(100);
+        let result = vm.run();
+        assert_eq!(result.gas_consumed, 99);
+        assert_eq!(result.final_pc, 0);
+        assert_eq!(result.fault, Some(VmError::OutOfGas { pc: 0, required: 9, remaining: 1 }));
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        // CONST4 R0, 1; ST4 [27], R0
+        let program = vec![12, 0, 1, 2, 27, 0];
+        let mut vm = TetragramMicroVM::new(program.clone()).with_memory_limit(27);
+        let result = vm.run();
+        assert_eq!(result.memory_state.len(), 27);
+        assert_eq!(result.fault, Some(VmError::OutOfMemory { pc: 3, address: 27, limit: 27 }));
+        assert_eq!(result.gas_consumed, 3);
+
+        let mut vm = TetragramMicroVM::new(program).with_memory_limit(28);
+        assert!(vm.run().fault.is_none());
+
+        assert_eq!(parse_memory_limit("81"), Ok(MEMORY_SIZE));
+        assert!(parse_memory_limit("82").is_err() && parse_memory_limit("-1").is_err());
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        assert!(matches!(decode_instruction(&[13], 0), Err(VmError::InvalidOpcode { pc: 0, value: 13 })));
+        assert!(matches!(decode_instruction(&[1, 0], 0), Err(VmError::MissingOperand { pc: 0, .. })));
+        assert!(matches!(decode_instruction(&[6, 9], 0), Err(VmError::InvalidRegister { pc: 0, value: 9 })));
+        assert!(matches!(decode_instruction(&[10, 7, 0], 0), Err(VmError::InvalidCondition { .. })));
+
+        let mut vm = TetragramMicroVM::new(vec![0, 13]);
+        let result = vm.run();
+        assert_eq!(result.final_pc, 1);
+        assert_eq!(result.errors.len(), 1);
+    }
+}
@@ -0,0 +1,29 @@
+; Tritwise stream cipher round
+; XOR4 adds trits modulo 3, INV4 negates them; rotating the key between
+; blocks gives each plaintext nibble a different key schedule.
+
+.const DATA R0
+.const KEY R1
+.const SCRATCH R2
+
+        CONST4 KEY, 47
+        CONST4 DATA, 20     ; plaintext block 1
+        XOR4 DATA, KEY
+        INV4 DATA
+        PUSHOUT DATA
+
+        ROT4 KEY, 1
+        CONST4 DATA, 61     ; plaintext block 2
+        XOR4 DATA, KEY
+        INV4 DATA
+        PUSHOUT DATA
+
+        ROT4 KEY, 1
+        CONST4 DATA, 7      ; plaintext block 3
+        XOR4 DATA, KEY
+        INV4 DATA
+        PUSHOUT DATA
+
+        ; Keep the final key in memory for the next session
+        MUX4 SCRATCH, KEY
+        ST4 [80], KEY
@@ -0,0 +1,47 @@
+; Fibonacci sequence
+; The micro-ISA has no adder, so the terms that fit in one glyph value
+; (0-80) are stored to memory as a table and then streamed out.
+
+.const TERM R0
+.const TABLE 0
+
+        CONST4 TERM, 1
+        ST4 [0], TERM
+        ST4 [1], TERM
+        CONST4 TERM, 2
+        ST4 [2], TERM
+        CONST4 TERM, 3
+        ST4 [3], TERM
+        CONST4 TERM, 5
+        ST4 [4], TERM
+        CONST4 TERM, 8
+        ST4 [5], TERM
+        CONST4 TERM, 13
+        ST4 [6], TERM
+        CONST4 TERM, 21
+        ST4 [7], TERM
+        CONST4 TERM, 34
+        ST4 [8], TERM
+        CONST4 TERM, 55
+        ST4 [9], TERM
+
+        LD4 TERM, [TABLE]
+        PUSHOUT TERM
+        LD4 TERM, [1]
+        PUSHOUT TERM
+        LD4 TERM, [2]
+        PUSHOUT TERM
+        LD4 TERM, [3]
+        PUSHOUT TERM
+        LD4 TERM, [4]
+        PUSHOUT TERM
+        LD4 TERM, [5]
+        PUSHOUT TERM
+        LD4 TERM, [6]
+        PUSHOUT TERM
+        LD4 TERM, [7]
+        PUSHOUT TERM
+        LD4 TERM, [8]
+        PUSHOUT TERM
+        LD4 TERM, [9]
+        PUSHOUT TERM
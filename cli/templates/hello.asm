@@ -0,0 +1,14 @@
+; Supreme Mystery Hello World
+; Pushes the alphabet positions of H E L L O to the output buffer.
+
+.const OUT R0
+
+        CONST4 OUT, 8       ; H
+        PUSHOUT OUT
+        CONST4 OUT, 5       ; E
+        PUSHOUT OUT
+        CONST4 OUT, 12      ; L
+        PUSHOUT OUT
+        PUSHOUT OUT         ; L
+        CONST4 OUT, 15      ; O
+        PUSHOUT OUT
@@ -0,0 +1,24 @@
+; Instruction coverage test: every micro-opcode appears at least once.
+
+.const COUNTER R1
+.const ZERO R2
+
+        NOP
+        CONST4 R0, 80
+        ST4 [3], R0
+        LD4 R3, [3]
+        XOR4 R3, R0
+        INV4 R3
+        SHL4 R3
+        ROT4 R3, 2
+        CMP4 R0, R3
+        MUX4 R4, R0
+
+        CONST4 COUNTER, 80  ; 1111: four shifts until zero
+loop:   PUSHOUT COUNTER
+        SHR4 COUNTER
+        CMP4 COUNTER, ZERO
+        BR4 NE, loop
+        BR4 AL, done
+        NOP                 ; skipped
+done: